@@ -0,0 +1,360 @@
+use std::io::prelude::*;
+
+use crate::buffer::{self, Buffer, ScalarType};
+use crate::field;
+use crate::{next_line, DatasetAttributes, VTKparseError};
+
+/// A named array of attribute data
+pub struct DataArray {
+    pub name: String,
+    pub kind: DatasetAttributes,
+    pub numcomp: usize,
+    pub data: Buffer,
+    /// Name of the lookup table for `SCALARS`
+    pub lookup_table: Option<String>,
+}
+
+impl DataArray {
+    /// Number of tuples in the array
+    pub fn len(&self) -> usize {
+        self.data.len() / self.numcomp.max(1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+/// A colour table defined with `LOOKUP_TABLE name size`
+#[derive(Clone)]
+pub struct LookupTable {
+    pub name: String,
+    /// RGBA entries in [0, 1]
+    pub colours: Vec<[f32; 4]>,
+}
+
+/// Attribute arrays attached to the points and cells of a dataset
+#[derive(Default)]
+pub struct Attributes {
+    pub point: Vec<DataArray>,
+    pub cell: Vec<DataArray>,
+    pub lookup_tables: Vec<LookupTable>,
+}
+
+impl Attributes {
+    pub fn point_array(&self, name: &str) -> Option<&DataArray> {
+        self.point.iter().find(|x| x.name == name)
+    }
+
+    pub fn cell_array(&self, name: &str) -> Option<&DataArray> {
+        self.cell.iter().find(|x| x.name == name)
+    }
+
+    /// The table named by the `SCALARS` array, `None` for `default`
+    pub fn lookup_table(&self, array: &DataArray) -> Option<&LookupTable> {
+        let name = array.lookup_table.as_ref()?;
+        self.lookup_tables.iter().find(|x| &x.name == name)
+    }
+
+    /// All `SCALARS` arrays attached to the points
+    pub fn point_scalars(&self) -> impl Iterator<Item = &DataArray> {
+        self.point
+            .iter()
+            .filter(|x| x.kind == DatasetAttributes::Scalars)
+    }
+}
+
+/// Parses `POINT_DATA` and `CELL_DATA` sections until the end of the file
+pub(crate) fn parse<R: BufRead>(reader: &mut R, binary: bool) -> Result<Attributes, VTKparseError> {
+    let mut attributes = Attributes::default();
+
+    // Which of point or cell data is currently read, and the number of tuples
+    let mut section: Option<(bool, usize)> = None;
+
+    while let Some(line) = next_line(reader)? {
+        let mut words = line.split_whitespace();
+        let keyword = words.next().unwrap().to_uppercase();
+
+        if keyword == "POINT_DATA" || keyword == "CELL_DATA" {
+            let len = words
+                .next()
+                .ok_or_else(|| VTKparseError::FileFormat(format!("{} has no length", keyword)))?
+                .parse()?;
+            section = Some((keyword == "POINT_DATA", len));
+            continue;
+        }
+
+        if keyword == "LOOKUP_TABLE" {
+            let mut words = line.split_whitespace().skip(1);
+            let name = words
+                .next()
+                .ok_or_else(|| VTKparseError::FileFormat("LOOKUP_TABLE has no name".to_string()))?;
+            let size: usize = words
+                .next()
+                .ok_or_else(|| VTKparseError::FileFormat("LOOKUP_TABLE has no size".to_string()))?
+                .parse()?;
+
+            let colours = read_colours(reader, binary, buffer::count(&[size, 4])?)?;
+            attributes.lookup_tables.push(LookupTable {
+                name: name.to_string(),
+                colours: colours
+                    .chunks_exact(4)
+                    .map(|c| [c[0], c[1], c[2], c[3]])
+                    .collect(),
+            });
+            continue;
+        }
+
+        let (is_point, len) = section.ok_or_else(|| {
+            VTKparseError::FileFormat(format!(
+                "{} is not preceded by POINT_DATA or CELL_DATA",
+                keyword
+            ))
+        })?;
+
+        // The arrays of a field are attached like any other attribute
+        if keyword == "FIELD" {
+            let field = field::parse(reader, binary, &line)?;
+            if is_point {
+                attributes.point.extend(field.arrays);
+            } else {
+                attributes.cell.extend(field.arrays);
+            }
+            continue;
+        }
+
+        let name = words
+            .next()
+            .ok_or_else(|| VTKparseError::FileFormat(format!("{} has no name", keyword)))?
+            .to_string();
+
+        let (kind, numcomp, datatype) = match keyword.as_str() {
+            "SCALARS" => {
+                let datatype = words.next();
+                let numcomp = words.next().unwrap_or("1").parse()?;
+                (DatasetAttributes::Scalars, numcomp, datatype)
+            }
+            "COLOR_SCALARS" => {
+                let numcomp = words
+                    .next()
+                    .ok_or_else(|| {
+                        VTKparseError::FileFormat("Could not find number of values".to_string())
+                    })?
+                    .parse()?;
+                (DatasetAttributes::ColorScalars, numcomp, None)
+            }
+            "VECTORS" => (DatasetAttributes::Vectors, 3, words.next()),
+            "NORMALS" => (DatasetAttributes::Normals, 3, words.next()),
+            "TENSORS" => (DatasetAttributes::Tensors, 9, words.next()),
+            "TEXTURE_COORDINATES" => {
+                let numcomp = words
+                    .next()
+                    .ok_or_else(|| {
+                        VTKparseError::FileFormat("Could not find dimension".to_string())
+                    })?
+                    .parse()?;
+                (DatasetAttributes::TextureCoordinates, numcomp, words.next())
+            }
+            _ => {
+                return Err(VTKparseError::FileFormat(format!(
+                    "Unknown attribute {}",
+                    keyword
+                )))
+            }
+        };
+
+        let (data, lookup_table) = if kind == DatasetAttributes::ColorScalars {
+            (
+                Buffer::F32(read_colours(
+                    reader,
+                    binary,
+                    buffer::count(&[len, numcomp])?,
+                )?),
+                None,
+            )
+        } else {
+            let datatype = datatype
+                .ok_or_else(|| VTKparseError::FileFormat("Could not find datatype".to_string()))?;
+            let datatype = ScalarType::from_name(datatype)
+                .ok_or_else(|| VTKparseError::NotImplemented(format!("Datatype {}", datatype)))?;
+
+            let lookup_table = if kind == DatasetAttributes::Scalars {
+                let line = next_line(reader)?.unwrap_or_default();
+                if crate::keyword(&line) != "LOOKUP_TABLE" {
+                    return Err(VTKparseError::Expected("LOOKUP_TABLE".to_string()));
+                }
+                Some(
+                    line.split_whitespace()
+                        .nth(1)
+                        .unwrap_or("default")
+                        .to_string(),
+                )
+            } else {
+                None
+            };
+
+            let data = buffer::read(reader, binary, datatype, buffer::count(&[len, numcomp])?)?;
+            (data, lookup_table)
+        };
+
+        let array = DataArray {
+            name,
+            kind,
+            numcomp,
+            data,
+            lookup_table,
+        };
+
+        if is_point {
+            attributes.point.push(array);
+        } else {
+            attributes.cell.push(array);
+        }
+    }
+
+    Ok(attributes)
+}
+
+/// Colour components, stored as unsigned chars in binary files and as
+/// floats in [0, 1] in ASCII files
+fn read_colours<R: BufRead>(
+    reader: &mut R,
+    binary: bool,
+    n: usize,
+) -> Result<Vec<f32>, VTKparseError> {
+    if binary {
+        let data = buffer::read_binary(reader, ScalarType::UnsignedChar, n)?;
+        Ok(data
+            .as_u8()
+            .unwrap()
+            .iter()
+            .map(|&x| f32::from(x) / 255.0)
+            .collect())
+    } else {
+        let data = buffer::read_ascii(reader, ScalarType::Float, n)?;
+        Ok(data.as_f32().unwrap().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point_and_cell_data() {
+        let text = "POINT_DATA 2
+SCALARS density float
+LOOKUP_TABLE default
+0.5 1.5
+SCALARS flags int 2
+LOOKUP_TABLE default
+1 2 3 4
+
+VECTORS velocity double
+1 0 0 0 1 0
+CELL_DATA 1
+NORMALS n float
+0 0 1
+TEXTURE_COORDINATES uv 2 float
+0.25 0.75
+";
+        let attributes = parse(&mut text.as_bytes(), false).unwrap();
+
+        assert_eq!(attributes.point.len(), 3);
+        assert_eq!(attributes.cell.len(), 2);
+        assert_eq!(attributes.point_scalars().count(), 2);
+
+        let flags = attributes.point_array("flags").unwrap();
+        assert_eq!(flags.numcomp, 2);
+        assert_eq!(flags.len(), 2);
+        assert_eq!(flags.data, Buffer::I32(vec![1, 2, 3, 4]));
+
+        let velocity = attributes.point_array("velocity").unwrap();
+        assert_eq!(velocity.kind, DatasetAttributes::Vectors);
+        assert_eq!(velocity.lookup_table, None);
+
+        let uv = attributes.cell_array("uv").unwrap();
+        assert_eq!(uv.kind, DatasetAttributes::TextureCoordinates);
+        assert_eq!(uv.data, Buffer::F32(vec![0.25, 0.75]));
+        assert!(attributes.point_array("n").is_none());
+    }
+
+    #[test]
+    fn binary_arrays() {
+        let mut bytes = b"POINT_DATA 2\nSCALARS a unsigned_char\nLOOKUP_TABLE default\n".to_vec();
+        bytes.extend_from_slice(&[7, 8]);
+        bytes.extend_from_slice(b"\nSCALARS b short\nLOOKUP_TABLE default\n");
+        bytes.extend_from_slice(&[0, 1, 1, 0]);
+        bytes.extend_from_slice(b"\n");
+
+        let attributes = parse(&mut &bytes[..], true).unwrap();
+        assert_eq!(attributes.point[0].data, Buffer::U8(vec![7, 8]));
+        assert_eq!(attributes.point[1].data, Buffer::I16(vec![1, 256]));
+    }
+
+    #[test]
+    fn lookup_tables() {
+        let text = "POINT_DATA 2
+SCALARS a unsigned_char
+LOOKUP_TABLE palette
+0 1
+LOOKUP_TABLE palette 2
+0 0 0 1
+1 0.5 0 1
+COLOR_SCALARS c 3
+1 0 0 0 1 0
+";
+        let attributes = parse(&mut text.as_bytes(), false).unwrap();
+        let scalars = attributes.point_array("a").unwrap();
+        let table = attributes.lookup_table(scalars).unwrap();
+        assert_eq!(
+            table.colours,
+            vec![[0.0, 0.0, 0.0, 1.0], [1.0, 0.5, 0.0, 1.0]]
+        );
+
+        let colours = attributes.point_array("c").unwrap();
+        assert_eq!(colours.kind, DatasetAttributes::ColorScalars);
+        assert_eq!(colours.len(), 2);
+
+        let mut bytes = b"LOOKUP_TABLE t 1\n".to_vec();
+        bytes.extend_from_slice(&[255, 0, 0, 255]);
+        bytes.extend_from_slice(b"\nPOINT_DATA 1\nCOLOR_SCALARS c 4\n");
+        bytes.extend_from_slice(&[0, 255, 0, 255]);
+
+        let attributes = parse(&mut &bytes[..], true).unwrap();
+        assert_eq!(
+            attributes.lookup_tables[0].colours,
+            vec![[1.0, 0.0, 0.0, 1.0]]
+        );
+        assert_eq!(
+            attributes.point[0].data,
+            Buffer::F32(vec![0.0, 1.0, 0.0, 1.0])
+        );
+    }
+
+    #[test]
+    fn field_in_cell_data() {
+        let text = "CELL_DATA 2
+SCALARS id int
+LOOKUP_TABLE default
+0 1
+FIELD FieldData 2
+material 1 2 int
+4 5
+stress 3 2 float
+1 2 3 4 5 6
+";
+        let attributes = parse(&mut text.as_bytes(), false).unwrap();
+        assert_eq!(attributes.cell.len(), 3);
+        let stress = attributes.cell_array("stress").unwrap();
+        assert_eq!(stress.kind, DatasetAttributes::FieldData);
+        assert_eq!(stress.numcomp, 3);
+        assert_eq!(stress.len(), 2);
+    }
+
+    #[test]
+    fn missing_section() {
+        let text = "SCALARS a float\nLOOKUP_TABLE default\n1\n";
+        assert!(parse(&mut text.as_bytes(), false).is_err());
+    }
+}
@@ -0,0 +1,268 @@
+//! Converts volumes between legacy VTK, raw, NumPy and NRRD files, and
+//! from MetaImage, NIfTI-1 and DICOM files
+//!
+//! The format is chosen by the extension of each file. Exits with status 1
+//! if the conversion failed, and 2 on bad arguments
+
+use std::path::Path;
+use std::process;
+
+use vtk_parser::{Data, Datatype, Header, RawLayout, ScalarType, StructuredPoints, VTKparseError};
+
+const USAGE: &str = "Usage: vtkconvert [OPTIONS] INPUT OUTPUT
+
+Formats, by extension:
+    .vtk            Legacy VTK structured points, binary unless --ascii
+    .vti            VTK XML image data, as input only
+    .raw            Values without a header, laid out as in INPUT.layout
+    .npy            NumPy array
+    .nrrd           NRRD with the data attached
+    .nhdr           Detached NRRD header, as input only
+    .mha, .mhd      MetaImage, as input only
+    .nii, .hdr      Three dimensional NIfTI-1, as input only
+    A folder        DICOM slices of a series, as input only
+
+Options:
+    --ascii             Write legacy VTK files as ASCII
+    --array NAME        Convert the point array NAME instead of the first
+    --layout FILE       Layout of a raw input, instead of INPUT.layout
+    --type TYPE         Cast the values to TYPE, e.g. unsigned_char or float
+    --rescale           Map the range of the values onto the range of TYPE,
+                        [0, 1] for floating point types
+    --from LO:HI        Map LO:HI instead of the range of the values
+    --to LO:HI          Map onto LO:HI instead of the range of TYPE
+    -h, --help          Print this message
+
+Values outside the range of TYPE are clamped, and rounded for integers.
+Only the converted array is written";
+
+#[derive(Default)]
+struct Options {
+    ascii: bool,
+    array: Option<String>,
+    layout: Option<String>,
+    scalar_type: Option<ScalarType>,
+    rescale: bool,
+    from: Option<(f64, f64)>,
+    to: Option<(f64, f64)>,
+    input: String,
+    output: String,
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let result = read(&options)
+        .and_then(|points| convert(points, &options))
+        .and_then(|points| write(points, &options));
+    if let Err(err) = result {
+        eprintln!("{}: {}", options.input, err);
+        process::exit(1);
+    }
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut files = Vec::new();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Option {} needs a value", arg))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            "--ascii" => options.ascii = true,
+            "--array" => options.array = Some(value()?),
+            "--layout" => options.layout = Some(value()?),
+            "--type" => {
+                let name = value()?;
+                options.scalar_type =
+                    Some(ScalarType::from_name(&name).ok_or(format!("Unknown type {}", name))?);
+            }
+            "--rescale" => options.rescale = true,
+            "--from" => options.from = Some(range(&value()?)?),
+            "--to" => options.to = Some(range(&value()?)?),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ => files.push(arg),
+        }
+    }
+    if files.len() != 2 {
+        return Err("Expected an input and an output file".to_string());
+    }
+    options.output = files.pop().unwrap();
+    options.input = files.pop().unwrap();
+    options.rescale |= options.from.is_some() || options.to.is_some();
+    Ok(options)
+}
+
+fn range(text: &str) -> Result<(f64, f64), String> {
+    let mut values = text.splitn(2, ':').map(str::parse::<f64>);
+    match (values.next(), values.next()) {
+        (Some(Ok(lo)), Some(Ok(hi))) => Ok((lo, hi)),
+        _ => Err(format!("Invalid range {}, expected LO:HI", text)),
+    }
+}
+
+fn extension(file: &str) -> String {
+    Path::new(file)
+        .extension()
+        .map_or_else(String::new, |x| x.to_string_lossy().to_lowercase())
+}
+
+fn read(options: &Options) -> Result<StructuredPoints, VTKparseError> {
+    let file = &options.input;
+    if Path::new(file).is_dir() {
+        return vtk_parser::read_dicom_dir(file);
+    }
+    match extension(file).as_str() {
+        "raw" => {
+            let layout = match options.layout {
+                Some(ref layout) => RawLayout::read_sidecar(layout)?,
+                None => RawLayout::read_sidecar(RawLayout::sidecar(Path::new(file)))?,
+            };
+            vtk_parser::read_raw(file, &layout)
+        }
+        "npy" => vtk_parser::read_npy(file),
+        "nrrd" | "nhdr" => vtk_parser::read_nrrd(file),
+        "mha" | "mhd" => vtk_parser::read_mhd(file),
+        "nii" | "hdr" => {
+            let mut volumes = vtk_parser::read_nifti(file)?;
+            if volumes.len() != 1 {
+                return Err(VTKparseError::NotImplemented(format!(
+                    "Converting {} timesteps",
+                    volumes.len()
+                )));
+            }
+            Ok(volumes.pop().unwrap().1)
+        }
+        _ => match vtk_parser::read_file(file)?.dataset {
+            Datatype::StructuredPoints(points) => Ok(points),
+            _ => Err(VTKparseError::WrongFormat(
+                "Only structured points can be converted".to_string(),
+            )),
+        },
+    }
+}
+
+/// Keeps the selected array, rescaled and cast as asked
+fn convert(
+    mut points: StructuredPoints,
+    options: &Options,
+) -> Result<StructuredPoints, VTKparseError> {
+    let index = match options.array {
+        Some(ref name) => points
+            .attributes
+            .point
+            .iter()
+            .position(|x| &x.name == name)
+            .ok_or_else(|| VTKparseError::FileFormat(format!("No point array {}", name)))?,
+        None if points.attributes.point.is_empty() => {
+            return Err(VTKparseError::FileFormat(
+                "Volume has no point array".to_string(),
+            ))
+        }
+        None => 0,
+    };
+    let mut array = points.attributes.point.swap_remove(index);
+
+    let scalar_type = options
+        .scalar_type
+        .unwrap_or_else(|| array.data.scalar_type());
+    if options.rescale {
+        let from = options
+            .from
+            .or_else(|| array.data.range())
+            .unwrap_or((0.0, 1.0));
+        let to = options.to.unwrap_or_else(|| {
+            if scalar_type.is_integer() {
+                scalar_type.limits()
+            } else {
+                (0.0, 1.0)
+            }
+        });
+        array.data = array.data.rescale(from, to);
+    }
+    array.data = array.data.cast(scalar_type);
+
+    Ok(StructuredPoints::from_array(
+        points.dims,
+        points.origin,
+        points.spacing,
+        array,
+    ))
+}
+
+fn write(points: StructuredPoints, options: &Options) -> Result<(), VTKparseError> {
+    let file = &options.output;
+    match extension(file).as_str() {
+        "vtk" => {
+            let data = Data {
+                header: Header {
+                    file_version: (3, 0),
+                    header: format!("Converted from {}", options.input),
+                    binary: !options.ascii,
+                },
+                dataset: Datatype::StructuredPoints(points),
+                field_data: None,
+            };
+            vtk_parser::write_file(file, &data)
+        }
+        "raw" => vtk_parser::write_raw(file, &points),
+        "npy" => vtk_parser::write_npy(file, &points),
+        "nrrd" => vtk_parser::write_nrrd(file, &points),
+        x => Err(VTKparseError::NotImplemented(format!(
+            "Writing files with the extension {:?}",
+            x
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vtk_parser::{Buffer, DataArray, DatasetAttributes};
+
+    fn args(text: &str) -> Result<Options, String> {
+        parse_args(text.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn rescale_and_cast() {
+        let options = args("--type unsigned_char --from 0:1000 in.npy out.vtk").unwrap();
+        assert!(options.rescale);
+        assert_eq!(options.output, "out.vtk");
+        assert!(args("--from 1 in.npy out.vtk").is_err());
+        assert!(args("in.npy").is_err());
+
+        let array = DataArray {
+            name: "values".to_string(),
+            kind: DatasetAttributes::Scalars,
+            numcomp: 1,
+            data: Buffer::I16(vec![-100, 0, 500, 1000, 2000]),
+            lookup_table: None,
+        };
+        let points =
+            StructuredPoints::from_array((5, 1, 1), (0.0, 0.0, 0.0), (1.0, 1.0, 1.0), array);
+        let points = convert(points, &options).unwrap();
+        assert_eq!(
+            points.scalars().unwrap().data,
+            Buffer::U8(vec![0, 0, 128, 255, 255])
+        );
+
+        let options = args("--type float --rescale in.npy out.vtk").unwrap();
+        let points = convert(points, &options).unwrap();
+        assert_eq!(
+            points.scalars().unwrap().data,
+            Buffer::F32(vec![0.0, 0.0, 128.0 / 255.0, 1.0, 1.0])
+        );
+    }
+}
@@ -0,0 +1,415 @@
+//! Prints the header, geometry and arrays of VTK files
+//!
+//! Exits with status 1 if a file could not be read, and 2 on bad arguments
+
+use std::process;
+
+use vtk_parser::{Attributes, Buffer, Data, DataArray, DatasetAttributes, Datatype, FieldData};
+
+const USAGE: &str = "Usage: vtkinfo [--json] [--histogram[=BINS]] FILE...
+
+Options:
+    --json              Print a JSON array with an object for each file
+    --histogram[=BINS]  Count the values of each array in BINS bins (16)
+    -h, --help          Print this message";
+
+/// Width of the longest histogram bar
+const BAR_WIDTH: usize = 40;
+
+struct Options {
+    json: bool,
+    bins: Option<usize>,
+    files: Vec<String>,
+}
+
+/// What is printed about a file
+struct Info {
+    file: String,
+    version: (usize, usize),
+    title: String,
+    binary: bool,
+    dataset: &'static str,
+    /// Dimensions, point counts and the like, depending on the dataset
+    geometry: Vec<(&'static str, Vec<f64>)>,
+    arrays: Vec<Array>,
+}
+
+struct Array {
+    name: String,
+    /// `point`, `cell` or `field`
+    section: &'static str,
+    kind: &'static str,
+    datatype: &'static str,
+    numcomp: usize,
+    tuples: usize,
+    range: Option<(f64, f64)>,
+    histogram: Option<Vec<usize>>,
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let mut failed = false;
+    let mut json = Vec::new();
+    for file in &options.files {
+        match vtk_parser::read_file(file) {
+            Ok(data) => {
+                let info = info(file, &data, options.bins);
+                if options.json {
+                    json.push(to_json(&info));
+                } else {
+                    print_text(&info);
+                }
+            }
+            Err(err) => {
+                failed = true;
+                eprintln!("{}: {}", file, err);
+                if options.json {
+                    json.push(format!(
+                        "{{\"file\": {}, \"error\": {}}}",
+                        string(file),
+                        string(&err.to_string())
+                    ));
+                }
+            }
+        }
+    }
+    if options.json {
+        println!("[{}]", json.join(",\n"));
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
+
+fn parse_args<I: Iterator<Item = String>>(args: I) -> Result<Options, String> {
+    let mut options = Options {
+        json: false,
+        bins: None,
+        files: Vec::new(),
+    };
+    for arg in args {
+        if arg == "-h" || arg == "--help" {
+            println!("{}", USAGE);
+            process::exit(0);
+        } else if arg == "--json" {
+            options.json = true;
+        } else if arg == "--histogram" {
+            options.bins = Some(16);
+        } else if let Some(bins) = arg.strip_prefix("--histogram=") {
+            match bins.parse() {
+                Ok(bins) if bins > 0 => options.bins = Some(bins),
+                _ => return Err(format!("Invalid number of bins {}", bins)),
+            }
+        } else if arg.starts_with('-') {
+            return Err(format!("Unknown option {}", arg));
+        } else {
+            options.files.push(arg);
+        }
+    }
+    if options.files.is_empty() {
+        return Err("No files given".to_string());
+    }
+    Ok(options)
+}
+
+fn info(file: &str, data: &Data, bins: Option<usize>) -> Info {
+    let count = |n: usize| vec![n as f64];
+    let triple = |x: (f32, f32, f32)| vec![x.0.into(), x.1.into(), x.2.into()];
+    let dims = |x: (u32, u32, u32)| vec![x.0.into(), x.1.into(), x.2.into()];
+
+    let mut arrays = Vec::new();
+    let (dataset, geometry) = match data.dataset {
+        Datatype::StructuredPoints(ref x) => {
+            add_attributes(&mut arrays, &x.attributes, bins);
+            (
+                "STRUCTURED_POINTS",
+                vec![
+                    ("dims", dims(x.dims)),
+                    ("origin", triple(x.origin)),
+                    ("spacing", triple(x.spacing)),
+                ],
+            )
+        }
+        Datatype::StructuredGrid(ref x) => {
+            add_attributes(&mut arrays, &x.attributes, bins);
+            (
+                "STRUCTURED_GRID",
+                vec![("dims", dims(x.dims)), ("points", count(x.points.len()))],
+            )
+        }
+        Datatype::RectilinearGrid(ref x) => {
+            add_attributes(&mut arrays, &x.attributes, bins);
+            let bounds = x
+                .bounds()
+                .iter()
+                .flat_map(|&(lo, hi)| vec![lo, hi])
+                .collect();
+            (
+                "RECTILINEAR_GRID",
+                vec![("dims", dims(x.dims)), ("bounds", bounds)],
+            )
+        }
+        Datatype::PolyData(ref x) => {
+            add_attributes(&mut arrays, &x.attributes, bins);
+            (
+                "POLYDATA",
+                vec![
+                    ("points", count(x.points.len())),
+                    ("vertices", count(x.vertices.len())),
+                    ("lines", count(x.lines.len())),
+                    ("polygons", count(x.polygons.len())),
+                    ("triangle_strips", count(x.triangle_strips.len())),
+                ],
+            )
+        }
+        Datatype::UnstructuredGrid(ref x) => {
+            add_attributes(&mut arrays, &x.attributes, bins);
+            (
+                "UNSTRUCTURED_GRID",
+                vec![
+                    ("points", count(x.points.len())),
+                    ("cells", count(x.cells.len())),
+                ],
+            )
+        }
+        Datatype::Field(ref x) => {
+            add_field(&mut arrays, x, bins);
+            ("FIELD", Vec::new())
+        }
+        Datatype::Empty => ("EMPTY", Vec::new()),
+    };
+    if let Some(ref field) = data.field_data {
+        add_field(&mut arrays, field, bins);
+    }
+
+    Info {
+        file: file.to_string(),
+        version: data.header.file_version,
+        title: data.header.header.clone(),
+        binary: data.header.binary,
+        dataset,
+        geometry,
+        arrays,
+    }
+}
+
+fn add_attributes(arrays: &mut Vec<Array>, attributes: &Attributes, bins: Option<usize>) {
+    for x in &attributes.point {
+        arrays.push(array("point", x, bins));
+    }
+    for x in &attributes.cell {
+        arrays.push(array("cell", x, bins));
+    }
+}
+
+fn add_field(arrays: &mut Vec<Array>, field: &FieldData, bins: Option<usize>) {
+    for x in &field.arrays {
+        arrays.push(array("field", x, bins));
+    }
+}
+
+fn array(section: &'static str, array: &DataArray, bins: Option<usize>) -> Array {
+    let range = array.data.range();
+    Array {
+        name: array.name.clone(),
+        section,
+        kind: kind(array.kind),
+        datatype: array.data.scalar_type().name(),
+        numcomp: array.numcomp,
+        tuples: array.len(),
+        range,
+        histogram: bins.map(|bins| histogram(&array.data, range, bins)),
+    }
+}
+
+fn kind(kind: DatasetAttributes) -> &'static str {
+    match kind {
+        DatasetAttributes::Scalars => "SCALARS",
+        DatasetAttributes::ColorScalars => "COLOR_SCALARS",
+        DatasetAttributes::LookupTable => "LOOKUP_TABLE",
+        DatasetAttributes::Vectors => "VECTORS",
+        DatasetAttributes::Normals => "NORMALS",
+        DatasetAttributes::TextureCoordinates => "TEXTURE_COORDINATES",
+        DatasetAttributes::Tensors => "TENSORS",
+        DatasetAttributes::FieldData => "FIELD",
+    }
+}
+
+/// Counts of all values in `bins` bins of equal width spanning `range`,
+/// NaN is not counted
+fn histogram(data: &Buffer, range: Option<(f64, f64)>, bins: usize) -> Vec<usize> {
+    let mut counts = vec![0; bins];
+    let (lo, hi) = match range {
+        Some(range) => range,
+        None => return counts,
+    };
+    let scale = if hi > lo {
+        bins as f64 / (hi - lo)
+    } else {
+        0.0
+    };
+    for v in (0..data.len()).filter_map(|i| data.get(i)) {
+        if !v.is_nan() {
+            counts[(((v - lo) * scale) as usize).min(bins - 1)] += 1;
+        }
+    }
+    counts
+}
+
+fn numbers(x: &[f64]) -> String {
+    x.iter().map(f64::to_string).collect::<Vec<_>>().join(" ")
+}
+
+fn print_text(info: &Info) {
+    println!("{}", info.file);
+    println!("  version: {}.{}", info.version.0, info.version.1);
+    println!("  title: {}", info.title);
+    println!("  format: {}", if info.binary { "binary" } else { "ascii" });
+    println!("  dataset: {}", info.dataset);
+    for (name, values) in &info.geometry {
+        println!("  {}: {}", name, numbers(values));
+    }
+
+    for array in &info.arrays {
+        let range = array.range.map_or_else(
+            || "none".to_string(),
+            |(lo, hi)| format!("{} .. {}", lo, hi),
+        );
+        println!(
+            "  {} {} {}: {}, {} components, {} tuples, range {}",
+            array.section,
+            array.kind,
+            array.name,
+            array.datatype,
+            array.numcomp,
+            array.tuples,
+            range
+        );
+
+        if let (Some(counts), Some((lo, hi))) = (&array.histogram, array.range) {
+            let most = counts.iter().copied().max().unwrap_or(0).max(1);
+            let width = (hi - lo) / counts.len() as f64;
+            for (i, &n) in counts.iter().enumerate() {
+                println!(
+                    "    {:>12} {:>10} {}",
+                    lo + i as f64 * width,
+                    n,
+                    "#".repeat((n * BAR_WIDTH).div_ceil(most))
+                );
+            }
+        }
+    }
+}
+
+fn to_json(info: &Info) -> String {
+    let mut fields = vec![
+        ("file".to_string(), string(&info.file)),
+        (
+            "version".to_string(),
+            format!("[{}, {}]", info.version.0, info.version.1),
+        ),
+        ("title".to_string(), string(&info.title)),
+        ("binary".to_string(), info.binary.to_string()),
+        ("dataset".to_string(), string(info.dataset)),
+    ];
+    for (name, values) in &info.geometry {
+        let values = if values.len() == 1 {
+            number(values[0])
+        } else {
+            list(values.iter().map(|&x| number(x)))
+        };
+        fields.push((name.to_string(), values));
+    }
+
+    let arrays = info.arrays.iter().map(|array| {
+        let mut fields = vec![
+            ("name", string(&array.name)),
+            ("section", string(array.section)),
+            ("kind", string(array.kind)),
+            ("type", string(array.datatype)),
+            ("components", array.numcomp.to_string()),
+            ("tuples", array.tuples.to_string()),
+            (
+                "range",
+                array.range.map_or_else(
+                    || "null".to_string(),
+                    |(lo, hi)| list(vec![number(lo), number(hi)].into_iter()),
+                ),
+            ),
+        ];
+        if let Some(ref counts) = array.histogram {
+            fields.push(("histogram", list(counts.iter().map(usize::to_string))));
+        }
+        object(fields.into_iter().map(|(k, v)| (k.to_string(), v)))
+    });
+    fields.push(("arrays".to_string(), list(arrays)));
+
+    object(fields.into_iter())
+}
+
+fn object<I: Iterator<Item = (String, String)>>(fields: I) -> String {
+    let fields: Vec<String> = fields
+        .map(|(key, value)| format!("{}: {}", string(&key), value))
+        .collect();
+    format!("{{{}}}", fields.join(", "))
+}
+
+fn list<I: Iterator<Item = String>>(values: I) -> String {
+    format!("[{}]", values.collect::<Vec<_>>().join(", "))
+}
+
+/// JSON has no NaN or infinity, they are written as null
+fn number(x: f64) -> String {
+    if x.is_finite() {
+        x.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+fn string(x: &str) -> String {
+    let mut s = String::with_capacity(x.len() + 2);
+    s.push('"');
+    for c in x.chars() {
+        match c {
+            '"' => s.push_str("\\\""),
+            '\\' => s.push_str("\\\\"),
+            '\n' => s.push_str("\\n"),
+            '\r' => s.push_str("\\r"),
+            '\t' => s.push_str("\\t"),
+            c if (c as u32) < 0x20 => s.push_str(&format!("\\u{:04x}", c as u32)),
+            c => s.push(c),
+        }
+    }
+    s.push('"');
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_and_json() {
+        let data = Buffer::F32(vec![0.0, 1.0, 2.0, 3.0, 4.0, f32::NAN]);
+        assert_eq!(histogram(&data, data.range(), 2), vec![2, 3]);
+        assert_eq!(
+            histogram(&Buffer::U8(vec![7; 3]), Some((7.0, 7.0)), 4),
+            vec![3, 0, 0, 0]
+        );
+
+        assert_eq!(string("a \"b\"\n\u{1}"), r#""a \"b\"\n\u0001""#);
+        assert_eq!(
+            object(vec![("x".to_string(), number(f64::NAN))].into_iter()),
+            r#"{"x": null}"#
+        );
+    }
+}
@@ -0,0 +1,671 @@
+use std::convert::TryInto;
+use std::io::prelude::*;
+
+use crate::VTKparseError;
+
+/// The scalar types allowed by the legacy VTK format
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScalarType {
+    Bit,
+    UnsignedChar,
+    Char,
+    UnsignedShort,
+    Short,
+    UnsignedInt,
+    Int,
+    UnsignedLong,
+    Long,
+    Float,
+    Double,
+}
+
+impl ScalarType {
+    /// Looks up the type from the name used in the file, e.g. `unsigned_short`
+    ///
+    /// `long` is taken to be 64 bits, as written by VTK on all platforms
+    /// except Windows. The `vtktypeint64` family of names written by newer
+    /// versions of VTK is also recognised
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "bit" => Some(ScalarType::Bit),
+            "unsigned_char" | "vtktypeuint8" => Some(ScalarType::UnsignedChar),
+            "char" | "signed_char" | "vtktypeint8" => Some(ScalarType::Char),
+            "unsigned_short" | "vtktypeuint16" => Some(ScalarType::UnsignedShort),
+            "short" | "vtktypeint16" => Some(ScalarType::Short),
+            "unsigned_int" | "vtktypeuint32" => Some(ScalarType::UnsignedInt),
+            "int" | "vtktypeint32" => Some(ScalarType::Int),
+            "unsigned_long" | "vtktypeuint64" => Some(ScalarType::UnsignedLong),
+            "long" | "vtktypeint64" | "vtkidtype" => Some(ScalarType::Long),
+            "float" | "vtktypefloat32" => Some(ScalarType::Float),
+            "double" | "vtktypefloat64" => Some(ScalarType::Double),
+            _ => None,
+        }
+    }
+
+    /// The name of the type as written in legacy files
+    pub fn name(self) -> &'static str {
+        match self {
+            ScalarType::Bit => "bit",
+            ScalarType::UnsignedChar => "unsigned_char",
+            ScalarType::Char => "char",
+            ScalarType::UnsignedShort => "unsigned_short",
+            ScalarType::Short => "short",
+            ScalarType::UnsignedInt => "unsigned_int",
+            ScalarType::Int => "int",
+            ScalarType::UnsignedLong => "unsigned_long",
+            ScalarType::Long => "long",
+            ScalarType::Float => "float",
+            ScalarType::Double => "double",
+        }
+    }
+
+    /// Size in bytes of one value, bits are packed and reported as 0
+    pub fn size(self) -> usize {
+        match self {
+            ScalarType::Bit => 0,
+            ScalarType::UnsignedChar | ScalarType::Char => 1,
+            ScalarType::UnsignedShort | ScalarType::Short => 2,
+            ScalarType::UnsignedInt | ScalarType::Int | ScalarType::Float => 4,
+            ScalarType::UnsignedLong | ScalarType::Long | ScalarType::Double => 8,
+        }
+    }
+
+    /// Smallest and largest value of the type
+    pub fn limits(self) -> (f64, f64) {
+        match self {
+            ScalarType::Bit => (0.0, 1.0),
+            ScalarType::UnsignedChar => (0.0, u8::MAX.into()),
+            ScalarType::Char => (i8::MIN.into(), i8::MAX.into()),
+            ScalarType::UnsignedShort => (0.0, u16::MAX.into()),
+            ScalarType::Short => (i16::MIN.into(), i16::MAX.into()),
+            ScalarType::UnsignedInt => (0.0, u32::MAX.into()),
+            ScalarType::Int => (i32::MIN.into(), i32::MAX.into()),
+            ScalarType::UnsignedLong => (0.0, u64::MAX as f64),
+            ScalarType::Long => (i64::MIN as f64, i64::MAX as f64),
+            ScalarType::Float => (f32::MIN.into(), f32::MAX.into()),
+            ScalarType::Double => (f64::MIN, f64::MAX),
+        }
+    }
+
+    pub fn is_integer(self) -> bool {
+        !matches!(self, ScalarType::Float | ScalarType::Double)
+    }
+
+    /// Number of bytes taken up by `n` values in a binary file
+    pub fn bytes(self, n: usize) -> Result<usize, VTKparseError> {
+        match self {
+            ScalarType::Bit => Ok(n.div_ceil(8)),
+            _ => n.checked_mul(self.size()).ok_or_else(|| {
+                VTKparseError::FileFormat(format!("{} values of {} are too many", n, self.name()))
+            }),
+        }
+    }
+}
+
+/// Typed storage of the values of an array
+#[derive(Clone, Debug, PartialEq)]
+pub enum Buffer {
+    Bit(Vec<bool>),
+    U8(Vec<u8>),
+    I8(Vec<i8>),
+    U16(Vec<u16>),
+    I16(Vec<i16>),
+    U32(Vec<u32>),
+    I32(Vec<i32>),
+    U64(Vec<u64>),
+    I64(Vec<i64>),
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+}
+
+macro_rules! for_each_buffer {
+    ($buffer:expr, $v:ident => $e:expr) => {
+        match $buffer {
+            Buffer::Bit($v) => $e,
+            Buffer::U8($v) => $e,
+            Buffer::I8($v) => $e,
+            Buffer::U16($v) => $e,
+            Buffer::I16($v) => $e,
+            Buffer::U32($v) => $e,
+            Buffer::I32($v) => $e,
+            Buffer::U64($v) => $e,
+            Buffer::I64($v) => $e,
+            Buffer::F32($v) => $e,
+            Buffer::F64($v) => $e,
+        }
+    };
+}
+
+macro_rules! map_buffer {
+    ($buffer:expr, $v:ident => $e:expr) => {
+        match $buffer {
+            Buffer::Bit($v) => Buffer::Bit($e),
+            Buffer::U8($v) => Buffer::U8($e),
+            Buffer::I8($v) => Buffer::I8($e),
+            Buffer::U16($v) => Buffer::U16($e),
+            Buffer::I16($v) => Buffer::I16($e),
+            Buffer::U32($v) => Buffer::U32($e),
+            Buffer::I32($v) => Buffer::I32($e),
+            Buffer::U64($v) => Buffer::U64($e),
+            Buffer::I64($v) => Buffer::I64($e),
+            Buffer::F32($v) => Buffer::F32($e),
+            Buffer::F64($v) => Buffer::F64($e),
+        }
+    };
+}
+
+macro_rules! as_slice {
+    ($fn:ident, $variant:ident, $t:ty) => {
+        pub fn $fn(&self) -> Option<&[$t]> {
+            if let Buffer::$variant(ref x) = *self {
+                return Some(x);
+            }
+            None
+        }
+    };
+}
+
+impl Buffer {
+    pub fn scalar_type(&self) -> ScalarType {
+        match *self {
+            Buffer::Bit(_) => ScalarType::Bit,
+            Buffer::U8(_) => ScalarType::UnsignedChar,
+            Buffer::I8(_) => ScalarType::Char,
+            Buffer::U16(_) => ScalarType::UnsignedShort,
+            Buffer::I16(_) => ScalarType::Short,
+            Buffer::U32(_) => ScalarType::UnsignedInt,
+            Buffer::I32(_) => ScalarType::Int,
+            Buffer::U64(_) => ScalarType::UnsignedLong,
+            Buffer::I64(_) => ScalarType::Long,
+            Buffer::F32(_) => ScalarType::Float,
+            Buffer::F64(_) => ScalarType::Double,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        for_each_buffer!(self, x => x.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Value at `index` converted to `f64`
+    pub fn get(&self, index: usize) -> Option<f64> {
+        match *self {
+            Buffer::Bit(ref x) => x.get(index).map(|&b| if b { 1.0 } else { 0.0 }),
+            Buffer::U8(ref x) => x.get(index).map(|&v| v.into()),
+            Buffer::I8(ref x) => x.get(index).map(|&v| v.into()),
+            Buffer::U16(ref x) => x.get(index).map(|&v| v.into()),
+            Buffer::I16(ref x) => x.get(index).map(|&v| v.into()),
+            Buffer::U32(ref x) => x.get(index).map(|&v| v.into()),
+            Buffer::I32(ref x) => x.get(index).map(|&v| v.into()),
+            Buffer::U64(ref x) => x.get(index).map(|&v| v as f64),
+            Buffer::I64(ref x) => x.get(index).map(|&v| v as f64),
+            Buffer::F32(ref x) => x.get(index).map(|&v| v.into()),
+            Buffer::F64(ref x) => x.get(index).copied(),
+        }
+    }
+
+    /// All values converted to `f64`
+    pub fn to_f64(&self) -> Vec<f64> {
+        (0..self.len()).map(|i| self.get(i).unwrap()).collect()
+    }
+
+    /// Smallest and largest value, ignoring NaN
+    pub fn range(&self) -> Option<(f64, f64)> {
+        let mut range: Option<(f64, f64)> = None;
+        for v in (0..self.len()).map(|i| self.get(i).unwrap()) {
+            if v.is_nan() {
+                continue;
+            }
+            range = Some(match range {
+                None => (v, v),
+                Some((lo, hi)) => (lo.min(v), hi.max(v)),
+            });
+        }
+        range
+    }
+
+    /// The values converted to `datatype`, rounded to the nearest integer and
+    /// clamped to the range of integer types
+    pub fn cast(&self, datatype: ScalarType) -> Buffer {
+        if self.scalar_type() == datatype {
+            return self.clone();
+        }
+        from_f64((0..self.len()).map(|i| self.get(i).unwrap()), datatype)
+    }
+
+    /// The values mapped linearly from the range `from` onto `to`, as doubles
+    pub fn rescale(&self, from: (f64, f64), to: (f64, f64)) -> Buffer {
+        let scale = if from.1 != from.0 {
+            (to.1 - to.0) / (from.1 - from.0)
+        } else {
+            0.0
+        };
+        Buffer::F64(
+            (0..self.len())
+                .map(|i| (self.get(i).unwrap() - from.0) * scale + to.0)
+                .collect(),
+        )
+    }
+
+    /// The tuples of `numcomp` values at the given indices, in that order
+    pub(crate) fn select(&self, tuples: &[usize], numcomp: usize) -> Buffer {
+        map_buffer!(self, x => tuples
+            .iter()
+            .flat_map(|&i| x[i * numcomp..(i + 1) * numcomp].iter().copied())
+            .collect())
+    }
+
+    as_slice!(as_bit, Bit, bool);
+    as_slice!(as_u8, U8, u8);
+    as_slice!(as_i8, I8, i8);
+    as_slice!(as_u16, U16, u16);
+    as_slice!(as_i16, I16, i16);
+    as_slice!(as_u32, U32, u32);
+    as_slice!(as_i32, I32, i32);
+    as_slice!(as_u64, U64, u64);
+    as_slice!(as_i64, I64, i64);
+    as_slice!(as_f32, F32, f32);
+    as_slice!(as_f64, F64, f64);
+}
+
+macro_rules! from_bytes {
+    ($bytes:expr, $t:ty, $little_endian:expr) => {
+        $bytes
+            .chunks_exact(std::mem::size_of::<$t>())
+            .map(|c| {
+                if $little_endian {
+                    <$t>::from_le_bytes(c.try_into().unwrap())
+                } else {
+                    <$t>::from_be_bytes(c.try_into().unwrap())
+                }
+            })
+            .collect()
+    };
+}
+
+/// Converts packed values of type `datatype` in the given byte order
+///
+/// Trailing bytes which do not make up a whole value are ignored, and
+/// `bytes` must hold whole bytes of bits
+pub(crate) fn from_bytes(bytes: Vec<u8>, datatype: ScalarType, little_endian: bool) -> Buffer {
+    match datatype {
+        ScalarType::Bit => Buffer::Bit(
+            (0..bytes.len() * 8)
+                .map(|i| bytes[i / 8] & (0x80 >> (i % 8)) != 0)
+                .collect(),
+        ),
+        ScalarType::UnsignedChar => Buffer::U8(bytes),
+        ScalarType::Char => Buffer::I8(bytes.into_iter().map(|x| x as i8).collect()),
+        ScalarType::UnsignedShort => Buffer::U16(from_bytes!(bytes, u16, little_endian)),
+        ScalarType::Short => Buffer::I16(from_bytes!(bytes, i16, little_endian)),
+        ScalarType::UnsignedInt => Buffer::U32(from_bytes!(bytes, u32, little_endian)),
+        ScalarType::Int => Buffer::I32(from_bytes!(bytes, i32, little_endian)),
+        ScalarType::UnsignedLong => Buffer::U64(from_bytes!(bytes, u64, little_endian)),
+        ScalarType::Long => Buffer::I64(from_bytes!(bytes, i64, little_endian)),
+        ScalarType::Float => Buffer::F32(from_bytes!(bytes, f32, little_endian)),
+        ScalarType::Double => Buffer::F64(from_bytes!(bytes, f64, little_endian)),
+    }
+}
+
+macro_rules! from_f64 {
+    ($values:expr, $t:ty) => {
+        $values.map(|x| x.round() as $t).collect()
+    };
+}
+
+/// Converts values to `datatype`, integers are rounded and saturate at the
+/// limits of the type, with NaN becoming zero
+pub(crate) fn from_f64<I: Iterator<Item = f64>>(values: I, datatype: ScalarType) -> Buffer {
+    match datatype {
+        ScalarType::Bit => Buffer::Bit(values.map(|x| x != 0.0 && !x.is_nan()).collect()),
+        ScalarType::UnsignedChar => Buffer::U8(from_f64!(values, u8)),
+        ScalarType::Char => Buffer::I8(from_f64!(values, i8)),
+        ScalarType::UnsignedShort => Buffer::U16(from_f64!(values, u16)),
+        ScalarType::Short => Buffer::I16(from_f64!(values, i16)),
+        ScalarType::UnsignedInt => Buffer::U32(from_f64!(values, u32)),
+        ScalarType::Int => Buffer::I32(from_f64!(values, i32)),
+        ScalarType::UnsignedLong => Buffer::U64(from_f64!(values, u64)),
+        ScalarType::Long => Buffer::I64(from_f64!(values, i64)),
+        ScalarType::Float => Buffer::F32(values.map(|x| x as f32).collect()),
+        ScalarType::Double => Buffer::F64(values.collect()),
+    }
+}
+
+/// Reads `n` values in either the binary or ASCII representation
+pub(crate) fn read<R: BufRead>(
+    reader: &mut R,
+    binary: bool,
+    datatype: ScalarType,
+    n: usize,
+) -> Result<Buffer, VTKparseError> {
+    if binary {
+        read_binary(reader, datatype, n)
+    } else {
+        read_ascii(reader, datatype, n)
+    }
+}
+
+/// Most values reserved up front for a count read from a file, so that a
+/// corrupt count runs out of data rather than memory
+const RESERVE_LIMIT: usize = 1 << 24;
+
+/// An empty vector with room for `n` values, or fewer if `n` is large
+pub(crate) fn reserve<T>(n: usize) -> Vec<T> {
+    Vec::with_capacity(n.min(RESERVE_LIMIT))
+}
+
+/// Product of counts read from a file, failing if it overflows
+pub(crate) fn count(factors: &[usize]) -> Result<usize, VTKparseError> {
+    factors
+        .iter()
+        .try_fold(1usize, |n, &x| n.checked_mul(x))
+        .ok_or_else(|| VTKparseError::FileFormat(format!("Too many values: {:?}", factors)))
+}
+
+/// Reads `n` big-endian values of type `datatype`
+pub(crate) fn read_binary<R: BufRead>(
+    reader: &mut R,
+    datatype: ScalarType,
+    n: usize,
+) -> Result<Buffer, VTKparseError> {
+    let len = datatype.bytes(n)?;
+    let mut bytes = reserve(len);
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+
+    if bytes.len() != len {
+        return Err(VTKparseError::UnknownFormat(format!(
+            "Number of elements is not correct. Expected {} but got {}",
+            n,
+            if datatype == ScalarType::Bit {
+                bytes.len() * 8
+            } else {
+                bytes.len() / datatype.size()
+            }
+        )));
+    }
+
+    Ok(match from_bytes(bytes, datatype, false) {
+        // The last byte may be padded with unused bits
+        Buffer::Bit(mut x) => {
+            x.truncate(n);
+            Buffer::Bit(x)
+        }
+        buffer => buffer,
+    })
+}
+
+macro_rules! parse_words {
+    ($reader:expr, $n:expr, $t:ty) => {{
+        let mut values = reserve($n);
+        for _ in 0..$n {
+            match next_word($reader)? {
+                Some(word) => values.push(word.parse::<$t>()?),
+                None => break,
+            }
+        }
+        values
+    }};
+}
+
+/// Reads `n` whitespace separated values of type `datatype`
+///
+/// Values may be split over any number of lines, VTK writers usually
+/// emit a fixed number of values per line but this is not required
+pub(crate) fn read_ascii<R: BufRead>(
+    reader: &mut R,
+    datatype: ScalarType,
+    n: usize,
+) -> Result<Buffer, VTKparseError> {
+    let buffer = match datatype {
+        ScalarType::Bit => Buffer::Bit(
+            parse_words!(reader, n, u8)
+                .into_iter()
+                .map(|x| x != 0)
+                .collect(),
+        ),
+        ScalarType::UnsignedChar => Buffer::U8(parse_words!(reader, n, u8)),
+        ScalarType::Char => Buffer::I8(parse_words!(reader, n, i8)),
+        ScalarType::UnsignedShort => Buffer::U16(parse_words!(reader, n, u16)),
+        ScalarType::Short => Buffer::I16(parse_words!(reader, n, i16)),
+        ScalarType::UnsignedInt => Buffer::U32(parse_words!(reader, n, u32)),
+        ScalarType::Int => Buffer::I32(parse_words!(reader, n, i32)),
+        ScalarType::UnsignedLong => Buffer::U64(parse_words!(reader, n, u64)),
+        ScalarType::Long => Buffer::I64(parse_words!(reader, n, i64)),
+        ScalarType::Float => Buffer::F32(parse_words!(reader, n, f32)),
+        ScalarType::Double => Buffer::F64(parse_words!(reader, n, f64)),
+    };
+
+    if buffer.len() != n {
+        return Err(VTKparseError::UnknownFormat(format!(
+            "Number of elements is not correct. Expected {} but got {}",
+            n,
+            buffer.len()
+        )));
+    }
+    Ok(buffer)
+}
+
+/// Reads the next whitespace delimited word, `None` at the end of the file
+pub(crate) fn next_word<R: BufRead>(reader: &mut R) -> Result<Option<String>, VTKparseError> {
+    let mut word = Vec::new();
+    loop {
+        let (used, done) = {
+            let buf = reader.fill_buf()?;
+            if buf.is_empty() {
+                break;
+            }
+            let mut used = 0;
+            let mut done = false;
+            for &b in buf {
+                used += 1;
+                if b.is_ascii_whitespace() {
+                    if !word.is_empty() {
+                        done = true;
+                        break;
+                    }
+                } else {
+                    word.push(b);
+                }
+            }
+            (used, done)
+        };
+        reader.consume(used);
+        if done {
+            break;
+        }
+    }
+
+    if word.is_empty() {
+        return Ok(None);
+    }
+    String::from_utf8(word)
+        .map(Some)
+        .map_err(|_| VTKparseError::FileFormat("Value is not valid UTF-8".to_string()))
+}
+
+macro_rules! to_bytes {
+    ($writer:expr, $values:expr, $little_endian:expr) => {
+        for v in $values {
+            if $little_endian {
+                $writer.write_all(&v.to_le_bytes())?;
+            } else {
+                $writer.write_all(&v.to_be_bytes())?;
+            }
+        }
+    };
+}
+
+/// Writes the values in either the binary or ASCII representation, followed
+/// by a newline
+pub(crate) fn write<W: Write>(
+    writer: &mut W,
+    binary: bool,
+    buffer: &Buffer,
+) -> Result<(), VTKparseError> {
+    if binary {
+        write_binary(writer, buffer)?;
+        writer.write_all(b"\n")?;
+        return Ok(());
+    }
+
+    // Nine values per line, as written by VTK
+    for i in 0..buffer.len() {
+        let sep = if i % 9 == 8 || i + 1 == buffer.len() {
+            "\n"
+        } else {
+            " "
+        };
+        match *buffer {
+            Buffer::Bit(ref x) => write!(writer, "{}{}", x[i] as u8, sep)?,
+            _ => for_each_buffer!(buffer, x => write!(writer, "{}{}", x[i], sep)?),
+        }
+    }
+    Ok(())
+}
+
+/// Writes the values big-endian, with bits packed most significant first
+pub(crate) fn write_binary<W: Write>(writer: &mut W, buffer: &Buffer) -> Result<(), VTKparseError> {
+    write_bytes(writer, buffer, false)
+}
+
+/// Writes the values in the given byte order, with bits packed most
+/// significant first
+pub(crate) fn write_bytes<W: Write>(
+    writer: &mut W,
+    buffer: &Buffer,
+    little_endian: bool,
+) -> Result<(), VTKparseError> {
+    match *buffer {
+        Buffer::Bit(ref x) => {
+            let mut bytes = vec![0u8; x.len().div_ceil(8)];
+            for (i, _) in x.iter().enumerate().filter(|(_, &b)| b) {
+                bytes[i / 8] |= 0x80 >> (i % 8);
+            }
+            writer.write_all(&bytes)?;
+        }
+        Buffer::U8(ref x) => writer.write_all(x)?,
+        Buffer::I8(ref x) => to_bytes!(writer, x, little_endian),
+        Buffer::U16(ref x) => to_bytes!(writer, x, little_endian),
+        Buffer::I16(ref x) => to_bytes!(writer, x, little_endian),
+        Buffer::U32(ref x) => to_bytes!(writer, x, little_endian),
+        Buffer::I32(ref x) => to_bytes!(writer, x, little_endian),
+        Buffer::U64(ref x) => to_bytes!(writer, x, little_endian),
+        Buffer::I64(ref x) => to_bytes!(writer, x, little_endian),
+        Buffer::F32(ref x) => to_bytes!(writer, x, little_endian),
+        Buffer::F64(ref x) => to_bytes!(writer, x, little_endian),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_big_endian() {
+        let bytes = [0x01, 0x02, 0xff, 0xfe];
+        let buffer = read_binary(&mut &bytes[..], ScalarType::UnsignedShort, 2).unwrap();
+        assert_eq!(buffer, Buffer::U16(vec![0x0102, 0xfffe]));
+        let buffer = read_binary(&mut &bytes[..], ScalarType::Short, 2).unwrap();
+        assert_eq!(buffer, Buffer::I16(vec![0x0102, -2]));
+
+        let bytes = 1.5f32.to_be_bytes();
+        let buffer = read_binary(&mut &bytes[..], ScalarType::Float, 1).unwrap();
+        assert_eq!(buffer, Buffer::F32(vec![1.5]));
+
+        let bytes = [0b1010_0000];
+        let buffer = read_binary(&mut &bytes[..], ScalarType::Bit, 3).unwrap();
+        assert_eq!(buffer, Buffer::Bit(vec![true, false, true]));
+    }
+
+    #[test]
+    fn corrupt_counts() {
+        assert!(ScalarType::Double.bytes(usize::MAX).is_err());
+        assert!(count(&[1 << 40, 1 << 40]).is_err());
+
+        // Runs out of data instead of reserving room for the count
+        let bytes = [0; 4];
+        assert!(read_binary(&mut &bytes[..], ScalarType::Float, usize::MAX / 8).is_err());
+        assert!(read_ascii(&mut &b"1 2"[..], ScalarType::Float, usize::MAX).is_err());
+    }
+
+    #[test]
+    fn ascii_values() {
+        let text = "-1 2\n  3.5e1\n\n4 rest";
+        let mut reader = text.as_bytes();
+        let buffer = read_ascii(&mut reader, ScalarType::Double, 4).unwrap();
+        assert_eq!(buffer, Buffer::F64(vec![-1.0, 2.0, 35.0, 4.0]));
+        assert_eq!(buffer.range(), Some((-1.0, 35.0)));
+        assert_eq!(reader, b"rest");
+
+        assert!(read_ascii(&mut "1 2".as_bytes(), ScalarType::Int, 3).is_err());
+        assert!(read_ascii(&mut "1 -2".as_bytes(), ScalarType::UnsignedInt, 2).is_err());
+    }
+
+    #[test]
+    fn write_and_read() {
+        let buffers = [
+            Buffer::Bit(vec![
+                true, false, true, true, false, false, false, false, true,
+            ]),
+            Buffer::I16(vec![-2, 300]),
+            Buffer::F32((0..20).map(|x| x as f32 / 3.0).collect()),
+        ];
+        for binary in &[false, true] {
+            for buffer in &buffers {
+                let mut bytes = Vec::new();
+                write(&mut bytes, *binary, buffer).unwrap();
+                let mut reader = &bytes[..];
+                let read = read(&mut reader, *binary, buffer.scalar_type(), buffer.len());
+                assert_eq!(&read.unwrap(), buffer);
+                assert!(reader.iter().all(u8::is_ascii_whitespace));
+            }
+        }
+    }
+
+    #[test]
+    fn cast_and_rescale() {
+        let buffer = Buffer::F64(vec![-1.0, 0.4, 0.6, 300.0, f64::NAN]);
+        assert_eq!(
+            buffer.cast(ScalarType::UnsignedChar),
+            Buffer::U8(vec![0, 0, 1, 255, 0])
+        );
+        let buffer = Buffer::I16(vec![-100, 0, 100]);
+        assert_eq!(
+            buffer.rescale((-100.0, 100.0), (0.0, 1.0)),
+            Buffer::F64(vec![0.0, 0.5, 1.0])
+        );
+        assert_eq!(
+            buffer
+                .rescale((-100.0, 100.0), ScalarType::UnsignedChar.limits())
+                .cast(ScalarType::UnsignedChar),
+            Buffer::U8(vec![0, 127, 255])
+        );
+
+        let mut bytes = Vec::new();
+        write_bytes(&mut bytes, &Buffer::U16(vec![0x0102]), true).unwrap();
+        assert_eq!(bytes, [0x02, 0x01]);
+    }
+
+    #[test]
+    fn type_names() {
+        for name in &["bit", "unsigned_char", "char", "unsigned_short", "short"] {
+            assert_eq!(ScalarType::from_name(name).unwrap().name(), *name);
+        }
+        for name in &[
+            "unsigned_int",
+            "int",
+            "unsigned_long",
+            "long",
+            "float",
+            "double",
+        ] {
+            assert_eq!(ScalarType::from_name(name).unwrap().name(), *name);
+        }
+        assert_eq!(ScalarType::from_name("FLOAT"), Some(ScalarType::Float));
+        assert_eq!(ScalarType::from_name("complex"), None);
+    }
+}
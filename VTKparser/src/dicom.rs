@@ -0,0 +1,589 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{self, BufReader};
+use std::path::Path;
+
+use crate::attributes::DataArray;
+use crate::buffer::{self, ScalarType};
+use crate::spoints::axis_flips;
+use crate::{DatasetAttributes, StructuredPoints, VTKparseError};
+
+type Tag = (u16, u16);
+
+const TRANSFER_SYNTAX: Tag = (0x0002, 0x0010);
+const SERIES: Tag = (0x0020, 0x000e);
+const INSTANCE_NUMBER: Tag = (0x0020, 0x0013);
+const POSITION: Tag = (0x0020, 0x0032);
+const ORIENTATION: Tag = (0x0020, 0x0037);
+const SLICE_THICKNESS: Tag = (0x0018, 0x0050);
+const SAMPLES: Tag = (0x0028, 0x0002);
+const PLANAR_CONFIGURATION: Tag = (0x0028, 0x0006);
+const ROWS: Tag = (0x0028, 0x0010);
+const COLUMNS: Tag = (0x0028, 0x0011);
+const PIXEL_SPACING: Tag = (0x0028, 0x0030);
+const BITS_ALLOCATED: Tag = (0x0028, 0x0100);
+const PIXEL_REPRESENTATION: Tag = (0x0028, 0x0103);
+const RESCALE_INTERCEPT: Tag = (0x0028, 0x1052);
+const RESCALE_SLOPE: Tag = (0x0028, 0x1053);
+const PIXEL_DATA: Tag = (0x7fe0, 0x0010);
+
+const ITEM: Tag = (0xfffe, 0xe000);
+const ITEM_END: Tag = (0xfffe, 0xe00d);
+const SEQUENCE_END: Tag = (0xfffe, 0xe0dd);
+const UNDEFINED: u32 = 0xffff_ffff;
+
+/// Fields read from a slice, other than the pixel data
+const FIELDS: [Tag; 15] = [
+    TRANSFER_SYNTAX,
+    SERIES,
+    INSTANCE_NUMBER,
+    POSITION,
+    ORIENTATION,
+    SLICE_THICKNESS,
+    SAMPLES,
+    PLANAR_CONFIGURATION,
+    ROWS,
+    COLUMNS,
+    PIXEL_SPACING,
+    BITS_ALLOCATED,
+    PIXEL_REPRESENTATION,
+    RESCALE_INTERCEPT,
+    RESCALE_SLOPE,
+];
+
+/// Whether the file starts like a DICOM Part 10 file
+pub fn is_dicom<P: AsRef<Path>>(file: P) -> bool {
+    let mut preamble = [0; 132];
+    File::open(file)
+        .and_then(|mut x| x.read_exact(&mut preamble))
+        .is_ok()
+        && &preamble[128..] == b"DICM"
+}
+
+/// Reads the DICOM slices in a folder as a volume, ignoring other files
+pub fn read_dicom_dir<P: AsRef<Path>>(directory: P) -> Result<StructuredPoints, VTKparseError> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.is_file() && is_dicom(&path) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    read_dicom(&files)
+}
+
+/// Assembles the slices of a DICOM series into a volume
+///
+/// Slices are stored uncompressed, with explicit or implicit VR little
+/// endian. They are sorted along the normal of the slices by their
+/// `ImagePositionPatient`, or else by `InstanceNumber`. The spacing between
+/// slices is taken from their positions, or else from `SliceThickness`.
+/// Slices with a `RescaleSlope` or `RescaleIntercept` are rescaled into
+/// floats. Axes which the orientation reverses are flipped
+pub fn read_dicom<P: AsRef<Path>>(files: &[P]) -> Result<StructuredPoints, VTKparseError> {
+    let mut slices = files
+        .iter()
+        .map(|file| read_slice(BufReader::new(File::open(file)?)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if slices.is_empty() {
+        return Err(VTKparseError::FileFormat("No DICOM slices".to_string()));
+    }
+    let mut series: Vec<&str> = slices.iter().map(|x| x.series.as_str()).collect();
+    series.sort_unstable();
+    series.dedup();
+    if series.len() > 1 {
+        return Err(VTKparseError::NotImplemented(format!(
+            "Reading {} DICOM series at once, each series needs its own folder",
+            series.len()
+        )));
+    }
+
+    let first = &slices[0];
+    let layout = |x: &Slice| (x.rows, x.columns, x.samples, x.scalar_type);
+    if slices.iter().any(|x| layout(x) != layout(first)) {
+        return Err(VTKparseError::FileFormat(
+            "DICOM slices differ in size or type".to_string(),
+        ));
+    }
+    let orientation = first.orientation.unwrap_or([1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+    let row = [orientation[0], orientation[1], orientation[2]];
+    let column = [orientation[3], orientation[4], orientation[5]];
+    let normal = [
+        row[1] * column[2] - row[2] * column[1],
+        row[2] * column[0] - row[0] * column[2],
+        row[0] * column[1] - row[1] * column[0],
+    ];
+    let distance = |x: &Slice| {
+        x.position
+            .map(|p| p[0] * normal[0] + p[1] * normal[1] + p[2] * normal[2])
+    };
+
+    let positioned = slices.iter().all(|x| x.position.is_some());
+    if positioned {
+        slices.sort_by(|a, b| distance(a).unwrap().total_cmp(&distance(b).unwrap()));
+    } else {
+        slices.sort_by_key(|x| x.instance);
+    }
+
+    let n = slices.len();
+    let first = &slices[0];
+    let thickness = first.thickness.filter(|&x| x > 0.0).unwrap_or(1.0);
+    let slice_spacing = if positioned && n > 1 {
+        let extent = distance(&slices[n - 1]).unwrap() - distance(first).unwrap();
+        if extent <= 0.0 {
+            return Err(VTKparseError::FileFormat(
+                "DICOM slices share a position".to_string(),
+            ));
+        }
+        extent / (n - 1) as f64
+    } else {
+        thickness
+    };
+    // Pixel spacing is given as the distance between rows, then columns
+    let (row_spacing, column_spacing) = first.pixel_spacing.unwrap_or((1.0, 1.0));
+    let spacing = (
+        column_spacing as f32,
+        row_spacing as f32,
+        slice_spacing as f32,
+    );
+    let position = first.position.unwrap_or([0.0; 3]);
+    let origin = (position[0] as f32, position[1] as f32, position[2] as f32);
+
+    let scalar_type = first.scalar_type;
+    let data = if slices.iter().all(|x| x.rescale.is_none()) {
+        let mut bytes = Vec::new();
+        for slice in &slices {
+            bytes.extend_from_slice(&slice.pixels);
+        }
+        buffer::from_bytes(bytes, scalar_type, true)
+    } else {
+        let mut values = Vec::new();
+        for slice in &slices {
+            let data = buffer::from_bytes(slice.pixels.clone(), scalar_type, true);
+            let (slope, intercept) = slice.rescale.unwrap_or((1.0, 0.0));
+            values.extend(data.to_f64().into_iter().map(|x| x * slope + intercept));
+        }
+        buffer::from_f64(values.into_iter(), ScalarType::Float)
+    };
+
+    let samples = first.samples as usize;
+    let array = DataArray {
+        name: "values".to_string(),
+        kind: if samples == 1 {
+            DatasetAttributes::Scalars
+        } else {
+            DatasetAttributes::ColorScalars
+        },
+        numcomp: samples,
+        data,
+        lookup_table: Some("default".to_string()),
+    };
+    let dims = (first.columns as u32, first.rows as u32, n as u32);
+    let mut points = StructuredPoints::from_array(dims, origin, spacing, array);
+    let direction = |x: [f64; 3]| [x[0] as f32, x[1] as f32, x[2] as f32];
+    points.flip(axis_flips([
+        direction(row),
+        direction(column),
+        direction(normal),
+    ]));
+    Ok(points)
+}
+
+/// What is needed of a slice to place it in the volume
+struct Slice {
+    series: String,
+    instance: Option<i64>,
+    position: Option<[f64; 3]>,
+    orientation: Option<[f64; 6]>,
+    thickness: Option<f64>,
+    rows: u16,
+    columns: u16,
+    samples: u16,
+    scalar_type: ScalarType,
+    /// Distance between rows and between columns
+    pixel_spacing: Option<(f64, f64)>,
+    /// Slope and intercept, unless values are kept as they are
+    rescale: Option<(f64, f64)>,
+    pixels: Vec<u8>,
+}
+
+fn read_slice<R: Read>(mut reader: R) -> Result<Slice, VTKparseError> {
+    let mut preamble = [0; 132];
+    reader.read_exact(&mut preamble)?;
+    if &preamble[128..] != b"DICM" {
+        return Err(VTKparseError::UnknownFormat("Not a DICOM file".to_string()));
+    }
+
+    let mut parser = Parser {
+        reader,
+        explicit: true,
+    };
+    let mut fields = BTreeMap::new();
+    let pixels = loop {
+        let (tag, len) = match parser.element()? {
+            Some(x) => x,
+            None => return Err(VTKparseError::Expected("PixelData".to_string())),
+        };
+        if tag == PIXEL_DATA {
+            if len == UNDEFINED {
+                return Err(VTKparseError::NotImplemented(
+                    "Compressed DICOM pixel data".to_string(),
+                ));
+            }
+            break parser.value(len)?;
+        }
+        if len == UNDEFINED {
+            parser.skip_sequence()?;
+        } else if FIELDS.contains(&tag) {
+            let value = parser.value(len)?;
+            // The meta information gives the transfer syntax of the data set
+            if tag == TRANSFER_SYNTAX {
+                parser.explicit = match text(&value).as_str() {
+                    "1.2.840.10008.1.2" => false,
+                    "1.2.840.10008.1.2.1" => true,
+                    x => {
+                        return Err(VTKparseError::NotImplemented(format!(
+                            "DICOM transfer syntax {}",
+                            x
+                        )))
+                    }
+                };
+            }
+            fields.insert(tag, value);
+        } else {
+            parser.skip(len)?;
+        }
+    };
+
+    let require = |tag: Tag, name: &str| {
+        fields
+            .get(&tag)
+            .filter(|x| x.len() >= 2)
+            .map(|x| u16::from_le_bytes([x[0], x[1]]))
+            .ok_or_else(|| VTKparseError::Expected(name.to_string()))
+    };
+    // Optional values may be present but empty, and are left out if they
+    // cannot be read
+    let numbers = |tag: Tag| -> Option<Vec<f64>> {
+        let value = text(fields.get(&tag)?);
+        if value.trim().is_empty() {
+            return None;
+        }
+        value.split('\\').map(|x| x.trim().parse().ok()).collect()
+    };
+
+    let rows = require(ROWS, "Rows")?;
+    let columns = require(COLUMNS, "Columns")?;
+    let samples = require(SAMPLES, "SamplesPerPixel").unwrap_or(1);
+    if samples > 1 && require(PLANAR_CONFIGURATION, "PlanarConfiguration").unwrap_or(0) != 0 {
+        return Err(VTKparseError::NotImplemented(
+            "DICOM colour planes stored one after the other".to_string(),
+        ));
+    }
+    let signed = require(PIXEL_REPRESENTATION, "PixelRepresentation").unwrap_or(0) == 1;
+    let bits = require(BITS_ALLOCATED, "BitsAllocated")?;
+    let scalar_type = match (bits, signed) {
+        (8, false) => ScalarType::UnsignedChar,
+        (8, true) => ScalarType::Char,
+        (16, false) => ScalarType::UnsignedShort,
+        (16, true) => ScalarType::Short,
+        (32, false) => ScalarType::UnsignedInt,
+        (32, true) => ScalarType::Int,
+        _ => {
+            return Err(VTKparseError::NotImplemented(format!(
+                "DICOM pixels of {} bits",
+                bits
+            )))
+        }
+    };
+
+    let len = scalar_type.bytes(buffer::count(&[
+        rows as usize,
+        columns as usize,
+        samples as usize,
+    ])?)?;
+    if pixels.len() < len {
+        return Err(VTKparseError::UnknownFormat(format!(
+            "Number of elements is not correct. Expected {} but got {}",
+            len / scalar_type.size(),
+            pixels.len() / scalar_type.size()
+        )));
+    }
+    let mut pixels = pixels;
+    // Pixel data is padded to an even length
+    pixels.truncate(len);
+
+    let slope = numbers(RESCALE_SLOPE).and_then(|x| x.first().copied());
+    let intercept = numbers(RESCALE_INTERCEPT).and_then(|x| x.first().copied());
+    let rescale = match (slope.unwrap_or(1.0), intercept.unwrap_or(0.0)) {
+        (slope, intercept) if slope == 1.0 && intercept == 0.0 => None,
+        x => Some(x),
+    };
+
+    let triple = |x: Vec<f64>| x.get(..3).map(|x| [x[0], x[1], x[2]]);
+    Ok(Slice {
+        series: fields.get(&SERIES).map(|x| text(x)).unwrap_or_default(),
+        instance: fields
+            .get(&INSTANCE_NUMBER)
+            .and_then(|x| text(x).parse().ok()),
+        position: numbers(POSITION).and_then(triple),
+        orientation: numbers(ORIENTATION)
+            .and_then(|x| x.get(..6).map(|x| [x[0], x[1], x[2], x[3], x[4], x[5]])),
+        thickness: numbers(SLICE_THICKNESS).and_then(|x| x.first().copied()),
+        rows,
+        columns,
+        samples,
+        scalar_type,
+        pixel_spacing: numbers(PIXEL_SPACING).and_then(|x| x.get(..2).map(|x| (x[0], x[1]))),
+        rescale,
+        pixels,
+    })
+}
+
+/// A string value without its padding
+fn text(value: &[u8]) -> String {
+    String::from_utf8_lossy(value)
+        .trim_matches(|c: char| c == '\0' || c.is_whitespace())
+        .to_string()
+}
+
+/// Reads the elements of a little endian data set one at a time
+struct Parser<R> {
+    reader: R,
+    /// Whether elements of the data set give their VR
+    explicit: bool,
+}
+
+impl<R: Read> Parser<R> {
+    /// The tag and length of the next element, or `None` at the end
+    fn element(&mut self) -> Result<Option<(Tag, u32)>, VTKparseError> {
+        let mut header = [0; 8];
+        let mut read = 0;
+        while read < header.len() {
+            match self.reader.read(&mut header[read..])? {
+                0 if read == 0 => return Ok(None),
+                0 => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                n => read += n,
+            }
+        }
+        let tag = (
+            u16::from_le_bytes([header[0], header[1]]),
+            u16::from_le_bytes([header[2], header[3]]),
+        );
+        let u32_at =
+            |i: usize| u32::from_le_bytes([header[i], header[i + 1], header[i + 2], header[i + 3]]);
+
+        // Items and delimiters never give a VR, and the meta information
+        // always does
+        let explicit = tag.0 != 0xfffe && (self.explicit || tag.0 == 0x0002);
+        if !explicit {
+            return Ok(Some((tag, u32_at(4))));
+        }
+        let len = match &header[4..6] {
+            b"OB" | b"OD" | b"OF" | b"OL" | b"OV" | b"OW" | b"SQ" | b"SV" | b"UC" | b"UN"
+            | b"UR" | b"UT" | b"UV" => {
+                let mut len = [0; 4];
+                self.reader.read_exact(&mut len)?;
+                u32::from_le_bytes(len)
+            }
+            _ => u16::from_le_bytes([header[6], header[7]]) as u32,
+        };
+        Ok(Some((tag, len)))
+    }
+
+    fn value(&mut self, len: u32) -> Result<Vec<u8>, VTKparseError> {
+        let mut value = buffer::reserve(len as usize);
+        (&mut self.reader)
+            .take(len as u64)
+            .read_to_end(&mut value)?;
+        if value.len() != len as usize {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(value)
+    }
+
+    fn skip(&mut self, len: u32) -> Result<(), VTKparseError> {
+        let skipped = io::copy(&mut (&mut self.reader).take(len as u64), &mut io::sink())?;
+        if skipped != len as u64 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(())
+    }
+
+    /// Skips the items of a sequence of undefined length
+    fn skip_sequence(&mut self) -> Result<(), VTKparseError> {
+        loop {
+            match self.element()? {
+                Some((SEQUENCE_END, _)) => return Ok(()),
+                Some((ITEM, UNDEFINED)) => self.skip_item()?,
+                Some((ITEM, len)) => self.skip(len)?,
+                _ => {
+                    return Err(VTKparseError::FileFormat(
+                        "Invalid DICOM sequence".to_string(),
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Skips the elements of an item of undefined length
+    fn skip_item(&mut self) -> Result<(), VTKparseError> {
+        loop {
+            match self.element()? {
+                Some((ITEM_END, _)) => return Ok(()),
+                Some((_, UNDEFINED)) => self.skip_sequence()?,
+                Some((_, len)) => self.skip(len)?,
+                None => {
+                    return Err(VTKparseError::FileFormat(
+                        "Invalid DICOM sequence".to_string(),
+                    ))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+
+    /// Writes elements in explicit or implicit VR little endian
+    struct Writer {
+        bytes: Vec<u8>,
+        explicit: bool,
+    }
+
+    impl Writer {
+        fn new(syntax: &str) -> Self {
+            let mut writer = Self {
+                bytes: vec![0; 128],
+                explicit: true,
+            };
+            writer.bytes.extend_from_slice(b"DICM");
+            writer.element((0x0002, 0x0010), b"UI", syntax.as_bytes());
+            writer.explicit = syntax == "1.2.840.10008.1.2.1";
+            writer
+        }
+
+        fn element(&mut self, tag: Tag, vr: &[u8; 2], value: &[u8]) {
+            let mut value = value.to_vec();
+            if value.len() % 2 == 1 {
+                value.push(b' ');
+            }
+            self.bytes.extend_from_slice(&tag.0.to_le_bytes());
+            self.bytes.extend_from_slice(&tag.1.to_le_bytes());
+            let long = matches!(vr, b"OB" | b"OW" | b"SQ");
+            if self.explicit || tag.0 == 0x0002 {
+                self.bytes.extend_from_slice(vr);
+                if long {
+                    self.bytes.extend_from_slice(&[0, 0]);
+                    self.bytes
+                        .extend_from_slice(&(value.len() as u32).to_le_bytes());
+                } else {
+                    self.bytes
+                        .extend_from_slice(&(value.len() as u16).to_le_bytes());
+                }
+            } else {
+                self.bytes
+                    .extend_from_slice(&(value.len() as u32).to_le_bytes());
+            }
+            self.bytes.extend_from_slice(&value);
+        }
+    }
+
+    /// A 2x1 slice of signed shorts at height `z`
+    fn slice(syntax: &str, z: f64, pixels: [i16; 2], slope: &str) -> Vec<u8> {
+        let mut writer = Writer::new(syntax);
+        writer.element((0x0008, 0x0060), b"CS", b"CT");
+        // A sequence of undefined length, holding an item of undefined length
+        writer.bytes.extend_from_slice(&[0x08, 0, 0x15, 0x11]);
+        if writer.explicit {
+            writer.bytes.extend_from_slice(b"SQ\0\0");
+        }
+        writer.bytes.extend_from_slice(&UNDEFINED.to_le_bytes());
+        writer.bytes.extend_from_slice(&[0xfe, 0xff, 0x00, 0xe0]);
+        writer.bytes.extend_from_slice(&UNDEFINED.to_le_bytes());
+        writer.element((0x0008, 0x1150), b"UI", b"1.2.3");
+        writer
+            .bytes
+            .extend_from_slice(&[0xfe, 0xff, 0x0d, 0xe0, 0, 0, 0, 0]);
+        writer
+            .bytes
+            .extend_from_slice(&[0xfe, 0xff, 0xdd, 0xe0, 0, 0, 0, 0]);
+
+        writer.element(SERIES, b"UI", b"1.2.840.1");
+        writer.element(POSITION, b"DS", format!("-10\\20\\{}", z).as_bytes());
+        writer.element(ORIENTATION, b"DS", b"1\\0\\0\\0\\1\\0");
+        // Present but empty, as scanners often write it
+        writer.element(SLICE_THICKNESS, b"DS", b"");
+        writer.element(ROWS, b"US", &1u16.to_le_bytes());
+        writer.element(COLUMNS, b"US", &2u16.to_le_bytes());
+        writer.element(PIXEL_SPACING, b"DS", b"0.5\\0.25");
+        writer.element(BITS_ALLOCATED, b"US", &16u16.to_le_bytes());
+        writer.element(PIXEL_REPRESENTATION, b"US", &1u16.to_le_bytes());
+        writer.element(RESCALE_INTERCEPT, b"DS", b"-1024");
+        writer.element(RESCALE_SLOPE, b"DS", slope.as_bytes());
+        let pixels: Vec<u8> = pixels
+            .iter()
+            .flat_map(|x| x.to_le_bytes().to_vec())
+            .collect();
+        writer.element(PIXEL_DATA, b"OW", &pixels);
+        writer.bytes
+    }
+
+    #[test]
+    fn series() {
+        let directory =
+            std::env::temp_dir().join(format!("vtk_parser_dicom_series_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let explicit = "1.2.840.10008.1.2.1";
+        let implicit = "1.2.840.10008.1.2";
+        let slices = [
+            (explicit, 5.0, [5, 6], "1"),
+            (implicit, 0.0, [1, 2], "1"),
+            (explicit, 2.5, [3, 4], "2"),
+        ];
+        for (i, &(syntax, z, pixels, slope)) in slices.iter().enumerate() {
+            let bytes = slice(syntax, z, pixels, slope);
+            std::fs::write(directory.join(format!("IM{}", i)), bytes).unwrap();
+        }
+        std::fs::write(directory.join("DICOMDIR.txt"), "not a slice").unwrap();
+        assert!(is_dicom(directory.join("IM0")));
+        assert!(!is_dicom(directory.join("DICOMDIR.txt")));
+
+        let points = read_dicom_dir(&directory).unwrap();
+        assert_eq!(points.dims, (2, 1, 3));
+        assert_eq!(points.spacing, (0.25, 0.5, 2.5));
+        assert_eq!(points.origin, (-10.0, 20.0, 0.0));
+        assert_eq!(
+            points.scalars().unwrap().data,
+            Buffer::F32(vec![-1023.0, -1022.0, -1018.0, -1016.0, -1019.0, -1018.0])
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn unsupported() {
+        let bytes = slice("1.2.840.10008.1.2.4.50", 0.0, [0, 0], "1");
+        assert!(read_slice(&bytes[..]).is_err());
+        assert!(read_slice(&b"DICM"[..]).is_err());
+
+        // A slope which cannot be read is left out
+        let bytes = slice("1.2.840.10008.1.2.1", 0.0, [7, -7], "one");
+        assert_eq!(
+            read_slice(&bytes[..]).unwrap().rescale,
+            Some((1.0, -1024.0))
+        );
+
+        let bytes = slice("1.2.840.10008.1.2.1", 0.0, [7, -7], "1");
+        let slice = read_slice(&bytes[..bytes.len() - 1]);
+        assert!(slice.is_err());
+        let slice = read_slice(&bytes[..]).unwrap();
+        assert_eq!(slice.rescale, Some((1.0, -1024.0)));
+        assert_eq!(slice.thickness, None);
+        assert_eq!(slice.pixels.len(), 4);
+    }
+}
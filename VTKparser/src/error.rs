@@ -10,6 +10,48 @@ pub enum VTKparseError {
     ParseFloat(num::ParseFloatError),
     UnknownFormat(String),
     WrongFormat(String),
+    /// A line did not start with the given keyword
+    Expected(String),
+    /// An error together with where in the file it happened
+    Located(Location, Box<VTKparseError>),
+}
+
+/// The line being read when an error happened
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    /// Line number, starting at 1
+    pub line: usize,
+    /// Byte offset of the start of the line
+    pub offset: u64,
+    /// The line without its line break, cut short for binary data
+    pub content: String,
+}
+
+impl VTKparseError {
+    /// Where the error happened, if known
+    pub fn location(&self) -> Option<&Location> {
+        match *self {
+            VTKparseError::Located(ref location, _) => Some(location),
+            _ => None,
+        }
+    }
+
+    /// The keyword which was expected, if that was the error
+    pub fn expected(&self) -> Option<&str> {
+        match *self {
+            VTKparseError::Expected(ref keyword) => Some(keyword),
+            VTKparseError::Located(_, ref err) => err.expected(),
+            _ => None,
+        }
+    }
+
+    /// Attaches a location, unless there already is one
+    pub(crate) fn at(self, location: Location) -> Self {
+        match self {
+            VTKparseError::Located(..) => self,
+            err => VTKparseError::Located(location, Box::new(err)),
+        }
+    }
 }
 
 impl fmt::Display for VTKparseError {
@@ -22,6 +64,12 @@ impl fmt::Display for VTKparseError {
             VTKparseError::NotImplemented(ref err) => write!(f, "{} is not yet implemented", err),
             VTKparseError::UnknownFormat(ref err) => write!(f, "Format is not known: {}", err),
             VTKparseError::WrongFormat(ref err) => write!(f, "Format was not recognized: {}", err),
+            VTKparseError::Expected(ref keyword) => write!(f, "Expected keyword {}", keyword),
+            VTKparseError::Located(ref location, ref err) => write!(
+                f,
+                "{} at line {}, byte {}: {:?}",
+                err, location.line, location.offset, location.content
+            ),
         }
     }
 }
@@ -32,10 +80,12 @@ impl Error for VTKparseError {
             VTKparseError::Io(ref err) => Some(err),
             VTKparseError::ParseInt(ref err) => Some(err),
             VTKparseError::ParseFloat(ref err) => Some(err),
+            VTKparseError::Located(_, ref err) => Some(&**err),
             VTKparseError::FileFormat(_)
             | VTKparseError::NotImplemented(_)
             | VTKparseError::UnknownFormat(_)
-            | VTKparseError::WrongFormat(_) => None,
+            | VTKparseError::WrongFormat(_)
+            | VTKparseError::Expected(_) => None,
         }
     }
 }
@@ -0,0 +1,124 @@
+use std::io::prelude::*;
+
+use crate::attributes::DataArray;
+use crate::buffer::{self, ScalarType};
+use crate::{next_line, DatasetAttributes, VTKparseError};
+
+/// A named collection of arrays, each with its own number of tuples
+pub struct FieldData {
+    pub name: String,
+    pub arrays: Vec<DataArray>,
+}
+
+impl FieldData {
+    pub fn array(&self, name: &str) -> Option<&DataArray> {
+        self.arrays.iter().find(|x| x.name == name)
+    }
+}
+
+/// Parses the arrays following `line`, which is `FIELD name numArrays`
+///
+/// Every array starts with `name numComponents numTuples dataType`. Arrays
+/// written as `NULL_ARRAY` are skipped
+pub(crate) fn parse<R: BufRead>(
+    reader: &mut R,
+    binary: bool,
+    line: &str,
+) -> Result<FieldData, VTKparseError> {
+    let mut words = line.split_whitespace().skip(1);
+    let name = words
+        .next()
+        .ok_or_else(|| VTKparseError::FileFormat("FIELD has no name".to_string()))?
+        .to_string();
+    let n: usize = words
+        .next()
+        .ok_or_else(|| VTKparseError::FileFormat("FIELD has no number of arrays".to_string()))?
+        .parse()?;
+
+    let mut arrays = buffer::reserve(n);
+    for _ in 0..n {
+        let line = next_line(reader)?.ok_or_else(|| {
+            VTKparseError::FileFormat(format!("FIELD {} has fewer arrays than given", name))
+        })?;
+        let (array_name, numcomp, len, datatype) = match array_header(&line)? {
+            Some(header) => header,
+            None => continue,
+        };
+
+        let data = buffer::read(reader, binary, datatype, buffer::count(&[len, numcomp])?)?;
+
+        arrays.push(DataArray {
+            name: array_name,
+            kind: DatasetAttributes::FieldData,
+            numcomp,
+            data,
+            lookup_table: None,
+        });
+    }
+
+    Ok(FieldData { name, arrays })
+}
+
+/// Parses `name numComponents numTuples dataType` starting an array of a
+/// field, `None` for a `NULL_ARRAY`
+pub(crate) fn array_header(
+    line: &str,
+) -> Result<Option<(String, usize, usize, ScalarType)>, VTKparseError> {
+    let mut words = line.split_whitespace();
+    let array_name = words.next().unwrap_or_default().to_string();
+    if array_name == "NULL_ARRAY" {
+        return Ok(None);
+    }
+
+    let mut number = |what: &str| -> Result<usize, VTKparseError> {
+        Ok(words
+            .next()
+            .ok_or_else(|| {
+                VTKparseError::FileFormat(format!("Array {} has no {}", array_name, what))
+            })?
+            .parse()?)
+    };
+    let numcomp = number("number of components")?;
+    let len = number("number of tuples")?;
+
+    let datatype = words.next().ok_or_else(|| {
+        VTKparseError::FileFormat(format!("Array {} has no datatype", array_name))
+    })?;
+    let datatype = ScalarType::from_name(datatype)
+        .ok_or_else(|| VTKparseError::NotImplemented(format!("Datatype {}", datatype)))?;
+
+    Ok(Some((array_name, numcomp, len, datatype)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+
+    #[test]
+    fn field_arrays() {
+        let text = "TIME 1 1 double
+0.25
+NULL_ARRAY
+materials 2 3 int
+1 10
+2 20 3 30
+";
+        let field = parse(&mut text.as_bytes(), false, "FIELD FieldData 3").unwrap();
+        assert_eq!(field.name, "FieldData");
+        assert_eq!(field.arrays.len(), 2);
+        assert_eq!(field.array("TIME").unwrap().data, Buffer::F64(vec![0.25]));
+
+        let materials = field.array("materials").unwrap();
+        assert_eq!(materials.numcomp, 2);
+        assert_eq!(materials.len(), 3);
+    }
+
+    #[test]
+    fn binary_field() {
+        let mut bytes = b"steps 1 2 unsigned_short\n".to_vec();
+        bytes.extend_from_slice(&[0, 1, 0, 2]);
+        let field = parse(&mut &bytes[..], true, "FIELD f 1").unwrap();
+        assert_eq!(field.arrays[0].data, Buffer::U16(vec![1, 2]));
+    }
+}
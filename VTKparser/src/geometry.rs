@@ -0,0 +1,225 @@
+use std::io::prelude::*;
+
+use crate::buffer::{self, Buffer, ScalarType};
+use crate::{next_line, VTKparseError};
+
+/// Reads a `POINTS n dataType` section
+///
+/// If `expected` is given the number of points must match
+pub(crate) fn read_points<R: BufRead>(
+    reader: &mut R,
+    binary: bool,
+    expected: Option<usize>,
+) -> Result<Vec<[f64; 3]>, VTKparseError> {
+    let line = next_line(reader)?.unwrap_or_default();
+    if crate::keyword(&line) != "POINTS" {
+        return Err(VTKparseError::Expected("POINTS".to_string()));
+    }
+    let mut words = line.split_whitespace().skip(1);
+
+    let n: usize = words
+        .next()
+        .ok_or_else(|| VTKparseError::FileFormat("POINTS has no length".to_string()))?
+        .parse()?;
+    if let Some(expected) = expected {
+        if n != expected {
+            return Err(VTKparseError::FileFormat(format!(
+                "Number of points is {}, but the dimensions give {}",
+                n, expected
+            )));
+        }
+    }
+
+    let datatype = words.next().unwrap_or("float");
+    let datatype = ScalarType::from_name(datatype)
+        .ok_or_else(|| VTKparseError::NotImplemented(format!("Datatype {}", datatype)))?;
+
+    let data = buffer::read(reader, binary, datatype, buffer::count(&[n, 3])?)?.to_f64();
+    Ok(data.chunks_exact(3).map(|p| [p[0], p[1], p[2]]).collect())
+}
+
+/// Smallest and largest coordinate along each axis
+pub(crate) fn bounds(points: &[[f64; 3]]) -> [(f64, f64); 3] {
+    let mut bounds = [(f64::INFINITY, f64::NEG_INFINITY); 3];
+    for p in points {
+        for (b, &x) in bounds.iter_mut().zip(p) {
+            b.0 = b.0.min(x);
+            b.1 = b.1.max(x);
+        }
+    }
+    bounds
+}
+
+/// Cells given as a flat list of point indices, the points of cell `i` are
+/// `connectivity[offsets[i]..offsets[i + 1]]`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cells {
+    pub offsets: Vec<usize>,
+    pub connectivity: Vec<usize>,
+}
+
+impl Default for Cells {
+    fn default() -> Self {
+        Self {
+            offsets: vec![0],
+            connectivity: Vec::new(),
+        }
+    }
+}
+
+impl Cells {
+    /// Number of cells
+    pub fn len(&self) -> usize {
+        self.offsets.len().saturating_sub(1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Point indices of cell `i`
+    pub fn get(&self, i: usize) -> Option<&[usize]> {
+        if i >= self.len() {
+            return None;
+        }
+        Some(&self.connectivity[self.offsets[i]..self.offsets[i + 1]])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &[usize]> {
+        self.offsets
+            .windows(2)
+            .map(move |w| &self.connectivity[w[0]..w[1]])
+    }
+}
+
+/// Reads the cells following a header such as `POLYGONS n size`
+///
+/// The legacy layout gives each cell as its number of points followed by
+/// the indices. Files written by VTK 5.1 and newer instead have `n` as the
+/// number of offsets, followed by `OFFSETS` and `CONNECTIVITY` arrays
+pub(crate) fn read_cells<R: BufRead>(
+    reader: &mut R,
+    binary: bool,
+    line: &str,
+) -> Result<Cells, VTKparseError> {
+    let mut words = line.split_whitespace();
+    let keyword = words.next().unwrap_or_default();
+    let mut number = |what: &str| -> Result<usize, VTKparseError> {
+        Ok(words
+            .next()
+            .ok_or_else(|| VTKparseError::FileFormat(format!("{} has no {}", keyword, what)))?
+            .parse()?)
+    };
+    let n = number("number of cells")?;
+    let size = number("size")?;
+
+    let (offsets, read) = read_prefix(reader, b"OFFSETS")?;
+    let mut reader = (&read[..]).chain(reader);
+    if offsets {
+        let offsets = read_index_array(&mut reader, binary, "OFFSETS", n)?;
+        let connectivity = read_index_array(&mut reader, binary, "CONNECTIVITY", size)?;
+        if offsets.first().is_some_and(|&x| x != 0)
+            || offsets.windows(2).any(|w| w[0] > w[1])
+            || offsets.last().is_some_and(|&x| x != connectivity.len())
+        {
+            return Err(VTKparseError::FileFormat(format!(
+                "{} has invalid offsets",
+                keyword
+            )));
+        }
+        return Ok(Cells {
+            offsets: if offsets.is_empty() { vec![0] } else { offsets },
+            connectivity,
+        });
+    }
+
+    let data = to_indices(buffer::read(&mut reader, binary, ScalarType::Int, size)?)?;
+
+    let mut cells = Cells {
+        offsets: buffer::reserve(n.saturating_add(1)),
+        connectivity: Vec::with_capacity(size - n.min(size)),
+    };
+    cells.offsets.push(0);
+    let mut rest = &data[..];
+    for _ in 0..n {
+        let (&count, tail) = rest.split_first().ok_or_else(|| {
+            VTKparseError::FileFormat(format!("{} has fewer values than given", keyword))
+        })?;
+        if tail.len() < count {
+            return Err(VTKparseError::FileFormat(format!(
+                "{} has fewer values than given",
+                keyword
+            )));
+        }
+        cells.connectivity.extend_from_slice(&tail[..count]);
+        cells.offsets.push(cells.connectivity.len());
+        rest = &tail[count..];
+    }
+    if !rest.is_empty() {
+        return Err(VTKparseError::FileFormat(format!(
+            "{} has more values than given",
+            keyword
+        )));
+    }
+    Ok(cells)
+}
+
+/// Reads an `OFFSETS type` or `CONNECTIVITY type` array of `n` values
+fn read_index_array<R: BufRead>(
+    reader: &mut R,
+    binary: bool,
+    keyword: &str,
+    n: usize,
+) -> Result<Vec<usize>, VTKparseError> {
+    let line = next_line(reader)?.unwrap_or_default();
+    if crate::keyword(&line) != keyword {
+        return Err(VTKparseError::Expected(keyword.to_string()));
+    }
+    let mut words = line.split_whitespace().skip(1);
+    let datatype = words.next().unwrap_or("vtktypeint64");
+    let datatype = ScalarType::from_name(datatype)
+        .ok_or_else(|| VTKparseError::NotImplemented(format!("Datatype {}", datatype)))?;
+
+    to_indices(buffer::read(reader, binary, datatype, n)?)
+}
+
+fn to_indices(buffer: Buffer) -> Result<Vec<usize>, VTKparseError> {
+    buffer
+        .to_f64()
+        .into_iter()
+        .map(|x| {
+            if x < 0.0 || x.fract() != 0.0 {
+                Err(VTKparseError::FileFormat(format!("Invalid index {}", x)))
+            } else {
+                Ok(x as usize)
+            }
+        })
+        .collect()
+}
+
+/// Reads leading whitespace and as much of `prefix` as the data starts
+/// with, ignoring case, and tells whether all of it was there
+///
+/// The bytes read are returned to be put back in front of the reader, as
+/// the buffer of the reader may be too small to look ahead
+pub(crate) fn read_prefix<R: BufRead>(
+    reader: &mut R,
+    prefix: &[u8],
+) -> Result<(bool, Vec<u8>), VTKparseError> {
+    let mut read = Vec::new();
+    let mut matched = 0;
+    while matched < prefix.len() {
+        let byte = match reader.fill_buf()?.first() {
+            Some(&byte) => byte,
+            None => break,
+        };
+        if byte.eq_ignore_ascii_case(&prefix[matched]) {
+            matched += 1;
+        } else if matched > 0 || !byte.is_ascii_whitespace() {
+            break;
+        }
+        read.push(byte);
+        reader.consume(1);
+    }
+    Ok((matched == prefix.len(), read))
+}
@@ -3,15 +3,60 @@ use std::io::prelude::*;
 use std::io::BufReader;
 use std::path::Path;
 
-pub use error::VTKparseError;
+use tracking::Tracking;
+
+pub use attributes::{Attributes, DataArray, LookupTable};
+pub use buffer::{Buffer, ScalarType};
+pub use dicom::{is_dicom, read_dicom, read_dicom_dir};
+pub use error::{Location, VTKparseError};
+pub use field::FieldData;
+pub use geometry::Cells;
+pub use mhd::{mhd_data_files, read_mhd, read_mhd_from};
+pub use nifti::{read_nifti, read_nifti_from};
+pub use npy::{read_npy, read_npy_from, write_npy, write_npy_to};
+pub use nrrd::{nrrd_data_files, read_nrrd, read_nrrd_from, write_nrrd, write_nrrd_to};
+pub use polydata::PolyData;
+pub use probe::{probe_file, probe_from, ArrayInfo, Probe};
+pub use pvd::{read_collection, CollectionEntry};
+pub use raw::{read_raw, read_raw_from, write_raw, write_raw_to, RawLayout};
+pub use region::{read_region, read_region_from, Region};
+pub use rgrid::RectilinearGrid;
+pub use sgrid::StructuredGrid;
 pub use spoints::StructuredPoints;
+pub use stream::SliceReader;
+pub use ugrid::{CellType, UnstructuredGrid};
+pub use writer::{write_file, Writer};
 
+mod attributes;
+mod buffer;
+mod dicom;
 mod error;
+mod field;
+mod geometry;
+mod mhd;
+mod nifti;
+mod npy;
+mod nrrd;
+mod polydata;
+mod probe;
+mod pvd;
+mod raw;
+mod region;
+mod resample;
+mod rgrid;
+mod sgrid;
 mod spoints;
+mod stream;
+mod tracking;
+mod ugrid;
+mod vti;
+mod writer;
 
 pub struct Data {
     pub header: Header,
     pub dataset: Datatype,
+    /// Field data given for the whole dataset, such as the time step
+    pub field_data: Option<FieldData>,
 }
 
 pub struct Header {
@@ -22,16 +67,18 @@ pub struct Header {
 
 pub enum Datatype {
     StructuredPoints(StructuredPoints),
-    StructuredGrid,
-    RectilinearGrid,
-    PolyData,
-    UnstructuredGrid,
-    Field,
+    StructuredGrid(StructuredGrid),
+    RectilinearGrid(RectilinearGrid),
+    PolyData(PolyData),
+    UnstructuredGrid(UnstructuredGrid),
+    Field(FieldData),
     Empty,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DatasetAttributes {
     Scalars,
+    ColorScalars,
     LookupTable,
     Vectors,
     Normals,
@@ -47,26 +94,96 @@ impl Data {
         }
         None
     }
+
+    pub fn structured_grid(&self) -> Option<&StructuredGrid> {
+        if let Datatype::StructuredGrid(ref x) = self.dataset {
+            return Some(x);
+        }
+        None
+    }
+
+    pub fn poly_data(&self) -> Option<&PolyData> {
+        if let Datatype::PolyData(ref x) = self.dataset {
+            return Some(x);
+        }
+        None
+    }
+
+    pub fn unstructured_grid(&self) -> Option<&UnstructuredGrid> {
+        if let Datatype::UnstructuredGrid(ref x) = self.dataset {
+            return Some(x);
+        }
+        None
+    }
+
+    pub fn rectilinear_grid(&self) -> Option<&RectilinearGrid> {
+        if let Datatype::RectilinearGrid(ref x) = self.dataset {
+            return Some(x);
+        }
+        None
+    }
+
+    pub fn field(&self) -> Option<&FieldData> {
+        if let Datatype::Field(ref x) = self.dataset {
+            return Some(x);
+        }
+        None
+    }
 }
 
+/// Reads a legacy VTK file, or a VTK XML `ImageData` (.vti) file
 pub fn read_file<P: AsRef<Path>>(file: P) -> Result<Data, VTKparseError> {
     let file = File::open(file)?;
-    let mut reader = BufReader::new(file);
+    read_from(BufReader::new(file))
+}
+
+/// Reads a file held in memory
+pub fn read_bytes(bytes: &[u8]) -> Result<Data, VTKparseError> {
+    read_from(bytes)
+}
+
+/// Reads a file from any buffered source, such as an archive entry or a
+/// network stream
+pub fn read_from<R: BufRead>(mut reader: R) -> Result<Data, VTKparseError> {
+    if vti::is_xml(reader.fill_buf()?) {
+        return vti::parse(reader);
+    }
+
+    // Errors in legacy files are given with the line they happened on
+    let mut reader = Tracking::new(reader);
+    let data = read_header(&mut reader).and_then(|header| {
+        let (dataset, field_data) = dataset_parse(&mut reader, header.binary)?;
+        Ok(Data {
+            header,
+            dataset,
+            field_data,
+        })
+    });
+    data.map_err(|err| err.at(reader.location()))
+}
 
+/// Reads the version, title and format lines of a legacy file
+pub(crate) fn read_header<R: BufRead>(reader: &mut R) -> Result<Header, VTKparseError> {
     let mut version = String::new();
-    reader.read_line(&mut version)?;
+    while version.trim().is_empty() {
+        if reader.read_line(&mut version)? == 0 {
+            break;
+        }
+    }
 
     let version = get_version(&version)?;
 
+    // The title may be blank, so it is always the line after the version
     let mut header = String::with_capacity(256);
     reader.read_line(&mut header)?;
+    header.truncate(header.trim_end().len());
 
-    let mut file_format = String::new();
-    reader.read_line(&mut file_format)?;
+    let file_format = next_line(reader)?.unwrap_or_default();
+    let file_format = keyword(&file_format);
 
-    let binary = if file_format == "ASCII\n" {
+    let binary = if file_format == "ASCII" {
         Ok(false)
-    } else if file_format == "BINARY\n" {
+    } else if file_format == "BINARY" {
         Ok(true)
     } else {
         Err(VTKparseError::FileFormat(
@@ -74,18 +191,20 @@ pub fn read_file<P: AsRef<Path>>(file: P) -> Result<Data, VTKparseError> {
         ))
     }?;
 
-    Ok(Data {
-        header: Header {
-            file_version: version,
-            header: header,
-            binary: binary,
-        },
-        dataset: dataset_parse(reader, binary)?,
+    Ok(Header {
+        file_version: version,
+        header,
+        binary,
     })
 }
 
 fn get_version(version: &str) -> Result<(usize, usize), VTKparseError> {
-    if !version.starts_with("# vtk DataFile Version ") {
+    let version = version.trim_start_matches('\u{feff}').trim();
+    let identifier = "# vtk DataFile Version";
+    if !version
+        .get(..identifier.len())
+        .is_some_and(|x| x.eq_ignore_ascii_case(identifier))
+    {
         return Err(VTKparseError::UnknownFormat(
             "Identifier is not recognized".to_string(),
         ));
@@ -101,37 +220,313 @@ fn get_version(version: &str) -> Result<(usize, usize), VTKparseError> {
     Ok((d0.parse()?, d1.parse()?))
 }
 
-fn dataset_parse<R: Read>(
-    mut reader: BufReader<R>,
+/// Parses the dataset and the field data which may follow its `DATASET` line
+fn dataset_parse<R: BufRead>(
+    mut reader: R,
     binary: bool,
-) -> Result<Datatype, VTKparseError> {
-    let mut dataset = String::new();
-    reader.read_line(&mut dataset)?;
+) -> Result<(Datatype, Option<FieldData>), VTKparseError> {
+    let dataset = next_line(&mut reader)?.unwrap_or_default();
 
-    if !dataset.starts_with("DATASET ") {
-        return Ok(Datatype::Empty);
+    // A file may hold only field data, without a DATASET line
+    if keyword(&dataset) == "FIELD" {
+        let field = field::parse(&mut reader, binary, &dataset)?;
+        return Ok((Datatype::Field(field), None));
+    }
+    if keyword(&dataset) != "DATASET" {
+        return Ok((Datatype::Empty, None));
     }
 
-    let vtk_type = dataset.split_whitespace().last().unwrap().to_uppercase();
+    let vtk_type = dataset
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("")
+        .to_uppercase();
 
-    if vtk_type == "STRUCTURED_POINTS" {
-        Ok(Datatype::StructuredPoints(spoints::parse(
-            &mut reader,
-            binary,
-        )?))
+    let mut line = next_line(&mut reader)?.unwrap_or_default();
+    let field_data = if keyword(&line) == "FIELD" {
+        let field = field::parse(&mut reader, binary, &line)?;
+        if vtk_type == "FIELD" {
+            return Ok((Datatype::Field(field), None));
+        }
+        line = next_line(&mut reader)?.unwrap_or_default();
+        Some(field)
+    } else {
+        None
+    };
+    // The line read after the field starts the dataset
+    let mut reader = line.as_bytes().chain(reader);
+
+    let dataset = if vtk_type == "STRUCTURED_POINTS" {
+        Datatype::StructuredPoints(spoints::parse(&mut reader, binary)?)
     } else if vtk_type == "STRUCTURED_GRID" {
-        Ok(Datatype::StructuredGrid)
+        Datatype::StructuredGrid(sgrid::parse(&mut reader, binary)?)
     } else if vtk_type == "RECTILINEAR_GRID" {
-        Ok(Datatype::RectilinearGrid)
-    } else if vtk_type == "POLYGONAL_DATA" {
-        Ok(Datatype::PolyData)
+        Datatype::RectilinearGrid(rgrid::parse(&mut reader, binary)?)
+    } else if vtk_type == "POLYDATA" || vtk_type == "POLYGONAL_DATA" {
+        Datatype::PolyData(polydata::parse(&mut reader, binary)?)
     } else if vtk_type == "UNSTRUCTURED_GRID" {
-        Ok(Datatype::UnstructuredGrid)
+        Datatype::UnstructuredGrid(ugrid::parse(&mut reader, binary)?)
     } else if vtk_type == "FIELD" {
-        Ok(Datatype::Field)
+        return Err(VTKparseError::Expected("FIELD".to_string()));
     } else {
-        Err(VTKparseError::FileFormat(
-            "Unknown dataset type".to_string(),
-        ))
+        return Err(VTKparseError::FileFormat(format!(
+            "Unknown dataset type {}",
+            vtk_type
+        )));
+    };
+
+    Ok((dataset, field_data))
+}
+
+/// Reads the next line which is neither blank nor a `#` comment, `None` at
+/// the end of the file
+pub(crate) fn next_line<R: BufRead>(reader: &mut R) -> Result<Option<String>, VTKparseError> {
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let trimmed = line.trim();
+        if !trimmed.is_empty() && !trimmed.starts_with('#') {
+            return Ok(Some(line));
+        }
+    }
+}
+
+/// The first word of a line in upper case, as keywords are matched without
+/// regard to case
+pub(crate) fn keyword(line: &str) -> String {
+    line.split_whitespace()
+        .next()
+        .unwrap_or("")
+        .to_ascii_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_bytes, read_file, read_from};
+    use std::io::BufReader;
+
+    #[test]
+    fn read_ascii_file() {
+        let path =
+            std::env::temp_dir().join(format!("vtk_parser_ascii_{}.vtk", std::process::id()));
+        std::fs::write(
+            &path,
+            "# vtk DataFile Version 3.0
+ascii volume
+ASCII
+DATASET STRUCTURED_POINTS
+DIMENSIONS 2 2 1
+ORIGIN 0 0 0
+SPACING 1 1 1
+POINT_DATA 4
+SCALARS volume unsigned_char
+LOOKUP_TABLE default
+1 2
+3 4
+",
+        )
+        .unwrap();
+
+        let data = read_file(&path).unwrap();
+        assert!(!data.header.binary);
+        assert!(data.field_data.is_none());
+        let points = data.structured_points().unwrap();
+        assert_eq!(points.dims, (2, 2, 1));
+        assert_eq!(
+            points.scalars().unwrap().data,
+            super::Buffer::U8(vec![1, 2, 3, 4])
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn dataset_field_data() {
+        let path =
+            std::env::temp_dir().join(format!("vtk_parser_field_{}.vtk", std::process::id()));
+        std::fs::write(
+            &path,
+            "# vtk DataFile Version 3.0
+time step
+ASCII
+DATASET POLYDATA
+FIELD FieldData 1
+TIME 1 1 double
+1.5
+POINTS 1 float
+0 0 0
+",
+        )
+        .unwrap();
+
+        let data = read_file(&path).unwrap();
+        let field = data.field_data.as_ref().unwrap();
+        assert_eq!(field.array("TIME").unwrap().data.get(0), Some(1.5));
+        assert_eq!(data.poly_data().unwrap().points.len(), 1);
+
+        std::fs::write(
+            &path,
+            "# vtk DataFile Version 3.0
+materials only
+ASCII
+DATASET FIELD
+FIELD materials 1
+density 1 3 float
+1000 2700 7800
+",
+        )
+        .unwrap();
+
+        let data = read_file(&path).unwrap();
+        let field = data.field().unwrap();
+        assert_eq!(field.name, "materials");
+        assert_eq!(field.arrays[0].len(), 3);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn overflowing_counts() {
+        let header = "# vtk DataFile Version 3.0\ncounts\nASCII\nDATASET STRUCTURED_POINTS\n";
+        let texts = [
+            "DIMENSIONS 1 1 1\nORIGIN 0 0 0\nSPACING 1 1 1\n\
+             POINT_DATA 9223372036854775807\nVECTORS v float\n0 0 0\n",
+            "DIMENSIONS 4294967295 4294967295 4294967295\nORIGIN 0 0 0\nSPACING 1 1 1\n",
+        ];
+        for text in texts.iter() {
+            assert!(read_bytes(format!("{}{}", header, text).as_bytes()).is_err());
+        }
+    }
+
+    #[test]
+    fn field_before_dataset() {
+        let text = "# vtk DataFile Version 3.0
+field first
+ASCII
+DATASET STRUCTURED_POINTS
+# the field comes first
+FIELD fd 1
+TIME 1 1 double
+2.5
+DIMENSIONS 2 1 1
+ORIGIN 0 0 0
+SPACING 1 1 1
+POINT_DATA 2
+SCALARS a float
+LOOKUP_TABLE default
+1 2
+";
+        for n in 1..=64 {
+            let data = read_from(BufReader::with_capacity(n, text.as_bytes())).unwrap();
+            assert_eq!(data.field_data.as_ref().unwrap().arrays[0].name, "TIME");
+            assert_eq!(data.structured_points().unwrap().dims, (2, 1, 1));
+        }
+    }
+
+    #[test]
+    fn tooth_from_memory() {
+        let bytes = std::fs::read("../data/tooth.vtk").unwrap();
+        let file = read_file("../data/tooth.vtk").unwrap();
+        let file = file.structured_points().unwrap();
+
+        let memory = read_bytes(&bytes).unwrap();
+        // A tiny buffer makes every section cross a buffer boundary
+        let small = read_from(BufReader::with_capacity(7, &bytes[..])).unwrap();
+
+        for data in &[memory, small] {
+            let points = data.structured_points().unwrap();
+            assert_eq!(points.dims, file.dims);
+            assert_eq!(points.spacing, file.spacing);
+            assert_eq!(points.scalars().unwrap().data, file.scalars().unwrap().data);
+        }
+    }
+
+    #[test]
+    fn located_errors() {
+        let text = "# vtk DataFile Version 3.0
+broken
+ASCII
+DATASET STRUCTURED_POINTS
+DIMENSIONS 2 1 1
+ORIGN 0 0 0
+SPACING 1 1 1
+";
+        let err = read_bytes(text.as_bytes()).err().unwrap();
+        assert_eq!(err.expected(), Some("ORIGIN"));
+        let location = err.location().unwrap();
+        assert_eq!(location.line, 6);
+        assert_eq!(location.offset, text.find("ORIGN").unwrap() as u64);
+        assert_eq!(location.content, "ORIGN 0 0 0");
+
+        let text = text.replace("ORIGN", "ORIGIN")
+            + "POINT_DATA 2\nSCALARS a float\nLOOKUP_TABLE default\n1.0 x\n";
+        let err = read_bytes(text.as_bytes()).err().unwrap();
+        assert!(err.to_string().contains("line 11"));
+        assert_eq!(err.location().unwrap().content, "1.0 x");
+    }
+
+    #[test]
+    fn tolerant_headers() {
+        let plain = "# vtk DataFile Version 3.0
+variants
+ASCII
+DATASET STRUCTURED_POINTS
+DIMENSIONS 2 2 1
+ORIGIN 1 2 3
+SPACING 0.5 0.5 1
+POINT_DATA 4
+SCALARS volume unsigned_char
+LOOKUP_TABLE default
+1 2
+3 4
+";
+        let corpus = [
+            plain.to_string(),
+            plain.replace('\n', "\r\n"),
+            plain.replace('\n', "  \n"),
+            plain
+                .replace("ASCII\n", "\nascii\n\n")
+                .replace("\nDIM", "\n\nDIM"),
+            plain.replace("DATASET", "# a comment\nDATASET"),
+            plain
+                .replace("# vtk DataFile", "\u{feff}# VTK DATAFILE")
+                .replace("DATASET STRUCTURED_POINTS", "dataset structured_points")
+                .replace("DIMENSIONS", "dimensions")
+                .replace("LOOKUP_TABLE", "lookup_table"),
+            plain.replace(
+                "DIMENSIONS 2 2 1\nORIGIN 1 2 3\nSPACING 0.5 0.5 1",
+                "SPACING 0.5 0.5 1\nDIMENSIONS 2 2 1\nORIGIN 1 2 3",
+            ),
+            plain.replace(
+                "DIMENSIONS 2 2 1\nORIGIN 1 2 3\nSPACING 0.5 0.5 1",
+                "origin 1 2 3\r\n\r\nAspect_Ratio 0.5 0.5 1 \r\n Dimensions 2 2 1",
+            ),
+        ];
+
+        for text in &corpus {
+            let data = read_bytes(text.as_bytes()).unwrap();
+            assert_eq!(data.header.header, "variants");
+            let points = data.structured_points().unwrap();
+            assert_eq!(points.dims, (2, 2, 1));
+            assert_eq!(points.origin, (1.0, 2.0, 3.0));
+            assert_eq!(points.spacing, (0.5, 0.5, 1.0));
+            assert_eq!(
+                points.scalars().unwrap().data,
+                super::Buffer::U8(vec![1, 2, 3, 4])
+            );
+
+            // The same headers in front of binary values
+            let binary = text
+                .replacen("ASCII", "BINARY", 1)
+                .replace("ascii", "binary");
+            let mut binary = binary.as_bytes()[..binary.rfind("1 2").unwrap()].to_vec();
+            binary.extend_from_slice(&[1, 2, 3, 4]);
+            let data = read_bytes(&binary).unwrap();
+            assert!(data.header.binary);
+            assert_eq!(
+                data.structured_points().unwrap().scalars().unwrap().data,
+                super::Buffer::U8(vec![1, 2, 3, 4])
+            );
+        }
     }
 }
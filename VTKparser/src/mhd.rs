@@ -0,0 +1,422 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+use crate::attributes::DataArray;
+use crate::buffer::{self, ScalarType};
+use crate::nrrd::numbered_names;
+use crate::spoints::axis_flips;
+use crate::{DatasetAttributes, StructuredPoints, VTKparseError};
+
+/// Reads a MetaImage, either a `.mha` with the data following the header or
+/// a `.mhd` header naming the files which hold the data
+///
+/// Compressed data is not supported. Axes which the `TransformMatrix`
+/// reverses are flipped
+pub fn read_mhd<P: AsRef<Path>>(file: P) -> Result<StructuredPoints, VTKparseError> {
+    let file = file.as_ref();
+    let mut reader = BufReader::new(File::open(file)?);
+    let header = Header::read(&mut reader)?;
+    let files = header.data_files(file.parent().unwrap_or_else(|| Path::new("")))?;
+    if files.is_empty() {
+        let payload = header.read_payload(&mut reader, header.payload_len()?, false)?;
+        return header.decode(payload);
+    }
+
+    // Every file holds an equal share of the data, after its own header
+    let len = header.payload_len()? / files.len();
+    let mut payload = Vec::new();
+    for path in files {
+        let mut reader = BufReader::new(File::open(path)?);
+        payload.extend(header.read_payload(&mut reader, len, true)?);
+    }
+    header.decode(payload)
+}
+
+/// Reads a MetaImage with the data following the header
+pub fn read_mhd_from<R: BufRead>(mut reader: R) -> Result<StructuredPoints, VTKparseError> {
+    let header = Header::read(&mut reader)?;
+    if !header
+        .require("ElementDataFile")?
+        .eq_ignore_ascii_case("LOCAL")
+    {
+        return Err(VTKparseError::FileFormat(
+            "MetaImage headers naming data files have to be read from a file".to_string(),
+        ));
+    }
+    let payload = header.read_payload(&mut reader, header.payload_len()?, false)?;
+    header.decode(payload)
+}
+
+/// The files holding the data of a MetaImage, which is empty when the data
+/// follows the header
+pub fn mhd_data_files<P: AsRef<Path>>(file: P) -> Result<Vec<PathBuf>, VTKparseError> {
+    let file = file.as_ref();
+    let header = Header::read(&mut BufReader::new(File::open(file)?))?;
+    header.data_files(file.parent().unwrap_or_else(|| Path::new("")))
+}
+
+/// The fields of a MetaImage header, with keys in lower case
+struct Header {
+    fields: Vec<(String, String)>,
+    /// File names following `ElementDataFile = LIST`
+    list: Vec<String>,
+}
+
+impl Header {
+    /// Reads `Key = Value` lines up to `ElementDataFile`, which ends the
+    /// header, and the list of files which may follow it
+    fn read<R: BufRead>(reader: &mut R) -> Result<Self, VTKparseError> {
+        let mut fields = Vec::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(VTKparseError::Expected("ElementDataFile".to_string()));
+            }
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = match line.find('=') {
+                Some(i) => (line[..i].trim().to_lowercase(), line[i + 1..].trim()),
+                None if fields.is_empty() => {
+                    return Err(VTKparseError::UnknownFormat(
+                        "Not a MetaImage file".to_string(),
+                    ))
+                }
+                None => {
+                    return Err(VTKparseError::FileFormat(format!(
+                        "Invalid MetaImage field {}",
+                        line
+                    )))
+                }
+            };
+            let last = key == "elementdatafile";
+            fields.push((key, value.to_string()));
+            if last {
+                break;
+            }
+        }
+
+        let mut header = Self {
+            fields,
+            list: Vec::new(),
+        };
+        if header.get("elementdatafile").map(crate::keyword).as_deref() == Some("LIST") {
+            for line in reader.lines() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    header.list.push(line.trim().to_string());
+                }
+            }
+        }
+        if let Some(kind) = header.get("objecttype") {
+            if !kind.eq_ignore_ascii_case("image") {
+                return Err(VTKparseError::NotImplemented(format!(
+                    "MetaImage objects of type {}",
+                    kind
+                )));
+            }
+        }
+        Ok(header)
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(x, _)| x == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The first of several names of a field which is given
+    fn get_any(&self, keys: &[&str]) -> Option<&str> {
+        keys.iter().find_map(|key| self.get(key))
+    }
+
+    fn require(&self, key: &str) -> Result<&str, VTKparseError> {
+        self.get(&key.to_lowercase())
+            .ok_or_else(|| VTKparseError::Expected(key.to_string()))
+    }
+
+    fn flag(&self, keys: &[&str]) -> bool {
+        self.get_any(keys)
+            .is_some_and(|x| x.eq_ignore_ascii_case("true"))
+    }
+
+    fn numbers(&self, keys: &[&str]) -> Result<Option<Vec<f32>>, VTKparseError> {
+        match self.get_any(keys) {
+            Some(x) => Ok(Some(
+                x.split_whitespace()
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// The files named by `ElementDataFile`, relative to `directory`
+    ///
+    /// The field holds `LOCAL`, a single name, `LIST` followed by names on
+    /// the lines after the header, or a format such as `slice%03d.raw 1 10 1`
+    /// with the first and last number and the step
+    fn data_files(&self, directory: &Path) -> Result<Vec<PathBuf>, VTKparseError> {
+        let value = self.require("ElementDataFile")?;
+        let words: Vec<&str> = value.split_whitespace().collect();
+        let names = match words.first().map(|x| x.to_uppercase()).as_deref() {
+            Some("LOCAL") => Vec::new(),
+            Some("LIST") => self.list.clone(),
+            _ if words.len() >= 4 && words[0].contains('%') => {
+                numbered_names(&words[..4], self.values()?)?
+            }
+            _ => vec![value.to_string()],
+        };
+        Ok(names.into_iter().map(|x| directory.join(x)).collect())
+    }
+
+    fn scalar_type(&self) -> Result<ScalarType, VTKparseError> {
+        let name = self.require("ElementType")?;
+        let scalar_type = match name.trim_end_matches("_ARRAY") {
+            "MET_CHAR" => ScalarType::Char,
+            "MET_UCHAR" => ScalarType::UnsignedChar,
+            "MET_SHORT" => ScalarType::Short,
+            "MET_USHORT" => ScalarType::UnsignedShort,
+            "MET_INT" | "MET_LONG" => ScalarType::Int,
+            "MET_UINT" | "MET_ULONG" => ScalarType::UnsignedInt,
+            "MET_LONG_LONG" => ScalarType::Long,
+            "MET_ULONG_LONG" => ScalarType::UnsignedLong,
+            "MET_FLOAT" => ScalarType::Float,
+            "MET_DOUBLE" => ScalarType::Double,
+            _ => {
+                return Err(VTKparseError::NotImplemented(format!(
+                    "MetaImage type {}",
+                    name
+                )))
+            }
+        };
+        Ok(scalar_type)
+    }
+
+    fn dims(&self) -> Result<(u32, u32, u32), VTKparseError> {
+        let ndims: usize = self.require("NDims")?.parse()?;
+        let sizes = self
+            .require("DimSize")?
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<Vec<u32>, _>>()?;
+        if sizes.len() != ndims || !(1..=3).contains(&ndims) {
+            return Err(VTKparseError::NotImplemented(format!(
+                "MetaImage with sizes {:?}",
+                sizes
+            )));
+        }
+        let axis = |i: usize| sizes.get(i).copied().unwrap_or(1);
+        Ok((axis(0), axis(1), axis(2)))
+    }
+
+    fn numcomp(&self) -> Result<usize, VTKparseError> {
+        Ok(self.get("elementnumberofchannels").unwrap_or("1").parse()?)
+    }
+
+    fn values(&self) -> Result<usize, VTKparseError> {
+        let (x, y, z) = self.dims()?;
+        buffer::count(&[x as usize, y as usize, z as usize, self.numcomp()?])
+    }
+
+    /// Number of bytes of binary data
+    fn payload_len(&self) -> Result<usize, VTKparseError> {
+        self.scalar_type()?.bytes(self.values()?)
+    }
+
+    fn binary(&self) -> bool {
+        self.get("binarydata")
+            .is_none_or(|x| x.eq_ignore_ascii_case("true"))
+    }
+
+    /// Reads `len` bytes of binary data, or all of the text of ASCII data,
+    /// skipping `HeaderSize` bytes first in separate data files
+    fn read_payload<R: BufRead>(
+        &self,
+        reader: &mut R,
+        len: usize,
+        separate: bool,
+    ) -> Result<Vec<u8>, VTKparseError> {
+        if self.flag(&["compresseddata"]) {
+            return Err(VTKparseError::NotImplemented(
+                "Compressed MetaImage data".to_string(),
+            ));
+        }
+        let skip: i64 = if separate {
+            self.get("headersize").unwrap_or("0").parse()?
+        } else {
+            0
+        };
+
+        let mut bytes = Vec::new();
+        if skip == -1 && self.binary() {
+            // The data is at the end of the file
+            reader.read_to_end(&mut bytes)?;
+            bytes.drain(..bytes.len().saturating_sub(len));
+        } else if skip < 0 {
+            return Err(VTKparseError::FileFormat(format!(
+                "Invalid header size {}",
+                skip
+            )));
+        } else {
+            io::copy(&mut reader.take(skip as u64), &mut io::sink())?;
+            if self.binary() {
+                reader.take(len as u64).read_to_end(&mut bytes)?;
+            } else {
+                reader.read_to_end(&mut bytes)?;
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Turns the data into a volume laid out as the header describes
+    fn decode(&self, payload: Vec<u8>) -> Result<StructuredPoints, VTKparseError> {
+        let scalar_type = self.scalar_type()?;
+        let dims = self.dims()?;
+        let n = self.values()?;
+        let data = if self.binary() {
+            let len = scalar_type.bytes(n)?;
+            if payload.len() != len {
+                return Err(VTKparseError::UnknownFormat(format!(
+                    "Number of elements is not correct. Expected {} but got {}",
+                    n,
+                    payload.len() / scalar_type.size()
+                )));
+            }
+            let big_endian = self.flag(&["binarydatabyteordermsb", "elementbyteordermsb"]);
+            buffer::from_bytes(payload, scalar_type, !big_endian)
+        } else {
+            buffer::read_ascii(&mut &payload[..], scalar_type, n)?
+        };
+
+        let triple = |x: Option<Vec<f32>>, default: f32| {
+            let x = x.unwrap_or_default();
+            let axis = |i: usize| x.get(i).copied().unwrap_or(default);
+            (axis(0), axis(1), axis(2))
+        };
+        let spacing = triple(self.numbers(&["elementspacing", "elementsize"])?, 1.0);
+        let origin = triple(self.numbers(&["offset", "origin", "position"])?, 0.0);
+
+        let mut points = StructuredPoints::from_array(
+            dims,
+            origin,
+            spacing,
+            DataArray {
+                name: "values".to_string(),
+                kind: DatasetAttributes::Scalars,
+                numcomp: self.numcomp()?,
+                data,
+                lookup_table: Some("default".to_string()),
+            },
+        );
+        if let Some(matrix) = self.numbers(&["transformmatrix", "rotation", "orientation"])? {
+            let ndims = (matrix.len() as f64).sqrt() as usize;
+            if ndims * ndims != matrix.len() {
+                return Err(VTKparseError::FileFormat(format!(
+                    "TransformMatrix has {} values",
+                    matrix.len()
+                )));
+            }
+            // Row i is the direction of axis i
+            let direction = |i: usize| {
+                let axis = |j: usize| {
+                    if i < ndims && j < ndims {
+                        matrix[i * ndims + j]
+                    } else {
+                        (i == j) as u8 as f32
+                    }
+                };
+                [axis(0), axis(1), axis(2)]
+            };
+            points.flip(axis_flips([direction(0), direction(1), direction(2)]));
+        }
+        Ok(points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+
+    #[test]
+    fn local() {
+        let mut bytes = b"ObjectType = Image
+NDims = 2
+DimSize = 3 2
+ElementNumberOfChannels = 1
+ElementSpacing = 0.5 2
+Offset = 10 20
+TransformMatrix = -1 0 0 1
+BinaryData = True
+BinaryDataByteOrderMSB = True
+ElementType = MET_SHORT
+ElementDataFile = LOCAL
+"
+        .to_vec();
+        for x in &[1i16, 2, 3, 4, 5, -6] {
+            bytes.extend_from_slice(&x.to_be_bytes());
+        }
+
+        let read = read_mhd_from(&bytes[..]).unwrap();
+        assert_eq!(read.dims, (3, 2, 1));
+        assert_eq!(read.spacing, (0.5, 2.0, 1.0));
+        assert_eq!(read.origin, (9.0, 20.0, 0.0));
+        assert_eq!(
+            read.scalars().unwrap().data,
+            Buffer::I16(vec![3, 2, 1, -6, 5, 4])
+        );
+
+        bytes.truncate(bytes.len() - 1);
+        assert!(read_mhd_from(&bytes[..]).is_err());
+        let text = "NDims = 1\nDimSize = 2\nElementDataFile = LOCAL\n";
+        assert_eq!(
+            read_mhd_from(text.as_bytes()).err().unwrap().expected(),
+            Some("ElementType")
+        );
+    }
+
+    #[test]
+    fn separate() {
+        let directory =
+            std::env::temp_dir().join(format!("vtk_parser_mhd_separate_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        for (i, values) in [[1.5f32, 2.5], [-1.0, 4.0]].iter().enumerate() {
+            let mut bytes = vec![0xff; 4];
+            for x in values {
+                bytes.extend_from_slice(&x.to_le_bytes());
+            }
+            std::fs::write(directory.join(format!("slice{}.raw", i)), bytes).unwrap();
+        }
+        let header = "NDims = 3
+DimSize = 1 2 2
+ElementSpacing = 1 1 3
+ElementType = MET_FLOAT
+";
+        let fields = [
+            "HeaderSize = 4\nElementDataFile = slice%d.raw 0 1 1",
+            "HeaderSize = -1\nElementDataFile = LIST\nslice0.raw\nslice1.raw",
+        ];
+        for field in fields.iter() {
+            let path = directory.join("volume.mhd");
+            std::fs::write(&path, format!("{}{}\n", header, field)).unwrap();
+            assert_eq!(
+                mhd_data_files(&path).unwrap(),
+                vec![directory.join("slice0.raw"), directory.join("slice1.raw")]
+            );
+
+            let read = read_mhd(&path).unwrap();
+            assert_eq!(read.dims, (1, 2, 2));
+            assert_eq!(read.spacing, (1.0, 1.0, 3.0));
+            assert_eq!(
+                read.scalars().unwrap().data,
+                Buffer::F32(vec![1.5, 2.5, -1.0, 4.0])
+            );
+        }
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
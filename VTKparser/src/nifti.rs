@@ -0,0 +1,415 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{self, BufReader};
+use std::path::Path;
+
+use crate::attributes::DataArray;
+use crate::buffer::{self, ScalarType};
+use crate::spoints::axis_flips;
+use crate::{DatasetAttributes, StructuredPoints, VTKparseError};
+
+const HEADER_SIZE: usize = 348;
+
+/// Reads a NIfTI-1 file, either a single `.nii` or a `.hdr` header with its
+/// data in the `.img` next to it, as volumes with their time
+///
+/// A three dimensional file gives one volume at time 0, a four dimensional
+/// one a volume for each timestep, with the time in seconds when the units
+/// are given, or else its number. Values are scaled by `scl_slope` and `scl_inter` into floats.
+/// The spacing and origin come from the sform, else from the qform, else
+/// from `pixdim`. Axes which the orientation reverses are flipped.
+/// Compressed files are not supported
+pub fn read_nifti<P: AsRef<Path>>(file: P) -> Result<Vec<(f64, StructuredPoints)>, VTKparseError> {
+    let file = file.as_ref();
+    let mut reader = BufReader::new(File::open(file)?);
+    let header = Header::read(&mut reader)?;
+    if header.pair {
+        let mut data = BufReader::new(File::open(file.with_extension("img"))?);
+        io::copy(&mut (&mut data).take(header.vox_offset), &mut io::sink())?;
+        header.read_volumes(data)
+    } else {
+        io::copy(
+            &mut (&mut reader).take(header.vox_offset.saturating_sub(HEADER_SIZE as u64)),
+            &mut io::sink(),
+        )?;
+        header.read_volumes(reader)
+    }
+}
+
+/// Reads a single file NIfTI-1 image
+pub fn read_nifti_from<R: Read>(
+    mut reader: R,
+) -> Result<Vec<(f64, StructuredPoints)>, VTKparseError> {
+    let header = Header::read(&mut reader)?;
+    if header.pair {
+        return Err(VTKparseError::FileFormat(
+            "NIfTI headers with the data in an .img have to be read from a file".to_string(),
+        ));
+    }
+    io::copy(
+        &mut (&mut reader).take(header.vox_offset.saturating_sub(HEADER_SIZE as u64)),
+        &mut io::sink(),
+    )?;
+    header.read_volumes(reader)
+}
+
+/// The fields of a NIfTI-1 header which describe the layout of the data
+struct Header {
+    little_endian: bool,
+    /// The data is in a separate `.img` file
+    pair: bool,
+    dims: (u32, u32, u32),
+    timesteps: usize,
+    scalar_type: ScalarType,
+    numcomp: usize,
+    spacing: (f32, f32, f32),
+    origin: (f32, f32, f32),
+    /// Directions of the three axes in world space
+    directions: [[f32; 3]; 3],
+    time_step: f64,
+    time_offset: f64,
+    vox_offset: u64,
+    scale: Option<(f64, f64)>,
+}
+
+impl Header {
+    fn read<R: Read>(reader: &mut R) -> Result<Self, VTKparseError> {
+        let mut bytes = vec![0; HEADER_SIZE];
+        reader.read_exact(&mut bytes)?;
+
+        let little_endian = match &bytes[..4] {
+            [0x5c, 0x01, 0, 0] => true,
+            [0, 0, 0x01, 0x5c] => false,
+            _ => {
+                return Err(VTKparseError::UnknownFormat(
+                    "Not a NIfTI-1 file".to_string(),
+                ))
+            }
+        };
+        let pair = match &bytes[344..348] {
+            b"n+1\0" => false,
+            b"ni1\0" => true,
+            _ => {
+                return Err(VTKparseError::NotImplemented(
+                    "Analyze files without a NIfTI-1 header".to_string(),
+                ))
+            }
+        };
+
+        let field = |offset: usize, len: usize| {
+            let mut x = [0; 4];
+            x[..len].copy_from_slice(&bytes[offset..offset + len]);
+            if !little_endian {
+                x[..len].reverse();
+            }
+            x
+        };
+        let short = |offset: usize| {
+            let x = field(offset, 2);
+            i16::from_le_bytes([x[0], x[1]])
+        };
+        let float = |offset: usize| f32::from_le_bytes(field(offset, 4));
+
+        let dim: Vec<i16> = (0..8).map(|i| short(40 + 2 * i)).collect();
+        let pixdim: Vec<f32> = (0..8).map(|i| float(76 + 4 * i)).collect();
+        let ndims = dim[0];
+        if !(1..=7).contains(&ndims) || dim[1..=ndims as usize].iter().any(|&x| x < 1) {
+            return Err(VTKparseError::FileFormat(format!(
+                "Invalid NIfTI dimensions {:?}",
+                dim
+            )));
+        }
+        let size = |i: usize| {
+            if i <= ndims as usize {
+                dim[i] as u32
+            } else {
+                1
+            }
+        };
+        if (5..=7).any(|i| size(i) > 1) {
+            return Err(VTKparseError::NotImplemented(format!(
+                "NIfTI files with more than four dimensions {:?}",
+                dim
+            )));
+        }
+
+        let datatype = short(70);
+        let (scalar_type, numcomp) = match datatype {
+            2 => (ScalarType::UnsignedChar, 1),
+            4 => (ScalarType::Short, 1),
+            8 => (ScalarType::Int, 1),
+            16 => (ScalarType::Float, 1),
+            64 => (ScalarType::Double, 1),
+            128 => (ScalarType::UnsignedChar, 3),
+            256 => (ScalarType::Char, 1),
+            512 => (ScalarType::UnsignedShort, 1),
+            768 => (ScalarType::UnsignedInt, 1),
+            1024 => (ScalarType::Long, 1),
+            1280 => (ScalarType::UnsignedLong, 1),
+            2304 => (ScalarType::UnsignedChar, 4),
+            _ => {
+                return Err(VTKparseError::NotImplemented(format!(
+                    "NIfTI datatype {}",
+                    datatype
+                )))
+            }
+        };
+
+        // Scaling does not apply to colours
+        let (slope, inter) = (float(112) as f64, float(116) as f64);
+        let scale = if numcomp == 1 && slope != 0.0 && (slope, inter) != (1.0, 0.0) {
+            Some((slope, inter))
+        } else {
+            None
+        };
+
+        // Without units of time, such as for Hz or ppm, timesteps are
+        // numbered instead
+        let units = match bytes[123] & 0x38 {
+            8 => Some(1.0),
+            16 => Some(1e-3),
+            24 => Some(1e-6),
+            _ => None,
+        };
+
+        let mut header = Self {
+            little_endian,
+            pair,
+            dims: (size(1), size(2), size(3)),
+            timesteps: size(4) as usize,
+            scalar_type,
+            numcomp,
+            spacing: (pixdim[1].abs(), pixdim[2].abs(), pixdim[3].abs()),
+            origin: (0.0, 0.0, 0.0),
+            directions: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            time_step: units.map_or(1.0, |x| pixdim[4] as f64 * x),
+            time_offset: units.map_or(0.0, |x| float(136) as f64 * x),
+            vox_offset: float(108).max(0.0) as u64,
+            scale,
+        };
+        // Axes of a single point have no spacing
+        for x in [
+            &mut header.spacing.0,
+            &mut header.spacing.1,
+            &mut header.spacing.2,
+        ] {
+            if *x == 0.0 || !x.is_finite() {
+                *x = 1.0;
+            }
+        }
+
+        let (qform_code, sform_code) = (short(252), short(254));
+        if sform_code > 0 {
+            let row = |offset: usize| {
+                [
+                    float(offset),
+                    float(offset + 4),
+                    float(offset + 8),
+                    float(offset + 12),
+                ]
+            };
+            let rows = [row(280), row(296), row(312)];
+            let column = |i: usize| [rows[0][i], rows[1][i], rows[2][i]];
+            let norm = |x: [f32; 3]| x.iter().map(|x| x * x).sum::<f32>().sqrt();
+            header.spacing = (norm(column(0)), norm(column(1)), norm(column(2)));
+            header.origin = (rows[0][3], rows[1][3], rows[2][3]);
+            header.directions = [column(0), column(1), column(2)];
+        } else if qform_code > 0 {
+            let (b, c, d) = (float(256), float(260), float(264));
+            let a = (1.0 - (b * b + c * c + d * d)).max(0.0).sqrt();
+            let qfac = if pixdim[0] < 0.0 { -1.0 } else { 1.0 };
+            header.origin = (float(268), float(272), float(276));
+            header.directions = [
+                [
+                    a * a + b * b - c * c - d * d,
+                    2.0 * (b * c + a * d),
+                    2.0 * (b * d - a * c),
+                ],
+                [
+                    2.0 * (b * c - a * d),
+                    a * a + c * c - b * b - d * d,
+                    2.0 * (c * d + a * b),
+                ],
+                [
+                    qfac * 2.0 * (b * d + a * c),
+                    qfac * 2.0 * (c * d - a * b),
+                    qfac * (a * a + d * d - b * b - c * c),
+                ],
+            ];
+        }
+        Ok(header)
+    }
+
+    /// Reads the data of every timestep, which starts at the reader
+    fn read_volumes<R: Read>(
+        &self,
+        mut reader: R,
+    ) -> Result<Vec<(f64, StructuredPoints)>, VTKparseError> {
+        let (x, y, z) = self.dims;
+        let n = buffer::count(&[x as usize, y as usize, z as usize, self.numcomp])?;
+        let len = self.scalar_type.bytes(n)?;
+
+        let mut volumes = buffer::reserve(self.timesteps);
+        for t in 0..self.timesteps {
+            let mut bytes = buffer::reserve(len);
+            (&mut reader).take(len as u64).read_to_end(&mut bytes)?;
+            if bytes.len() != len {
+                return Err(VTKparseError::UnknownFormat(format!(
+                    "Number of elements is not correct. Expected {} but got {}",
+                    n * self.timesteps,
+                    (t * len + bytes.len()) / self.scalar_type.size()
+                )));
+            }
+            let mut data = buffer::from_bytes(bytes, self.scalar_type, self.little_endian);
+            if let Some((slope, inter)) = self.scale {
+                data = data
+                    .rescale((0.0, 1.0), (inter, inter + slope))
+                    .cast(ScalarType::Float);
+            }
+
+            let array = DataArray {
+                name: "values".to_string(),
+                kind: if self.numcomp == 1 {
+                    DatasetAttributes::Scalars
+                } else {
+                    DatasetAttributes::ColorScalars
+                },
+                numcomp: self.numcomp,
+                data,
+                lookup_table: Some("default".to_string()),
+            };
+            let mut points =
+                StructuredPoints::from_array(self.dims, self.origin, self.spacing, array);
+            points.flip(axis_flips(self.directions));
+            let time = self.time_offset + t as f64 * self.time_step;
+            volumes.push((time, points));
+        }
+        Ok(volumes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+
+    /// A header of shorts in the given byte order, with the fields
+    /// `(offset, bytes)` overwritten
+    fn header(little_endian: bool, dim: &[i16], fields: &[(usize, Vec<u8>)]) -> Vec<u8> {
+        let short = |x: i16| {
+            if little_endian {
+                x.to_le_bytes().to_vec()
+            } else {
+                x.to_be_bytes().to_vec()
+            }
+        };
+        let float = |x: f32| {
+            if little_endian {
+                x.to_le_bytes().to_vec()
+            } else {
+                x.to_be_bytes().to_vec()
+            }
+        };
+        let mut bytes = vec![0; HEADER_SIZE];
+        let mut put =
+            |offset: usize, x: Vec<u8>| bytes[offset..offset + x.len()].copy_from_slice(&x);
+        put(
+            0,
+            if little_endian {
+                348i32.to_le_bytes()
+            } else {
+                348i32.to_be_bytes()
+            }
+            .to_vec(),
+        );
+        for (i, &x) in dim.iter().enumerate() {
+            put(40 + 2 * i, short(x));
+        }
+        put(70, short(4));
+        put(72, short(16));
+        for (i, &x) in [1.0f32, 0.5, 2.0, 3.0, 1.5].iter().enumerate() {
+            put(76 + 4 * i, float(x));
+        }
+        put(108, float(352.0));
+        put(344, b"n+1\0".to_vec());
+        for (offset, x) in fields {
+            put(*offset, x.clone());
+        }
+        bytes
+    }
+
+    #[test]
+    fn time_series() {
+        // Two timesteps of 2x1x1 shorts, scaled, with the time in ms
+        let mut bytes = header(
+            true,
+            &[4, 2, 1, 1, 2],
+            &[
+                (112, 2.0f32.to_le_bytes().to_vec()),
+                (116, 1.0f32.to_le_bytes().to_vec()),
+                (123, vec![2 | 16]),
+            ],
+        );
+        bytes.extend_from_slice(&[0; 4]);
+        for x in &[1i16, 2, 3, -4] {
+            bytes.extend_from_slice(&x.to_le_bytes());
+        }
+
+        let volumes = read_nifti_from(&bytes[..]).unwrap();
+        assert_eq!(volumes.len(), 2);
+        assert_eq!(volumes[1].0, 0.0015);
+        let (_, ref points) = volumes[1];
+        assert_eq!(points.dims, (2, 1, 1));
+        assert_eq!(points.spacing, (0.5, 2.0, 3.0));
+        assert_eq!(points.scalars().unwrap().data, Buffer::F32(vec![7.0, -7.0]));
+
+        // Timesteps in Hz are numbered
+        bytes[123] = 2 | 32;
+        assert_eq!(read_nifti_from(&bytes[..]).unwrap()[1].0, 1.0);
+
+        bytes.truncate(bytes.len() - 1);
+        assert!(read_nifti_from(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn orientation() {
+        // A big-endian radiological sform, x running right to left
+        let row = |x: [f32; 4]| x.iter().flat_map(|x| x.to_be_bytes().to_vec()).collect();
+        let mut bytes = header(
+            false,
+            &[3, 3, 2, 1],
+            &[
+                (254, 1i16.to_be_bytes().to_vec()),
+                (280, row([-2.0, 0.0, 0.0, 10.0])),
+                (296, row([0.0, 1.0, 0.0, -5.0])),
+                (312, row([0.0, 0.0, 4.0, 0.0])),
+            ],
+        );
+        bytes.extend_from_slice(&[0; 4]);
+        for x in &[1i16, 2, 3, 4, 5, 6] {
+            bytes.extend_from_slice(&x.to_be_bytes());
+        }
+
+        let (_, points) = read_nifti_from(&bytes[..]).unwrap().pop().unwrap();
+        assert_eq!(points.spacing, (2.0, 1.0, 4.0));
+        assert_eq!(points.origin, (6.0, -5.0, 0.0));
+        assert_eq!(
+            points.scalars().unwrap().data,
+            Buffer::I16(vec![3, 2, 1, 6, 5, 4])
+        );
+
+        // The same as a qform with b = 0, c = 1, d = 0, turning x and z
+        bytes[254..256].copy_from_slice(&[0, 0]);
+        bytes[252..254].copy_from_slice(&1i16.to_be_bytes());
+        bytes[260..264].copy_from_slice(&1.0f32.to_be_bytes());
+        let (_, points) = read_nifti_from(&bytes[..]).unwrap().pop().unwrap();
+        assert_eq!(points.spacing, (0.5, 2.0, 3.0));
+        assert_eq!(
+            points.scalars().unwrap().data,
+            Buffer::I16(vec![3, 2, 1, 6, 5, 4])
+        );
+
+        bytes[344..348].copy_from_slice(b"ni1\0");
+        assert!(read_nifti_from(&bytes[..]).is_err());
+    }
+}
@@ -0,0 +1,286 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use crate::attributes::DataArray;
+use crate::buffer::{self, Buffer, ScalarType};
+use crate::{DatasetAttributes, StructuredPoints, VTKparseError};
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+/// Reads a NumPy `.npy` array as a volume
+///
+/// The axes of a C ordered array are `(z, y, x)`, and a fourth axis holds
+/// the components. Fortran ordered arrays are `(x, y, z)`. Arrays with
+/// fewer axes get dimensions of one for the missing ones. NumPy stores no
+/// geometry, so the origin is zero and the spacing one
+pub fn read_npy<P: AsRef<Path>>(file: P) -> Result<StructuredPoints, VTKparseError> {
+    read_npy_from(BufReader::new(File::open(file)?))
+}
+
+pub fn read_npy_from<R: Read>(mut reader: R) -> Result<StructuredPoints, VTKparseError> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic[..6] != MAGIC {
+        return Err(VTKparseError::UnknownFormat("Not a NumPy file".to_string()));
+    }
+    let header_len = if magic[6] == 1 {
+        let mut len = [0; 2];
+        reader.read_exact(&mut len)?;
+        u16::from_le_bytes(len) as usize
+    } else {
+        let mut len = [0; 4];
+        reader.read_exact(&mut len)?;
+        u32::from_le_bytes(len) as usize
+    };
+    let mut header = buffer::reserve(header_len);
+    (&mut reader)
+        .take(header_len as u64)
+        .read_to_end(&mut header)?;
+    if header.len() != header_len {
+        return Err(VTKparseError::FileFormat(
+            "NumPy header is truncated".to_string(),
+        ));
+    }
+    let header = String::from_utf8_lossy(&header);
+
+    let field = |key: &str| {
+        value(&header, key)
+            .ok_or_else(|| VTKparseError::FileFormat(format!("NumPy header has no {}", key)))
+    };
+    let descr = field("descr")?;
+    let fortran_order = field("fortran_order")? == "True";
+    let shape = field("shape")?
+        .trim_matches(|c| c == '(' || c == ')')
+        .split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(str::parse)
+        .collect::<Result<Vec<u32>, _>>()?;
+
+    let (scalar_type, little_endian) = descr_type(descr)?;
+    let (dims, numcomp) = match (shape.len(), fortran_order) {
+        (0, _) => ((1, 1, 1), 1),
+        (1..=3, true) => (
+            (
+                shape[0],
+                shape.get(1).copied().unwrap_or(1),
+                shape.get(2).copied().unwrap_or(1),
+            ),
+            1,
+        ),
+        (1..=3, false) => {
+            let axis = |i: usize| shape.len().checked_sub(i + 1).map_or(1, |i| shape[i]);
+            ((axis(0), axis(1), axis(2)), 1)
+        }
+        (4, false) => ((shape[2], shape[1], shape[0]), shape[3] as usize),
+        _ => {
+            return Err(VTKparseError::NotImplemented(format!(
+                "NumPy arrays of shape {:?}{}",
+                shape,
+                if fortran_order {
+                    " in Fortran order"
+                } else {
+                    ""
+                }
+            )))
+        }
+    };
+
+    let n = buffer::count(&shape.iter().map(|&x| x as usize).collect::<Vec<_>>())?;
+    let data = if scalar_type == ScalarType::Bit {
+        // Booleans take a byte each
+        let mut bytes = buffer::reserve(n);
+        (&mut reader).take(n as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != n {
+            return Err(short(n, bytes.len()));
+        }
+        Buffer::Bit(bytes.into_iter().map(|x| x != 0).collect())
+    } else {
+        let len = scalar_type.bytes(n)?;
+        let mut bytes = buffer::reserve(len);
+        (&mut reader).take(len as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != len {
+            return Err(short(n, bytes.len() / scalar_type.size()));
+        }
+        buffer::from_bytes(bytes, scalar_type, little_endian)
+    };
+
+    let array = DataArray {
+        name: "values".to_string(),
+        kind: DatasetAttributes::Scalars,
+        numcomp,
+        data,
+        lookup_table: Some("default".to_string()),
+    };
+    Ok(StructuredPoints::from_array(
+        dims,
+        (0.0, 0.0, 0.0),
+        (1.0, 1.0, 1.0),
+        array,
+    ))
+}
+
+fn short(expected: usize, got: usize) -> VTKparseError {
+    VTKparseError::UnknownFormat(format!(
+        "Number of elements is not correct. Expected {} but got {}",
+        expected, got
+    ))
+}
+
+/// The value of a key in the Python dictionary of the header, without
+/// quotes
+fn value<'a>(header: &'a str, key: &str) -> Option<&'a str> {
+    let start = header
+        .find(&format!("'{}'", key))
+        .or_else(|| header.find(&format!("\"{}\"", key)))?;
+    let rest = header[start + key.len() + 2..].trim_start();
+    let rest = rest.strip_prefix(':')?.trim_start();
+
+    let end = match rest.chars().next()? {
+        q @ '\'' | q @ '"' => return rest[1..].find(q).map(|end| &rest[1..end + 1]),
+        '(' => rest.find(')')? + 1,
+        _ => rest.find([',', '}']).unwrap_or(rest.len()),
+    };
+    Some(rest[..end].trim())
+}
+
+/// The type and byte order of a NumPy type description such as `<u2`
+fn descr_type(descr: &str) -> Result<(ScalarType, bool), VTKparseError> {
+    let (little_endian, kind) = match descr.chars().next() {
+        Some('>') => (false, &descr[1..]),
+        Some('<') | Some('|') | Some('=') => (true, &descr[1..]),
+        _ => (true, descr),
+    };
+    let scalar_type = match kind {
+        "b1" | "?" => ScalarType::Bit,
+        "u1" => ScalarType::UnsignedChar,
+        "i1" => ScalarType::Char,
+        "u2" => ScalarType::UnsignedShort,
+        "i2" => ScalarType::Short,
+        "u4" => ScalarType::UnsignedInt,
+        "i4" => ScalarType::Int,
+        "u8" => ScalarType::UnsignedLong,
+        "i8" => ScalarType::Long,
+        "f4" => ScalarType::Float,
+        "f8" => ScalarType::Double,
+        _ => {
+            return Err(VTKparseError::NotImplemented(format!(
+                "NumPy type {}",
+                descr
+            )))
+        }
+    };
+    Ok((scalar_type, little_endian))
+}
+
+fn descr(scalar_type: ScalarType) -> &'static str {
+    match scalar_type {
+        ScalarType::Bit => "|b1",
+        ScalarType::UnsignedChar => "|u1",
+        ScalarType::Char => "|i1",
+        ScalarType::UnsignedShort => "<u2",
+        ScalarType::Short => "<i2",
+        ScalarType::UnsignedInt => "<u4",
+        ScalarType::Int => "<i4",
+        ScalarType::UnsignedLong => "<u8",
+        ScalarType::Long => "<i8",
+        ScalarType::Float => "<f4",
+        ScalarType::Double => "<f8",
+    }
+}
+
+/// Writes the first point array as a C ordered `.npy` array of shape
+/// `(z, y, x)`, or `(z, y, x, components)` for more than one component
+pub fn write_npy<P: AsRef<Path>>(file: P, points: &StructuredPoints) -> Result<(), VTKparseError> {
+    write_npy_to(BufWriter::new(File::create(file)?), points)
+}
+
+pub fn write_npy_to<W: Write>(
+    mut writer: W,
+    points: &StructuredPoints,
+) -> Result<(), VTKparseError> {
+    let array = points.single_array()?;
+    let (x, y, z) = points.dims;
+    let mut shape = format!("{}, {}, {}", z, y, x);
+    if array.numcomp > 1 {
+        shape += &format!(", {}", array.numcomp);
+    }
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': ({}), }}",
+        descr(array.data.scalar_type()),
+        shape
+    );
+    // The data starts on a multiple of 64 bytes, after a newline
+    while !(MAGIC.len() + 4 + header.len() + 1).is_multiple_of(64) {
+        header.push(' ');
+    }
+    header.push('\n');
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[1, 0])?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    match array.data {
+        Buffer::Bit(ref x) => {
+            let bytes: Vec<u8> = x.iter().map(|&b| b as u8).collect();
+            writer.write_all(&bytes)?;
+        }
+        ref data => buffer::write_bytes(&mut writer, data, true)?,
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_and_read() {
+        let array = DataArray {
+            name: "values".to_string(),
+            kind: DatasetAttributes::Scalars,
+            numcomp: 2,
+            data: Buffer::F32((0..48).map(|x| x as f32 * 0.5).collect()),
+            lookup_table: None,
+        };
+        let points =
+            StructuredPoints::from_array((4, 3, 2), (0.0, 0.0, 0.0), (1.0, 1.0, 1.0), array);
+
+        let mut bytes = Vec::new();
+        write_npy_to(&mut bytes, &points).unwrap();
+        let header_end = bytes.iter().position(|&b| b == b'\n').unwrap() + 1;
+        assert_eq!(header_end % 64, 0);
+        assert!(String::from_utf8_lossy(&bytes).contains("'shape': (2, 3, 4, 2)"));
+
+        let read = read_npy_from(&bytes[..]).unwrap();
+        assert_eq!(read.dims, (4, 3, 2));
+        let read = &read.attributes.point[0];
+        assert_eq!(read.numcomp, 2);
+        assert_eq!(read.data, points.attributes.point[0].data);
+    }
+
+    #[test]
+    fn header_variants() {
+        let mut bytes = MAGIC.to_vec();
+        let header = "{\"descr\": \">i2\", \"fortran_order\": True, \"shape\": (3,)}\n";
+        bytes.extend_from_slice(&[2, 0]);
+        bytes.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend_from_slice(&[0, 1, 0xff, 0xfe, 0, 3]);
+
+        let read = read_npy_from(&bytes[..]).unwrap();
+        assert_eq!(read.dims, (3, 1, 1));
+        assert_eq!(read.scalars().unwrap().data, Buffer::I16(vec![1, -2, 3]));
+
+        bytes.truncate(bytes.len() - 1);
+        assert!(read_npy_from(&bytes[..]).is_err());
+        assert!(read_npy_from(&b"not numpy at all"[..]).is_err());
+
+        // A header length far beyond the data
+        bytes[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(read_npy_from(&bytes[..]).is_err());
+    }
+}
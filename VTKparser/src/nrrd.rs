@@ -0,0 +1,670 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use crate::attributes::DataArray;
+use crate::buffer::{self, ScalarType};
+use crate::spoints::axis_flips;
+use crate::{DatasetAttributes, StructuredPoints, VTKparseError};
+
+/// Reads a NRRD file, either a `.nrrd` with the data attached or a `.nhdr`
+/// header naming the files which hold the data
+///
+/// The data may be raw or ASCII. An axis of four dimensional data whose
+/// kind is not a domain, usually the first, holds the components. The
+/// spacing is the length of the space directions, or given by `spacings`.
+/// Axes which the space directions reverse are flipped
+pub fn read_nrrd<P: AsRef<Path>>(file: P) -> Result<StructuredPoints, VTKparseError> {
+    let file = file.as_ref();
+    let mut reader = BufReader::new(File::open(file)?);
+    let header = Header::read(&mut reader)?;
+    let files = header.data_files(file.parent().unwrap_or_else(|| Path::new("")))?;
+    if files.is_empty() {
+        return header.read_data(reader);
+    }
+
+    // Every file holds an equal share of the data, after its own skips
+    let len = header.payload_len()? / files.len();
+    let mut payload = Vec::new();
+    for path in files {
+        let mut reader = BufReader::new(File::open(path)?);
+        payload.extend(header.read_payload(&mut reader, len)?);
+    }
+    header.decode(payload)
+}
+
+/// Reads a NRRD file with the data attached to the header
+pub fn read_nrrd_from<R: BufRead>(mut reader: R) -> Result<StructuredPoints, VTKparseError> {
+    let header = Header::read(&mut reader)?;
+    if header.get("data file").is_some() {
+        return Err(VTKparseError::FileFormat(
+            "Detached NRRD headers have to be read from a file".to_string(),
+        ));
+    }
+    header.read_data(reader)
+}
+
+/// The files holding the data of a detached NRRD header, which is empty
+/// when the data is attached
+pub fn nrrd_data_files<P: AsRef<Path>>(file: P) -> Result<Vec<PathBuf>, VTKparseError> {
+    let file = file.as_ref();
+    let header = Header::read(&mut BufReader::new(File::open(file)?))?;
+    header.data_files(file.parent().unwrap_or_else(|| Path::new("")))
+}
+
+/// The fields of a NRRD header, with keys in lower case
+pub(crate) struct Header {
+    fields: Vec<(String, String)>,
+    /// File names following `data file: LIST`
+    list: Vec<String>,
+}
+
+impl Header {
+    /// Reads the header up to the blank line before the data
+    pub(crate) fn read<R: BufRead>(reader: &mut R) -> Result<Self, VTKparseError> {
+        let mut magic = String::new();
+        reader.read_line(&mut magic)?;
+        if !magic.starts_with("NRRD000") {
+            return Err(VTKparseError::UnknownFormat("Not a NRRD file".to_string()));
+        }
+
+        let mut fields: Vec<(String, String)> = Vec::new();
+        let mut list = Vec::new();
+        let mut listing = false;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            let line = line.trim_end_matches(&['\r', '\n'][..]);
+            if listing {
+                // The rest of a detached header names the data files
+                if !line.trim().is_empty() {
+                    list.push(line.trim().to_string());
+                }
+                continue;
+            }
+            if line.is_empty() {
+                break;
+            }
+            // Comments, and key/value pairs which carry no layout
+            if line.starts_with('#') || line.contains(":=") {
+                continue;
+            }
+            let (key, value) = match line.find(": ") {
+                Some(i) => (line[..i].to_lowercase(), line[i + 2..].trim().to_string()),
+                None => {
+                    return Err(VTKparseError::FileFormat(format!(
+                        "Invalid NRRD field {}",
+                        line
+                    )))
+                }
+            };
+            // Older spellings of the fields
+            let key = match key.as_str() {
+                "datafile" => "data file".to_string(),
+                "lineskip" => "line skip".to_string(),
+                "byteskip" => "byte skip".to_string(),
+                _ => key,
+            };
+            listing = key == "data file" && value.split_whitespace().next() == Some("LIST");
+            fields.push((key, value));
+        }
+        Ok(Self { fields, list })
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(x, _)| x == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn require(&self, key: &str) -> Result<&str, VTKparseError> {
+        self.get(key)
+            .ok_or_else(|| VTKparseError::Expected(key.to_string()))
+    }
+
+    /// The files named by `data file`, relative to `directory`
+    ///
+    /// The field holds a single name, `LIST` followed by names on the lines
+    /// after the header, or a format such as `slice%03d.raw 1 10 1` with
+    /// the first and last number and the step
+    pub(crate) fn data_files(&self, directory: &Path) -> Result<Vec<PathBuf>, VTKparseError> {
+        let value = match self.get("data file") {
+            Some(x) => x,
+            None => return Ok(Vec::new()),
+        };
+        let words: Vec<&str> = value.split_whitespace().collect();
+        let names = if words.first() == Some(&"LIST") {
+            self.list.clone()
+        } else if words.len() >= 4 && words[0].contains('%') {
+            // Every file holds at least one value
+            numbered_names(&words[..4], self.values()?)?
+        } else {
+            vec![value.to_string()]
+        };
+        Ok(names.into_iter().map(|x| directory.join(x)).collect())
+    }
+
+    /// Reads the data which follows an attached header
+    fn read_data<R: BufRead>(&self, mut reader: R) -> Result<StructuredPoints, VTKparseError> {
+        let payload = self.read_payload(&mut reader, self.payload_len()?)?;
+        self.decode(payload)
+    }
+
+    fn scalar_type(&self) -> Result<ScalarType, VTKparseError> {
+        nrrd_type(self.require("type")?)
+    }
+
+    fn sizes(&self) -> Result<Vec<u32>, VTKparseError> {
+        let dimension: usize = self.require("dimension")?.parse()?;
+        let sizes = self
+            .require("sizes")?
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<Vec<u32>, _>>()?;
+        if sizes.len() != dimension || !(1..=4).contains(&dimension) {
+            return Err(VTKparseError::NotImplemented(format!(
+                "NRRD with sizes {:?}",
+                sizes
+            )));
+        }
+        Ok(sizes)
+    }
+
+    fn values(&self) -> Result<usize, VTKparseError> {
+        let sizes: Vec<usize> = self.sizes()?.iter().map(|&x| x as usize).collect();
+        buffer::count(&sizes)
+    }
+
+    /// Number of bytes of raw data
+    fn payload_len(&self) -> Result<usize, VTKparseError> {
+        self.scalar_type()?.bytes(self.values()?)
+    }
+
+    /// Skips the lines and bytes asked for, and reads `len` bytes of raw
+    /// data or all of the text of ASCII data
+    fn read_payload<R: BufRead>(
+        &self,
+        reader: &mut R,
+        len: usize,
+    ) -> Result<Vec<u8>, VTKparseError> {
+        let line_skip: usize = self.get("line skip").unwrap_or("0").parse()?;
+        for _ in 0..line_skip {
+            reader.read_line(&mut String::new())?;
+        }
+        let raw = self.require("encoding")? == "raw";
+        let byte_skip: i64 = self.get("byte skip").unwrap_or("0").parse()?;
+
+        let mut bytes = Vec::new();
+        if byte_skip == -1 && raw {
+            // The data is at the end of the file
+            reader.read_to_end(&mut bytes)?;
+            bytes.drain(..bytes.len().saturating_sub(len));
+        } else if byte_skip < 0 {
+            return Err(VTKparseError::FileFormat(format!(
+                "Invalid byte skip {}",
+                byte_skip
+            )));
+        } else {
+            io::copy(&mut reader.take(byte_skip as u64), &mut io::sink())?;
+            if raw {
+                reader.take(len as u64).read_to_end(&mut bytes)?;
+            } else {
+                reader.read_to_end(&mut bytes)?;
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Turns the data into a volume laid out as the header describes
+    fn decode(&self, payload: Vec<u8>) -> Result<StructuredPoints, VTKparseError> {
+        let scalar_type = self.scalar_type()?;
+        let sizes = self.sizes()?;
+        let dimension = sizes.len();
+
+        // Which axis holds the components, if any
+        let kinds: Vec<String> = self.get("kinds").map_or_else(Vec::new, |x| {
+            x.split_whitespace().map(str::to_lowercase).collect()
+        });
+        let components = if dimension == 4 {
+            Some(
+                kinds
+                    .iter()
+                    .position(|x| x != "domain" && x != "space" && x != "time")
+                    .ok_or_else(|| {
+                        VTKparseError::NotImplemented(
+                            "NRRD with four dimensions but no axis of components".to_string(),
+                        )
+                    })?,
+            )
+        } else {
+            None
+        };
+        if components.is_some_and(|axis| axis != 0) {
+            return Err(VTKparseError::NotImplemented(
+                "NRRD with components on another axis than the first".to_string(),
+            ));
+        }
+        let offset = components.map_or(0, |_| 1);
+        let numcomp = components.map_or(1, |axis| sizes[axis] as usize);
+        let axis = |i: usize| sizes.get(i + offset).copied().unwrap_or(1);
+        let dims = (axis(0), axis(1), axis(2));
+
+        let directions = self.directions(offset)?;
+        let spacing = self.spacing(offset, directions)?;
+        let origin = match self.get("space origin") {
+            Some(x) => {
+                let x = vector(x)?;
+                (
+                    x[0],
+                    x.get(1).copied().unwrap_or(0.0),
+                    x.get(2).copied().unwrap_or(0.0),
+                )
+            }
+            None => (0.0, 0.0, 0.0),
+        };
+
+        let little_endian = match self.get("endian") {
+            Some("big") => false,
+            Some("little") | None => true,
+            Some(x) => return Err(VTKparseError::FileFormat(format!("Unknown endian {}", x))),
+        };
+        let n = self.values()?;
+        let data = match self.require("encoding")? {
+            "raw" => {
+                let len = scalar_type.bytes(n)?;
+                if payload.len() != len {
+                    return Err(VTKparseError::UnknownFormat(format!(
+                        "Number of elements is not correct. Expected {} but got {}",
+                        n,
+                        payload.len() / scalar_type.size()
+                    )));
+                }
+                buffer::from_bytes(payload, scalar_type, little_endian)
+            }
+            "ascii" | "text" | "txt" => buffer::read_ascii(&mut &payload[..], scalar_type, n)?,
+            x => {
+                return Err(VTKparseError::NotImplemented(format!(
+                    "NRRD encoding {}",
+                    x
+                )))
+            }
+        };
+
+        let array = DataArray {
+            name: self.get("content").unwrap_or("values").to_string(),
+            kind: DatasetAttributes::Scalars,
+            numcomp,
+            data,
+            lookup_table: Some("default".to_string()),
+        };
+        let mut points = StructuredPoints::from_array(dims, origin, spacing, array);
+        if let Some(directions) = directions {
+            points.flip(axis_flips(directions));
+        }
+        Ok(points)
+    }
+
+    /// Space directions of the three axes after `offset`, if given, with
+    /// axes without a direction along their own world axis
+    fn directions(&self, offset: usize) -> Result<Option<[[f32; 3]; 3]>, VTKparseError> {
+        let directions = match self.get("space directions") {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let mut axes = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        for (i, direction) in split_vectors(directions)
+            .iter()
+            .skip(offset)
+            .take(3)
+            .enumerate()
+        {
+            if direction != "none" {
+                let v = vector(direction)?;
+                axes[i] = [0, 1, 2].map(|j| v.get(j).copied().unwrap_or(0.0));
+            }
+        }
+        Ok(Some(axes))
+    }
+
+    /// Spacing of the three axes after `offset`, from the length of the
+    /// space directions or from `spacings`
+    fn spacing(
+        &self,
+        offset: usize,
+        directions: Option<[[f32; 3]; 3]>,
+    ) -> Result<(f32, f32, f32), VTKparseError> {
+        let mut spacing = [1.0f32; 3];
+        if let Some(directions) = directions {
+            spacing = directions.map(|v| v.iter().map(|x| x * x).sum::<f32>().sqrt());
+        } else if let Some(spacings) = self.get("spacings") {
+            for (i, x) in spacings.split_whitespace().skip(offset).take(3).enumerate() {
+                let x: f32 = x.parse()?;
+                if x.is_finite() {
+                    spacing[i] = x;
+                }
+            }
+        }
+        Ok((spacing[0], spacing[1], spacing[2]))
+    }
+}
+
+/// The names given by a `printf` format such as `slice%03d.raw` followed by
+/// the first and last number and the step, failing if there are more than
+/// `limit`
+pub(crate) fn numbered_names(words: &[&str], limit: usize) -> Result<Vec<String>, VTKparseError> {
+    let (min, max, step): (i64, i64, i64) =
+        (words[1].parse()?, words[2].parse()?, words[3].parse()?);
+    let invalid =
+        || VTKparseError::FileFormat(format!("Invalid range of file numbers {}", words.join(" ")));
+    let span = i128::from(max) - i128::from(min);
+    if step == 0 || span.signum() * i128::from(step.signum()) < 0 {
+        return Err(invalid());
+    }
+    let n = span / i128::from(step) + 1;
+    if n > limit as i128 {
+        return Err(invalid());
+    }
+    // The numbers lie between the first and last, so they fit
+    (0..n)
+        .map(|k| format_number(words[0], (i128::from(min) + k * i128::from(step)) as i64))
+        .collect()
+}
+
+/// Fills in the number of a `printf` format such as `slice%03d.raw`
+fn format_number(format: &str, number: i64) -> Result<String, VTKparseError> {
+    let invalid = || VTKparseError::FileFormat(format!("Invalid data file format {}", format));
+    let start = format.find('%').ok_or_else(invalid)?;
+    let rest = &format[start + 1..];
+    let end = rest.find('d').ok_or_else(invalid)?;
+    let spec = &rest[..end];
+    let width: usize = if spec.is_empty() {
+        0
+    } else {
+        spec.parse().map_err(|_| invalid())?
+    };
+    let number = if spec.starts_with('0') {
+        format!("{:0width$}", number, width = width)
+    } else {
+        format!("{:width$}", number, width = width)
+    };
+    Ok(format!(
+        "{}{}{}",
+        &format[..start],
+        number,
+        &rest[end + 1..]
+    ))
+}
+
+/// Splits `(1,0,0) none (0, 1, 0)` into its vectors and `none`
+fn split_vectors(text: &str) -> Vec<String> {
+    let mut vectors = Vec::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let end = if rest.starts_with('(') {
+            rest.find(')').map_or(rest.len(), |x| x + 1)
+        } else {
+            rest.find(char::is_whitespace).unwrap_or(rest.len())
+        };
+        vectors.push(rest[..end].to_string());
+        rest = rest[end..].trim_start();
+    }
+    vectors
+}
+
+/// Parses a vector written as `(x,y,z)`
+fn vector(text: &str) -> Result<Vec<f32>, VTKparseError> {
+    text.trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split(',')
+        .map(|x| Ok(x.trim().parse()?))
+        .collect()
+}
+
+/// Looks up a NRRD type, which has several names
+pub(crate) fn nrrd_type(name: &str) -> Result<ScalarType, VTKparseError> {
+    let scalar_type = match name.to_lowercase().as_str() {
+        "signed char" | "int8" | "int8_t" => ScalarType::Char,
+        "uchar" | "unsigned char" | "uint8" | "uint8_t" => ScalarType::UnsignedChar,
+        "short" | "short int" | "signed short" | "signed short int" | "int16" | "int16_t" => {
+            ScalarType::Short
+        }
+        "ushort" | "unsigned short" | "unsigned short int" | "uint16" | "uint16_t" => {
+            ScalarType::UnsignedShort
+        }
+        "int" | "signed int" | "int32" | "int32_t" => ScalarType::Int,
+        "uint" | "unsigned int" | "uint32" | "uint32_t" => ScalarType::UnsignedInt,
+        "longlong"
+        | "long long"
+        | "long long int"
+        | "signed long long"
+        | "signed long long int"
+        | "int64"
+        | "int64_t" => ScalarType::Long,
+        "ulonglong" | "unsigned long long" | "unsigned long long int" | "uint64" | "uint64_t" => {
+            ScalarType::UnsignedLong
+        }
+        "float" => ScalarType::Float,
+        "double" => ScalarType::Double,
+        _ => return Err(VTKparseError::NotImplemented(format!("NRRD type {}", name))),
+    };
+    Ok(scalar_type)
+}
+
+fn type_name(scalar_type: ScalarType) -> Result<&'static str, VTKparseError> {
+    Ok(match scalar_type {
+        ScalarType::Bit => {
+            return Err(VTKparseError::NotImplemented(
+                "Bit arrays in NRRD files".to_string(),
+            ))
+        }
+        ScalarType::UnsignedChar => "uint8",
+        ScalarType::Char => "int8",
+        ScalarType::UnsignedShort => "uint16",
+        ScalarType::Short => "int16",
+        ScalarType::UnsignedInt => "uint32",
+        ScalarType::Int => "int32",
+        ScalarType::UnsignedLong => "uint64",
+        ScalarType::Long => "int64",
+        ScalarType::Float => "float",
+        ScalarType::Double => "double",
+    })
+}
+
+/// Writes the first point array as a NRRD file with raw little-endian data,
+/// with the components along the first axis
+pub fn write_nrrd<P: AsRef<Path>>(file: P, points: &StructuredPoints) -> Result<(), VTKparseError> {
+    write_nrrd_to(BufWriter::new(File::create(file)?), points)
+}
+
+pub fn write_nrrd_to<W: Write>(
+    mut writer: W,
+    points: &StructuredPoints,
+) -> Result<(), VTKparseError> {
+    let array = points.single_array()?;
+    let (x, y, z) = points.dims;
+    let (sx, sy, sz) = points.spacing;
+    let (ox, oy, oz) = points.origin;
+    let vector = array.numcomp > 1;
+
+    writeln!(writer, "NRRD0004")?;
+    writeln!(writer, "type: {}", type_name(array.data.scalar_type())?)?;
+    writeln!(writer, "dimension: {}", if vector { 4 } else { 3 })?;
+    writeln!(writer, "space dimension: 3")?;
+    if vector {
+        writeln!(writer, "sizes: {} {} {} {}", array.numcomp, x, y, z)?;
+        write!(writer, "space directions: none ")?;
+    } else {
+        writeln!(writer, "sizes: {} {} {}", x, y, z)?;
+        write!(writer, "space directions: ")?;
+    }
+    writeln!(writer, "({},0,0) (0,{},0) (0,0,{})", sx, sy, sz)?;
+    if vector {
+        writeln!(writer, "kinds: vector domain domain domain")?;
+    } else {
+        writeln!(writer, "kinds: domain domain domain")?;
+    }
+    writeln!(writer, "endian: little")?;
+    writeln!(writer, "encoding: raw")?;
+    writeln!(writer, "space origin: ({},{},{})", ox, oy, oz)?;
+    writeln!(writer)?;
+    buffer::write_bytes(&mut writer, &array.data, true)?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+
+    #[test]
+    fn write_and_read() {
+        let array = DataArray {
+            name: "values".to_string(),
+            kind: DatasetAttributes::Scalars,
+            numcomp: 3,
+            data: Buffer::U16((0..72).collect()),
+            lookup_table: None,
+        };
+        let points =
+            StructuredPoints::from_array((4, 3, 2), (1.0, -2.0, 0.5), (0.5, 0.25, 2.0), array);
+
+        let mut bytes = Vec::new();
+        write_nrrd_to(&mut bytes, &points).unwrap();
+        let read = read_nrrd_from(&bytes[..]).unwrap();
+        assert_eq!(read.dims, points.dims);
+        assert_eq!(read.origin, points.origin);
+        assert_eq!(read.spacing, points.spacing);
+        assert_eq!(read.attributes.point[0].numcomp, 3);
+        assert_eq!(
+            read.attributes.point[0].data,
+            points.attributes.point[0].data
+        );
+
+        // Without an axis of components the fourth axis is not known
+        let at = bytes.windows(6).position(|x| x == b"vector").unwrap();
+        bytes[at..at + 6].copy_from_slice(b"domain");
+        assert!(matches!(
+            read_nrrd_from(&bytes[..]),
+            Err(VTKparseError::NotImplemented(_))
+        ));
+    }
+
+    #[test]
+    fn ascii_and_spacings() {
+        let text = "NRRD0001
+# a comment
+content: density
+type: signed short int
+dimension: 2
+sizes: 3 2
+spacings: 0.5 2
+encoding: ascii
+key:=value
+
+1 -2 3
+4 5 -6
+";
+        let read = read_nrrd_from(text.as_bytes()).unwrap();
+        assert_eq!(read.dims, (3, 2, 1));
+        assert_eq!(read.spacing, (0.5, 2.0, 1.0));
+        let array = read.scalars().unwrap();
+        assert_eq!(array.name, "density");
+        assert_eq!(array.data, Buffer::I16(vec![1, -2, 3, 4, 5, -6]));
+
+        let err = read_nrrd_from(text.replace("type: signed short int\n", "").as_bytes());
+        assert_eq!(err.err().unwrap().expected(), Some("type"));
+    }
+
+    #[test]
+    fn file_numbers() {
+        let names = numbered_names(&["s%02d.raw", "3", "1", "-2"], 2).unwrap();
+        assert_eq!(names, vec!["s03.raw", "s01.raw"]);
+        assert!(numbered_names(&["s%d", "0", "9223372036854775807", "1"], 100).is_err());
+        let extremes = [
+            "s%d",
+            "-9223372036854775808",
+            "9223372036854775807",
+            "4611686018427387904",
+        ];
+        assert_eq!(numbered_names(&extremes, 4).unwrap().len(), 4);
+        assert!(numbered_names(&["s%d", "0", "1", "-1"], 100).is_err());
+    }
+
+    #[test]
+    fn detached() {
+        let directory =
+            std::env::temp_dir().join(format!("vtk_parser_nrrd_detached_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        // Two slices of big-endian shorts, behind a header and a text line
+        for (i, values) in [[1i16, -2, 3], [4, 5, -6]].iter().enumerate() {
+            let mut bytes = b"comment\n\0\0".to_vec();
+            for x in values {
+                bytes.extend_from_slice(&x.to_be_bytes());
+            }
+            std::fs::write(directory.join(format!("slice{:02}.raw", i + 1)), bytes).unwrap();
+        }
+        let header = "NRRD0005
+type: int16
+dimension: 3
+sizes: 3 1 2
+space directions: (-0.5,0,0) (0,2,0) (0,0,1.5)
+space origin: (10,20,30)
+endian: big
+encoding: raw
+lineskip: 1
+byte skip: 2
+";
+        let fields = [
+            "datafile: slice%02d.raw 1 2 1",
+            "data file: LIST\nslice01.raw\nslice02.raw",
+            "data file: slice%02d.raw 2 1 -1",
+        ];
+        for field in fields.iter() {
+            let path = directory.join("volume.nhdr");
+            std::fs::write(&path, format!("{}{}\n", header, field)).unwrap();
+            let files = nrrd_data_files(&path).unwrap();
+            assert_eq!(files.len(), 2);
+            assert!(files[0].ends_with(if field.contains("-1") {
+                "slice02.raw"
+            } else {
+                "slice01.raw"
+            }));
+
+            let read = read_nrrd(&path).unwrap();
+            assert_eq!(read.dims, (3, 1, 2));
+            assert_eq!(read.spacing, (0.5, 2.0, 1.5));
+            // The x axis runs backwards, so it is flipped to start at 9
+            assert_eq!(read.origin, (9.0, 20.0, 30.0));
+            let data = &read.scalars().unwrap().data;
+            if field.contains("-1") {
+                assert_eq!(*data, Buffer::I16(vec![-6, 5, 4, 3, -2, 1]));
+            } else {
+                assert_eq!(*data, Buffer::I16(vec![3, -2, 1, -6, 5, 4]));
+            }
+        }
+
+        // The data at the end of the file, whatever comes before it
+        let path = directory.join("end.nhdr");
+        let header = header.replace("lineskip: 1\nbyte skip: 2", "byte skip: -1");
+        std::fs::write(
+            &path,
+            format!(
+                "{}data file: slice01.raw\n",
+                header.replace("3 1 2", "3 1 1")
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            read_nrrd(&path).unwrap().scalars().unwrap().data,
+            Buffer::I16(vec![3, -2, 1])
+        );
+        assert!(read_nrrd_from(std::fs::File::open(&path).map(BufReader::new).unwrap()).is_err());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
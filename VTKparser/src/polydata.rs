@@ -0,0 +1,220 @@
+use std::io::prelude::*;
+
+use crate::attributes::{self, Attributes};
+use crate::geometry::{self, read_cells, read_points, Cells};
+use crate::{next_line, VTKparseError};
+
+/// Points connected by vertices, lines, polygons and triangle strips
+pub struct PolyData {
+    pub points: Vec<[f64; 3]>,
+    pub vertices: Cells,
+    pub lines: Cells,
+    pub polygons: Cells,
+    pub triangle_strips: Cells,
+    /// Cell data is given for the vertices, lines, polygons and triangle
+    /// strips, in that order
+    pub attributes: Attributes,
+}
+
+pub fn parse<R: BufRead>(reader: &mut R, binary: bool) -> Result<PolyData, VTKparseError> {
+    let points = read_points(reader, binary, None)?;
+
+    let mut vertices = Cells::default();
+    let mut lines = Cells::default();
+    let mut polygons = Cells::default();
+    let mut triangle_strips = Cells::default();
+
+    let mut attributes = Attributes::default();
+
+    while let Some(line) = next_line(reader)? {
+        let keyword = line.split_whitespace().next().unwrap().to_uppercase();
+        let cells = match keyword.as_str() {
+            "VERTICES" => &mut vertices,
+            "LINES" => &mut lines,
+            "POLYGONS" => &mut polygons,
+            "TRIANGLE_STRIPS" => &mut triangle_strips,
+            "POINT_DATA" | "CELL_DATA" => {
+                let mut rest = line.as_bytes().chain(&mut *reader);
+                attributes = attributes::parse(&mut rest, binary)?;
+                break;
+            }
+            _ => {
+                return Err(VTKparseError::FileFormat(format!(
+                    "Unknown polydata section {}",
+                    keyword
+                )))
+            }
+        };
+        *cells = read_cells(reader, binary, &line)?;
+    }
+
+    let data = PolyData {
+        points,
+        vertices,
+        lines,
+        polygons,
+        triangle_strips,
+        attributes,
+    };
+
+    if let Some(&id) = data
+        .cells()
+        .flat_map(|c| c.connectivity.iter())
+        .find(|&&id| id >= data.points.len())
+    {
+        return Err(VTKparseError::FileFormat(format!(
+            "Point index {} is out of range, there are {} points",
+            id,
+            data.points.len()
+        )));
+    }
+    if let Some(array) = data
+        .attributes
+        .point
+        .iter()
+        .find(|x| x.len() != data.points.len())
+    {
+        return Err(VTKparseError::FileFormat(format!(
+            "Array {} has {} points, but there are {}",
+            array.name,
+            array.len(),
+            data.points.len()
+        )));
+    }
+    let num_cells: usize = data.cells().map(Cells::len).sum();
+    if let Some(array) = data.attributes.cell.iter().find(|x| x.len() != num_cells) {
+        return Err(VTKparseError::FileFormat(format!(
+            "Array {} has {} cells, but there are {}",
+            array.name,
+            array.len(),
+            num_cells
+        )));
+    }
+
+    Ok(data)
+}
+
+impl PolyData {
+    /// Smallest and largest coordinate along each axis
+    pub fn bounds(&self) -> [(f64, f64); 3] {
+        geometry::bounds(&self.points)
+    }
+
+    /// The cells in the order used for cell data
+    pub fn cells(&self) -> impl Iterator<Item = &Cells> {
+        vec![
+            &self.vertices,
+            &self.lines,
+            &self.polygons,
+            &self.triangle_strips,
+        ]
+        .into_iter()
+    }
+
+    /// Splits the polygons and triangle strips into triangles
+    ///
+    /// Polygons are assumed to be convex and split as a fan
+    pub fn triangles(&self) -> Vec<[usize; 3]> {
+        let mut triangles = Vec::new();
+        for polygon in self.polygons.iter() {
+            for i in 2..polygon.len() {
+                triangles.push([polygon[0], polygon[i - 1], polygon[i]]);
+            }
+        }
+        for strip in self.triangle_strips.iter() {
+            for i in 2..strip.len() {
+                // Every other triangle has its orientation flipped
+                if i % 2 == 0 {
+                    triangles.push([strip[i - 2], strip[i - 1], strip[i]]);
+                } else {
+                    triangles.push([strip[i - 1], strip[i - 2], strip[i]]);
+                }
+            }
+        }
+        triangles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+    use std::io::BufReader;
+
+    const TEXT: &str = "POINTS 5 float
+0 0 0 1 0 0 1 1 0 0 1 0
+0 0 1
+VERTICES 1 2
+1 4
+LINES 1 3
+2 0 4
+POLYGONS 1 5
+4 0 1 2 3
+
+TRIANGLE_STRIPS 1 5
+4 0 1 3 2
+POINT_DATA 5
+SCALARS d float
+LOOKUP_TABLE default
+0 1 2 3 4
+CELL_DATA 4
+SCALARS id int
+LOOKUP_TABLE default
+0 1 2 3
+";
+
+    #[test]
+    fn parse_polydata() {
+        let data = parse(&mut TEXT.as_bytes(), false).unwrap();
+        assert_eq!(data.points.len(), 5);
+        assert_eq!(data.vertices.get(0), Some(&[4][..]));
+        assert_eq!(data.lines.get(0), Some(&[0, 4][..]));
+        assert_eq!(data.polygons.get(0), Some(&[0, 1, 2, 3][..]));
+        assert_eq!(
+            data.triangles(),
+            vec![[0, 1, 2], [0, 2, 3], [0, 1, 3], [3, 1, 2]]
+        );
+        assert_eq!(data.attributes.cell[0].data, Buffer::I32(vec![0, 1, 2, 3]));
+    }
+
+    #[test]
+    fn new_cell_layout() {
+        let text = "POINTS 3 float
+0 0 0 1 0 0 0 1 0
+POLYGONS 2 3
+OFFSETS vtktypeint64
+0 3
+CONNECTIVITY vtktypeint64
+0 1 2
+";
+        // A tiny buffer cannot hold the keyword to look ahead
+        let small = parse(&mut BufReader::with_capacity(1, text.as_bytes()), false).unwrap();
+        for data in &[parse(&mut text.as_bytes(), false).unwrap(), small] {
+            assert_eq!(data.polygons.len(), 1);
+            assert_eq!(data.triangles(), vec![[0, 1, 2]]);
+        }
+    }
+
+    #[test]
+    fn binary_cells() {
+        let mut bytes = b"POINTS 3 float\n".to_vec();
+        for x in &[0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            bytes.extend_from_slice(&x.to_be_bytes());
+        }
+        bytes.extend_from_slice(b"\nPOLYGONS 1 4\n");
+        for x in &[3i32, 2, 1, 0] {
+            bytes.extend_from_slice(&x.to_be_bytes());
+        }
+        bytes.extend_from_slice(b"\n");
+
+        let data = parse(&mut &bytes[..], true).unwrap();
+        assert_eq!(data.points[1], [1.0, 0.0, 0.0]);
+        assert_eq!(data.polygons.get(0), Some(&[2, 1, 0][..]));
+    }
+
+    #[test]
+    fn index_out_of_range() {
+        let text = "POINTS 1 float\n0 0 0\nLINES 1 3\n2 0 1\n";
+        assert!(parse(&mut text.as_bytes(), false).is_err());
+    }
+}
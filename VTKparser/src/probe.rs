@@ -0,0 +1,328 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::buffer::{self, ScalarType};
+use crate::tracking::Tracking;
+use crate::{field, keyword, next_line, read_header, spoints};
+use crate::{DatasetAttributes, Header, VTKparseError};
+
+/// Metadata of a structured points file, read without the values
+pub struct Probe {
+    pub header: Header,
+    pub dims: (u32, u32, u32),
+    pub origin: (f32, f32, f32),
+    pub spacing: (f32, f32, f32),
+    /// The arrays in the order of the file
+    ///
+    /// Binary files are searched by seeking past the values of each array.
+    /// In ASCII files only the first array is found, as the values would
+    /// have to be read to find the end of it
+    pub arrays: Vec<ArrayInfo>,
+}
+
+/// Header of an attribute array, and where its values start
+pub struct ArrayInfo {
+    pub name: String,
+    pub kind: DatasetAttributes,
+    /// Whether the array belongs to the points, rather than the cells
+    pub point: bool,
+    pub scalar_type: ScalarType,
+    pub numcomp: usize,
+    /// Number of tuples
+    pub len: usize,
+    /// Byte offset of the first value from the start of the file
+    pub offset: u64,
+    /// Name of the lookup table for `SCALARS`
+    pub lookup_table: Option<String>,
+}
+
+impl Probe {
+    /// The first scalar array attached to the points
+    pub fn scalars(&self) -> Option<&ArrayInfo> {
+        self.point_scalars().next()
+    }
+
+    /// All `SCALARS` arrays attached to the points which were found
+    pub fn point_scalars(&self) -> impl Iterator<Item = &ArrayInfo> {
+        self.arrays
+            .iter()
+            .filter(|x| x.point && x.kind == DatasetAttributes::Scalars)
+    }
+}
+
+/// Reads the header, geometry and array headers of a structured points file
+pub fn probe_file<P: AsRef<Path>>(file: P) -> Result<Probe, VTKparseError> {
+    let file = File::open(file)?;
+    probe_from(BufReader::new(file))
+}
+
+/// Probes a file from any buffered and seekable source, which should be at
+/// the start of the file
+pub fn probe_from<R: BufRead + Seek>(mut reader: R) -> Result<Probe, VTKparseError> {
+    let position = reader.stream_position()?;
+    let mut reader = Tracking::at(reader, position);
+    probe(&mut reader).map_err(|err| err.at(reader.location()))
+}
+
+fn probe<R: BufRead + Seek>(reader: &mut Tracking<R>) -> Result<Probe, VTKparseError> {
+    let header = read_header(reader)?;
+    let binary = header.binary;
+
+    let dataset = next_line(reader)?.unwrap_or_default();
+    if keyword(&dataset) != "DATASET" {
+        return Err(VTKparseError::Expected("DATASET".to_string()));
+    }
+    let kind = dataset
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("")
+        .to_uppercase();
+    if kind != "STRUCTURED_POINTS" {
+        return Err(VTKparseError::NotImplemented(format!("Probing {}", kind)));
+    }
+    let mut line = next_line(reader)?.unwrap_or_default();
+    if keyword(&line) == "FIELD" {
+        field::parse(reader, binary, &line)?;
+        line = next_line(reader)?.unwrap_or_default();
+    }
+
+    // The line read after the field starts the geometry
+    let (dims, origin, spacing) = spoints::read_geometry(&mut line.as_bytes().chain(&mut *reader))?;
+
+    let mut arrays = Vec::new();
+    let mut section: Option<(bool, usize)> = None;
+    while let Some(line) = next_line(reader)? {
+        let mut words = line.split_whitespace();
+        let keyword = words.next().unwrap().to_uppercase();
+        let mut word = |what: &str| {
+            words
+                .next()
+                .ok_or_else(|| VTKparseError::FileFormat(format!("{} has no {}", keyword, what)))
+        };
+
+        // The number of values to skip, and the array if this is one
+        let (values, scalar_type, array) = match keyword.as_str() {
+            "POINT_DATA" | "CELL_DATA" => {
+                section = Some((keyword == "POINT_DATA", word("length")?.parse()?));
+                continue;
+            }
+            "LOOKUP_TABLE" => {
+                word("name")?;
+                let size: usize = word("size")?.parse()?;
+                (buffer::count(&[size, 4])?, ScalarType::UnsignedChar, None)
+            }
+            "SCALARS"
+            | "COLOR_SCALARS"
+            | "VECTORS"
+            | "NORMALS"
+            | "TENSORS"
+            | "TEXTURE_COORDINATES" => {
+                let name = word("name")?.to_string();
+                let (kind, numcomp, scalar_type) = match keyword.as_str() {
+                    "SCALARS" => {
+                        let datatype = word("datatype")?;
+                        let numcomp = word("number of components").unwrap_or("1").parse()?;
+                        (DatasetAttributes::Scalars, numcomp, datatype)
+                    }
+                    "COLOR_SCALARS" => {
+                        let numcomp = word("number of values")?.parse()?;
+                        let datatype = if binary { "unsigned_char" } else { "float" };
+                        (DatasetAttributes::ColorScalars, numcomp, datatype)
+                    }
+                    "VECTORS" => (DatasetAttributes::Vectors, 3, word("datatype")?),
+                    "NORMALS" => (DatasetAttributes::Normals, 3, word("datatype")?),
+                    "TENSORS" => (DatasetAttributes::Tensors, 9, word("datatype")?),
+                    _ => {
+                        let numcomp = word("dimension")?.parse()?;
+                        (
+                            DatasetAttributes::TextureCoordinates,
+                            numcomp,
+                            word("datatype")?,
+                        )
+                    }
+                };
+                let scalar_type = ScalarType::from_name(scalar_type).ok_or_else(|| {
+                    VTKparseError::NotImplemented(format!("Datatype {}", scalar_type))
+                })?;
+
+                let lookup_table = if kind == DatasetAttributes::Scalars {
+                    let line = next_line(reader)?.unwrap_or_default();
+                    if crate::keyword(&line) != "LOOKUP_TABLE" {
+                        return Err(VTKparseError::Expected("LOOKUP_TABLE".to_string()));
+                    }
+                    Some(
+                        line.split_whitespace()
+                            .nth(1)
+                            .unwrap_or("default")
+                            .to_string(),
+                    )
+                } else {
+                    None
+                };
+
+                let (point, len) = section.ok_or_else(|| {
+                    VTKparseError::FileFormat(format!(
+                        "{} is not preceded by POINT_DATA or CELL_DATA",
+                        keyword
+                    ))
+                })?;
+                let array = ArrayInfo {
+                    name,
+                    kind,
+                    point,
+                    scalar_type,
+                    numcomp,
+                    len,
+                    offset: reader.position(),
+                    lookup_table,
+                };
+                (buffer::count(&[len, numcomp])?, scalar_type, Some(array))
+            }
+            "FIELD" => {
+                let (point, _) = section.ok_or_else(|| {
+                    VTKparseError::FileFormat(
+                        "FIELD is not preceded by POINT_DATA or CELL_DATA".to_string(),
+                    )
+                })?;
+                word("name")?;
+                let n: usize = word("number of arrays")?.parse()?;
+                probe_field(reader, binary, n, point, &mut arrays)?;
+                if !binary {
+                    break;
+                }
+                continue;
+            }
+            _ => break,
+        };
+
+        if let Some(array) = array {
+            arrays.push(array);
+        }
+        if !binary {
+            break;
+        }
+        reader.skip(scalar_type.bytes(values)? as u64)?;
+    }
+
+    Ok(Probe {
+        header,
+        dims,
+        origin,
+        spacing,
+        arrays,
+    })
+}
+
+/// Describes the `n` arrays of a field in a section of point or cell data,
+/// or only the first of them in ASCII files
+fn probe_field<R: BufRead + Seek>(
+    reader: &mut Tracking<R>,
+    binary: bool,
+    n: usize,
+    point: bool,
+    arrays: &mut Vec<ArrayInfo>,
+) -> Result<(), VTKparseError> {
+    for _ in 0..n {
+        let line = next_line(reader)?.ok_or_else(|| {
+            VTKparseError::FileFormat("FIELD has fewer arrays than given".to_string())
+        })?;
+        let (name, numcomp, len, scalar_type) = match field::array_header(&line)? {
+            Some(header) => header,
+            None => continue,
+        };
+        arrays.push(ArrayInfo {
+            name,
+            kind: DatasetAttributes::FieldData,
+            point,
+            scalar_type,
+            numcomp,
+            len,
+            offset: reader.position(),
+            lookup_table: None,
+        });
+        if !binary {
+            break;
+        }
+        reader.skip(scalar_type.bytes(buffer::count(&[len, numcomp])?)? as u64)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn tooth() {
+        let probe = probe_file("../data/tooth.vtk").unwrap();
+        let data = crate::read_file("../data/tooth.vtk").unwrap();
+        let data = data.structured_points().unwrap();
+        assert_eq!(probe.dims, data.dims);
+        assert_eq!(probe.spacing, data.spacing);
+
+        let scalars = probe.scalars().unwrap();
+        assert_eq!(scalars.name, data.scalars().unwrap().name);
+        assert_eq!(scalars.len, data.scalars().unwrap().len());
+    }
+
+    #[test]
+    fn binary_offsets() {
+        let mut bytes = b"# vtk DataFile Version 3.0
+probe
+BINARY
+DATASET STRUCTURED_POINTS
+DIMENSIONS 2 1 1
+ORIGIN 0 0 0
+SPACING 1 1 1
+POINT_DATA 2
+SCALARS a short
+LOOKUP_TABLE default
+"
+        .to_vec();
+        let a = bytes.len() as u64;
+        bytes.extend_from_slice(&[0, 1, 0, 2]);
+        bytes.extend_from_slice(b"\nLOOKUP_TABLE t 1\n");
+        bytes.extend_from_slice(&[0, 0, 0, 255]);
+        bytes.extend_from_slice(b"\nVECTORS v double\n");
+        let v = bytes.len() as u64;
+        bytes.extend_from_slice(&[0; 48]);
+
+        let probe = probe_from(Cursor::new(&bytes)).unwrap();
+        assert_eq!(probe.arrays.len(), 2);
+        assert_eq!(probe.arrays[0].offset, a);
+        assert_eq!(probe.arrays[0].scalar_type, ScalarType::Short);
+        assert_eq!(probe.arrays[1].offset, v);
+        assert_eq!(probe.arrays[1].kind, DatasetAttributes::Vectors);
+    }
+
+    #[test]
+    fn ascii_stops_at_first_array() {
+        let text = "# vtk DataFile Version 3.0
+probe
+ASCII
+DATASET STRUCTURED_POINTS
+DIMENSIONS 2 1 1
+ORIGIN 0 0 0
+SPACING 1 1 1
+POINT_DATA 2
+SCALARS a float
+LOOKUP_TABLE default
+1 2
+SCALARS b float
+LOOKUP_TABLE default
+3 4
+";
+        let probe = probe_from(Cursor::new(text)).unwrap();
+        assert_eq!(probe.arrays.len(), 1);
+        assert_eq!(probe.arrays[0].offset as usize, text.find("1 2").unwrap());
+
+        // A field before the geometry, read through a tiny buffer
+        let text = text.replace("DIMENSIONS", "FIELD fd 1\nTIME 1 1 double\n2.5\nDIMENSIONS");
+        let probe = probe_from(BufReader::with_capacity(1, Cursor::new(&text))).unwrap();
+        assert_eq!(probe.dims, (2, 1, 1));
+        assert_eq!(probe.arrays[0].offset as usize, text.find("1 2").unwrap());
+    }
+}
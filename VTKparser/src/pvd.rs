@@ -0,0 +1,87 @@
+use std::path::{Path, PathBuf};
+
+use crate::vti::tags;
+use crate::VTKparseError;
+
+/// A dataset of a ParaView data collection
+#[derive(Clone, Debug, PartialEq)]
+pub struct CollectionEntry {
+    pub timestep: f64,
+    /// Index of the piece when a timestep is split over several files
+    pub part: u32,
+    pub file: PathBuf,
+}
+
+/// Reads the datasets listed by a ParaView `.pvd` file, sorted by timestep
+///
+/// Relative file names are resolved against the directory of the `.pvd`
+pub fn read_collection<P: AsRef<Path>>(file: P) -> Result<Vec<CollectionEntry>, VTKparseError> {
+    let file = file.as_ref();
+    let xml = std::fs::read(file)?;
+    let xml = String::from_utf8(xml)
+        .map_err(|_| VTKparseError::FileFormat("XML is not valid UTF-8".to_string()))?;
+    parse(&xml, file.parent().unwrap_or_else(|| Path::new("")))
+}
+
+fn parse(xml: &str, directory: &Path) -> Result<Vec<CollectionEntry>, VTKparseError> {
+    let tags = tags(xml);
+    let kind = tags
+        .iter()
+        .find(|t| t.name == "VTKFile")
+        .and_then(|t| t.attribute("type"));
+    if kind != Some("Collection") {
+        return Err(VTKparseError::UnknownFormat(
+            "Not a VTK collection".to_string(),
+        ));
+    }
+
+    let mut entries = tags
+        .iter()
+        .filter(|t| t.name == "DataSet")
+        .map(|t| {
+            let file = t
+                .attribute("file")
+                .ok_or_else(|| VTKparseError::FileFormat("DataSet has no file".to_string()))?;
+            Ok(CollectionEntry {
+                timestep: t.attribute("timestep").unwrap_or("0").trim().parse()?,
+                part: t.attribute("part").unwrap_or("0").trim().parse()?,
+                file: directory.join(file),
+            })
+        })
+        .collect::<Result<Vec<_>, VTKparseError>>()?;
+    entries.sort_by(|a, b| a.timestep.total_cmp(&b.timestep).then(a.part.cmp(&b.part)));
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collection() {
+        let xml = r#"<?xml version="1.0"?>
+<VTKFile type="Collection" version="0.1" byte_order="LittleEndian">
+  <Collection>
+    <DataSet timestep="0.5" group="" part="0" file="flow/step_1.vti"/>
+    <!-- <DataSet timestep="9" file="skipped.vti"/> -->
+    <DataSet timestep="0" group="" part="0" file="flow/step_0.vti"/>
+    <DataSet timestep="0.5" part="1" file="/abs/step_1b.vti"/>
+  </Collection>
+</VTKFile>
+"#;
+        let entries = parse(xml, Path::new("data")).unwrap();
+        let files: Vec<_> = entries.iter().map(|x| x.file.clone()).collect();
+        assert_eq!(
+            files,
+            [
+                PathBuf::from("data/flow/step_0.vti"),
+                PathBuf::from("data/flow/step_1.vti"),
+                PathBuf::from("/abs/step_1b.vti"),
+            ]
+        );
+        assert_eq!(entries[1].timestep, 0.5);
+        assert_eq!(entries[2].part, 1);
+
+        assert!(parse("<VTKFile type=\"ImageData\">", Path::new("")).is_err());
+    }
+}
@@ -0,0 +1,310 @@
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+use crate::attributes::DataArray;
+use crate::buffer::{self, Buffer, ScalarType};
+use crate::{DatasetAttributes, StructuredPoints, VTKparseError};
+
+/// Layout of a volume in a file without a header, which has to be given
+/// by the user or by a sidecar file
+///
+/// The sidecar is a text file of `key = value` lines, with `#` comments:
+///
+/// ```text
+/// dims = 103 94 161
+/// type = unsigned_short
+/// components = 1
+/// endian = little
+/// skip = 0
+/// spacing = 1 1 1
+/// origin = 0 0 0
+/// ```
+///
+/// Only `dims` and `type` are required, the values above are the defaults
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawLayout {
+    pub dims: (u32, u32, u32),
+    pub scalar_type: ScalarType,
+    pub numcomp: usize,
+    pub little_endian: bool,
+    /// Number of bytes to skip at the start of the file
+    pub skip: u64,
+    pub origin: (f32, f32, f32),
+    pub spacing: (f32, f32, f32),
+}
+
+impl RawLayout {
+    pub fn new(dims: (u32, u32, u32), scalar_type: ScalarType) -> Self {
+        Self {
+            dims,
+            scalar_type,
+            numcomp: 1,
+            little_endian: true,
+            skip: 0,
+            origin: (0.0, 0.0, 0.0),
+            spacing: (1.0, 1.0, 1.0),
+        }
+    }
+
+    /// Where the sidecar of a raw file is looked for, `volume.raw.layout`
+    /// for `volume.raw`
+    pub fn sidecar(file: &Path) -> PathBuf {
+        let mut name = file.as_os_str().to_owned();
+        name.push(".layout");
+        PathBuf::from(name)
+    }
+
+    pub fn read_sidecar<P: AsRef<Path>>(file: P) -> Result<Self, VTKparseError> {
+        Self::parse(&std::fs::read_to_string(file)?)
+    }
+
+    /// Parses the text of a sidecar file
+    pub fn parse(text: &str) -> Result<Self, VTKparseError> {
+        let mut dims = None;
+        let mut scalar_type = None;
+        let mut layout = Self::new((0, 0, 0), ScalarType::UnsignedChar);
+
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = match line.find('=') {
+                Some(i) => (line[..i].trim().to_lowercase(), line[i + 1..].trim()),
+                None => {
+                    return Err(VTKparseError::FileFormat(format!(
+                        "Expected key = value, got {}",
+                        line
+                    )))
+                }
+            };
+            match key.as_str() {
+                "dims" | "dimensions" => dims = Some(triple(value, &key)?),
+                "type" => {
+                    scalar_type = Some(ScalarType::from_name(value).ok_or_else(|| {
+                        VTKparseError::NotImplemented(format!("Datatype {}", value))
+                    })?)
+                }
+                "components" => layout.numcomp = value.parse()?,
+                "endian" => {
+                    layout.little_endian = match value.to_lowercase().as_str() {
+                        "little" => true,
+                        "big" => false,
+                        _ => {
+                            return Err(VTKparseError::FileFormat(format!(
+                                "Unknown endian {}",
+                                value
+                            )))
+                        }
+                    }
+                }
+                "skip" => layout.skip = value.parse()?,
+                "spacing" => layout.spacing = triple(value, &key)?,
+                "origin" => layout.origin = triple(value, &key)?,
+                _ => {
+                    return Err(VTKparseError::FileFormat(format!(
+                        "Unknown layout key {}",
+                        key
+                    )))
+                }
+            }
+        }
+
+        layout.dims = dims.ok_or_else(|| VTKparseError::Expected("dims".to_string()))?;
+        layout.scalar_type =
+            scalar_type.ok_or_else(|| VTKparseError::Expected("type".to_string()))?;
+        Ok(layout)
+    }
+
+    /// Number of bytes of the values
+    pub fn data_len(&self) -> Result<usize, VTKparseError> {
+        let (x, y, z) = self.dims;
+        self.scalar_type.bytes(buffer::count(&[
+            x as usize,
+            y as usize,
+            z as usize,
+            self.numcomp,
+        ])?)
+    }
+
+    /// Fails unless the skipped header and the values fit in a file of
+    /// `file_len` bytes
+    pub fn check_len(&self, file_len: u64) -> Result<(), VTKparseError> {
+        let needed = self.skip.checked_add(self.data_len()? as u64);
+        match needed {
+            Some(needed) if needed <= file_len => Ok(()),
+            Some(needed) => Err(VTKparseError::FileFormat(format!(
+                "File holds {} bytes, the layout needs {}",
+                file_len, needed
+            ))),
+            None => Err(VTKparseError::FileFormat(
+                "Layout needs more bytes than a file can hold".to_string(),
+            )),
+        }
+    }
+}
+
+/// The layout in the format of a sidecar file
+impl fmt::Display for RawLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (x, y, z) = self.dims;
+        writeln!(f, "dims = {} {} {}", x, y, z)?;
+        writeln!(f, "type = {}", self.scalar_type.name())?;
+        writeln!(f, "components = {}", self.numcomp)?;
+        let endian = if self.little_endian { "little" } else { "big" };
+        writeln!(f, "endian = {}", endian)?;
+        writeln!(f, "skip = {}", self.skip)?;
+        let (x, y, z) = self.spacing;
+        writeln!(f, "spacing = {} {} {}", x, y, z)?;
+        let (x, y, z) = self.origin;
+        writeln!(f, "origin = {} {} {}", x, y, z)
+    }
+}
+
+fn triple<T: std::str::FromStr>(value: &str, key: &str) -> Result<(T, T, T), VTKparseError>
+where
+    VTKparseError: From<T::Err>,
+{
+    let values: Vec<&str> = value.split_whitespace().collect();
+    if values.len() != 3 {
+        return Err(VTKparseError::FileFormat(format!(
+            "{} needs three values",
+            key
+        )));
+    }
+    Ok((values[0].parse()?, values[1].parse()?, values[2].parse()?))
+}
+
+/// Reads a raw volume laid out as described
+pub fn read_raw<P: AsRef<Path>>(
+    file: P,
+    layout: &RawLayout,
+) -> Result<StructuredPoints, VTKparseError> {
+    let file = File::open(file)?;
+    layout.check_len(file.metadata()?.len())?;
+    read_raw_from(file, layout)
+}
+
+pub fn read_raw_from<R: Read>(
+    mut reader: R,
+    layout: &RawLayout,
+) -> Result<StructuredPoints, VTKparseError> {
+    io::copy(&mut (&mut reader).take(layout.skip), &mut io::sink())?;
+
+    let len = layout.data_len()?;
+    let mut bytes = buffer::reserve(len);
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(VTKparseError::FileFormat(format!(
+            "Raw file holds {} bytes after the skipped header, the layout needs {}",
+            bytes.len(),
+            len
+        )));
+    }
+
+    let mut array = DataArray {
+        name: "values".to_string(),
+        kind: DatasetAttributes::Scalars,
+        numcomp: layout.numcomp,
+        data: buffer::from_bytes(bytes, layout.scalar_type, layout.little_endian),
+        lookup_table: Some("default".to_string()),
+    };
+    // The last byte of bits may be padded
+    if let Buffer::Bit(ref mut x) = array.data {
+        let (nx, ny, nz) = layout.dims;
+        x.truncate(nx as usize * ny as usize * nz as usize * layout.numcomp);
+    }
+    Ok(StructuredPoints::from_array(
+        layout.dims,
+        layout.origin,
+        layout.spacing,
+        array,
+    ))
+}
+
+/// Writes the first point array little-endian, and its layout to the
+/// sidecar next to the file
+pub fn write_raw<P: AsRef<Path>>(file: P, points: &StructuredPoints) -> Result<(), VTKparseError> {
+    let file = file.as_ref();
+    let layout = write_raw_to(BufWriter::new(File::create(file)?), points)?;
+    std::fs::write(RawLayout::sidecar(file), layout.to_string())?;
+    Ok(())
+}
+
+/// Writes the values of the first point array little-endian, returning
+/// their layout
+pub fn write_raw_to<W: Write>(
+    mut writer: W,
+    points: &StructuredPoints,
+) -> Result<RawLayout, VTKparseError> {
+    let array = points.single_array()?;
+    buffer::write_bytes(&mut writer, &array.data, true)?;
+    writer.flush()?;
+
+    Ok(RawLayout {
+        numcomp: array.numcomp,
+        origin: points.origin,
+        spacing: points.spacing,
+        ..RawLayout::new(points.dims, array.data.scalar_type())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sidecar() {
+        let layout = RawLayout::parse(
+            "# scanner dump
+dims = 2 3 4
+type = short  # signed
+endian = big
+skip = 16
+",
+        )
+        .unwrap();
+        assert_eq!(layout.dims, (2, 3, 4));
+        assert_eq!(layout.scalar_type, ScalarType::Short);
+        assert!(!layout.little_endian);
+        assert_eq!(layout.spacing, (1.0, 1.0, 1.0));
+        assert_eq!(RawLayout::parse(&layout.to_string()).unwrap(), layout);
+
+        assert_eq!(
+            RawLayout::parse("type = float").err().unwrap().expected(),
+            Some("dims")
+        );
+        assert!(RawLayout::parse("dims = 1 2").is_err());
+    }
+
+    #[test]
+    fn write_and_read() {
+        let array = DataArray {
+            name: "values".to_string(),
+            kind: DatasetAttributes::Scalars,
+            numcomp: 1,
+            data: Buffer::I16((0..24).map(|x| x * 100 - 1000).collect()),
+            lookup_table: None,
+        };
+        let points =
+            StructuredPoints::from_array((2, 3, 4), (1.0, 2.0, 3.0), (0.5, 1.0, 1.0), array);
+
+        let mut bytes = vec![0xff; 3];
+        let mut layout = write_raw_to(&mut bytes, &points).unwrap();
+        layout.skip = 3;
+        let read = read_raw_from(&bytes[..], &layout).unwrap();
+        assert_eq!(read.dims, points.dims);
+        assert_eq!(read.origin, points.origin);
+        assert_eq!(read.scalars().unwrap().data, points.scalars().unwrap().data);
+
+        assert!(layout.check_len(bytes.len() as u64).is_ok());
+        layout.dims.2 = 5;
+        assert!(layout.check_len(bytes.len() as u64).is_err());
+        assert!(read_raw_from(&bytes[..], &layout).is_err());
+        layout.dims = (u32::MAX, u32::MAX, 2);
+        assert!(layout.check_len(u64::MAX).is_err());
+    }
+}
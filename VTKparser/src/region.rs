@@ -0,0 +1,338 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{BufReader, SeekFrom};
+use std::path::Path;
+
+use crate::attributes::{Attributes, DataArray};
+use crate::buffer::{self, ScalarType};
+use crate::spoints::point_count;
+use crate::{probe_from, read_from, Datatype, StructuredPoints, VTKparseError};
+
+/// A box of structured points in index space, taking every `stride`-th
+/// point along each axis
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    /// First index along each axis
+    pub start: (u32, u32, u32),
+    /// Index one past the last along each axis
+    pub end: (u32, u32, u32),
+    pub stride: (u32, u32, u32),
+}
+
+impl Region {
+    /// The points from `start` up to, but not including, `end`
+    pub fn new(start: (u32, u32, u32), end: (u32, u32, u32)) -> Self {
+        Self {
+            start,
+            end,
+            stride: (1, 1, 1),
+        }
+    }
+
+    /// All points of a volume with dimensions `dims`
+    pub fn full(dims: (u32, u32, u32)) -> Self {
+        Self::new((0, 0, 0), dims)
+    }
+
+    pub fn with_stride(self, stride: (u32, u32, u32)) -> Self {
+        Self { stride, ..self }
+    }
+
+    /// Dimensions of the points in the region
+    pub fn dims(&self) -> (u32, u32, u32) {
+        let axis =
+            |start: u32, end: u32, stride: u32| end.saturating_sub(start).div_ceil(stride.max(1));
+        (
+            axis(self.start.0, self.end.0, self.stride.0),
+            axis(self.start.1, self.end.1, self.stride.1),
+            axis(self.start.2, self.end.2, self.stride.2),
+        )
+    }
+
+    fn check(&self, dims: (u32, u32, u32)) -> Result<(), VTKparseError> {
+        let axes = [
+            (self.start.0, self.end.0, self.stride.0, dims.0),
+            (self.start.1, self.end.1, self.stride.1, dims.1),
+            (self.start.2, self.end.2, self.stride.2, dims.2),
+        ];
+        if axes
+            .iter()
+            .any(|&(start, end, stride, n)| start >= end || end > n || stride == 0)
+        {
+            return Err(VTKparseError::FileFormat(format!(
+                "Region {:?} is empty or outside the dimensions {:?}",
+                self, dims
+            )));
+        }
+        Ok(())
+    }
+
+    /// Indices of the region's points in a volume with dimensions `dims`,
+    /// grouped in rows along x
+    fn rows(&self, dims: (u32, u32, u32)) -> impl Iterator<Item = usize> + '_ {
+        let (nx, ny) = (dims.0 as usize, dims.1 as usize);
+        (self.start.2..self.end.2)
+            .step_by(self.stride.2 as usize)
+            .flat_map(move |z| {
+                (self.start.1..self.end.1)
+                    .step_by(self.stride.1 as usize)
+                    .map(move |y| (z as usize * ny + y as usize) * nx + self.start.0 as usize)
+            })
+    }
+
+    fn indices(&self, dims: (u32, u32, u32)) -> Vec<usize> {
+        let row: Vec<usize> = (0..self.end.0 - self.start.0)
+            .step_by(self.stride.0 as usize)
+            .map(|x| x as usize)
+            .collect();
+        self.rows(dims)
+            .flat_map(|start| row.iter().map(move |x| start + x))
+            .collect()
+    }
+
+    fn geometry(
+        &self,
+        origin: (f32, f32, f32),
+        spacing: (f32, f32, f32),
+    ) -> ((f32, f32, f32), (f32, f32, f32)) {
+        (
+            (
+                origin.0 + self.start.0 as f32 * spacing.0,
+                origin.1 + self.start.1 as f32 * spacing.1,
+                origin.2 + self.start.2 as f32 * spacing.2,
+            ),
+            (
+                spacing.0 * self.stride.0 as f32,
+                spacing.1 * self.stride.1 as f32,
+                spacing.2 * self.stride.2 as f32,
+            ),
+        )
+    }
+}
+
+impl StructuredPoints {
+    /// Copies the point data of a region, with the origin and spacing
+    /// adjusted to it
+    ///
+    /// Cell data is dropped, as the cells of a strided region do not match
+    /// the cells of the volume
+    pub fn region(&self, region: &Region) -> Result<StructuredPoints, VTKparseError> {
+        region.check(self.dims)?;
+        let indices = region.indices(self.dims);
+        let (origin, spacing) = region.geometry(self.origin, self.spacing);
+
+        let point = self
+            .attributes
+            .point
+            .iter()
+            .map(|array| DataArray {
+                name: array.name.clone(),
+                kind: array.kind,
+                numcomp: array.numcomp,
+                data: array.data.select(&indices, array.numcomp),
+                lookup_table: array.lookup_table.clone(),
+            })
+            .collect();
+
+        Ok(StructuredPoints {
+            dims: region.dims(),
+            origin,
+            spacing,
+            attributes: Attributes {
+                point,
+                cell: Vec::new(),
+                lookup_tables: self.attributes.lookup_tables.clone(),
+            },
+        })
+    }
+}
+
+/// Reads a region of a structured points file, see [`read_region_from`]
+pub fn read_region<P: AsRef<Path>>(
+    file: P,
+    region: &Region,
+) -> Result<StructuredPoints, VTKparseError> {
+    read_region_from(BufReader::new(File::open(file)?), region)
+}
+
+/// Reads the point data of a region of a structured points file, which
+/// should be at the start of the file
+///
+/// Only the rows of the region are read from binary files, and lookup
+/// tables are skipped. ASCII files are read as a whole and then cropped
+pub fn read_region_from<R: BufRead + Seek>(
+    mut reader: R,
+    region: &Region,
+) -> Result<StructuredPoints, VTKparseError> {
+    let start = reader.stream_position()?;
+    let probe = probe_from(&mut reader)?;
+    region.check(probe.dims)?;
+
+    if !probe.header.binary {
+        reader.seek(SeekFrom::Start(start))?;
+        return match read_from(reader)?.dataset {
+            Datatype::StructuredPoints(points) => points.region(region),
+            _ => Err(VTKparseError::FileFormat(
+                "Expected structured points".to_string(),
+            )),
+        };
+    }
+
+    let row_len = (region.end.0 - region.start.0) as usize;
+    let points = point_count(probe.dims)?;
+    let mut point = Vec::new();
+    for array in probe.arrays.iter().filter(|x| x.point) {
+        // Field arrays need not have a tuple for every point
+        if array.len != points {
+            return Err(VTKparseError::FileFormat(format!(
+                "Array {} has {} points, but the dimensions give {}",
+                array.name, array.len, points
+            )));
+        }
+        if array.scalar_type == ScalarType::Bit {
+            return Err(VTKparseError::NotImplemented(format!(
+                "Reading a region of bit array {}",
+                array.name
+            )));
+        }
+        let tuple = array.scalar_type.size() * array.numcomp;
+
+        let mut bytes = Vec::new();
+        let mut row = vec![0; row_len * tuple];
+        let mut position = None;
+        for index in region.rows(probe.dims) {
+            let offset = array.offset + (index * tuple) as u64;
+            // Consecutive rows need no seeking
+            if position != Some(offset) {
+                reader.seek(SeekFrom::Start(offset))?;
+            }
+            reader.read_exact(&mut row)?;
+            position = Some(offset + row.len() as u64);
+
+            for value in row.chunks_exact(tuple).step_by(region.stride.0 as usize) {
+                bytes.extend_from_slice(value);
+            }
+        }
+
+        point.push(DataArray {
+            name: array.name.clone(),
+            kind: array.kind,
+            numcomp: array.numcomp,
+            data: buffer::from_bytes(bytes, array.scalar_type, false),
+            lookup_table: array.lookup_table.clone(),
+        });
+    }
+
+    let (origin, spacing) = region.geometry(probe.origin, probe.spacing);
+    Ok(StructuredPoints {
+        dims: region.dims(),
+        origin,
+        spacing,
+        attributes: Attributes {
+            point,
+            ..Attributes::default()
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Buffer, DatasetAttributes};
+    use std::io::Cursor;
+
+    const HEADER: &str = "# vtk DataFile Version 3.0
+region
+FORMAT
+DATASET STRUCTURED_POINTS
+DIMENSIONS 4 3 2
+ORIGIN 1 1 1
+SPACING 0.5 1 2
+POINT_DATA 24
+SCALARS a short
+LOOKUP_TABLE default
+";
+
+    #[test]
+    fn binary_and_ascii_regions() {
+        let values: Vec<i16> = (0..24).collect();
+        let mut binary = HEADER.replace("FORMAT", "BINARY").into_bytes();
+        for v in &values {
+            binary.extend_from_slice(&v.to_be_bytes());
+        }
+        binary.extend_from_slice(b"\nVECTORS v unsigned_char\n");
+        binary.extend((0..72).map(|x| x as u8));
+        let mut ascii = HEADER.replace("FORMAT", "ASCII");
+        for v in &values {
+            ascii += &format!("{}\n", v);
+        }
+
+        let region = Region::new((1, 0, 0), (4, 3, 2)).with_stride((2, 2, 1));
+        assert_eq!(region.dims(), (2, 2, 2));
+
+        let points = read_region_from(Cursor::new(&binary), &region).unwrap();
+        assert_eq!(points.dims, (2, 2, 2));
+        assert_eq!(points.origin, (1.5, 1.0, 1.0));
+        assert_eq!(points.spacing, (1.0, 2.0, 2.0));
+        let expected = vec![1, 3, 9, 11, 13, 15, 21, 23];
+        assert_eq!(
+            points.scalars().unwrap().data,
+            Buffer::I16(expected.clone())
+        );
+        assert_eq!(
+            points.attributes.point[1].data.as_u8().unwrap()[..6],
+            [3, 4, 5, 9, 10, 11]
+        );
+
+        let ascii = read_region_from(Cursor::new(ascii), &region).unwrap();
+        assert_eq!(ascii.scalars().unwrap().data, Buffer::I16(expected));
+        assert_eq!(ascii.origin, points.origin);
+    }
+
+    #[test]
+    fn field_arrays() {
+        let mut binary = HEADER.replace("FORMAT", "BINARY").into_bytes();
+        for v in 0..24i16 {
+            binary.extend_from_slice(&v.to_be_bytes());
+        }
+        binary.extend_from_slice(b"\nFIELD FieldData 2\nNULL_ARRAY\nids 1 24 unsigned_char\n");
+        binary.extend(100..124u8);
+        let mut ascii = HEADER.replace("FORMAT", "ASCII");
+        for v in 0..24 {
+            ascii += &format!("{}\n", v);
+        }
+        ascii += "FIELD FieldData 2\nNULL_ARRAY\nids 1 24 unsigned_char\n";
+        for v in 100..124 {
+            ascii += &format!("{}\n", v);
+        }
+
+        let region = Region::new((0, 1, 1), (2, 3, 2));
+        let binary = read_region_from(Cursor::new(&binary), &region).unwrap();
+        let read = read_region_from(Cursor::new(&ascii), &region).unwrap();
+        for points in &[binary, read] {
+            let ids = points.attributes.point_array("ids").unwrap();
+            assert_eq!(ids.kind, DatasetAttributes::FieldData);
+            assert_eq!(ids.data, Buffer::U8(vec![116, 117, 120, 121]));
+        }
+
+        // Both reject a field array of another length
+        let short = ascii.replace("ids 1 24", "ids 1 23");
+        assert!(read_region_from(Cursor::new(&short), &region).is_err());
+        let mut short = HEADER.replace("FORMAT", "BINARY").into_bytes();
+        for v in 0..24i16 {
+            short.extend_from_slice(&v.to_be_bytes());
+        }
+        short.extend_from_slice(b"\nFIELD FieldData 1\nids 1 23 unsigned_char\n");
+        short.extend(100..123u8);
+        assert!(read_region_from(Cursor::new(&short), &region).is_err());
+    }
+
+    #[test]
+    fn outside_is_an_error() {
+        let bytes = HEADER.replace("FORMAT", "ASCII") + &"0\n".repeat(24);
+        let region = Region::new((0, 0, 0), (5, 3, 2));
+        assert!(read_region_from(Cursor::new(&bytes), &region).is_err());
+        let region = Region::full((4, 3, 2)).with_stride((0, 1, 1));
+        assert!(read_region_from(Cursor::new(&bytes), &region).is_err());
+    }
+}
@@ -0,0 +1,312 @@
+//! Resampling of point data on arbitrary cells onto a uniform grid
+//!
+//! Cells are split into tetrahedra, and every grid point inside a
+//! tetrahedron gets the linear interpolation of the point data at its
+//! corners, and the value of the cell data of the cell. Grid points outside
+//! all cells are set to zero
+
+use crate::attributes::{Attributes, DataArray};
+use crate::buffer::Buffer;
+use crate::StructuredPoints;
+
+/// Dimensions of a uniform grid covering `bounds`, with `n` points along
+/// the longest axis and the other axes scaled to give cubic cells
+pub(crate) fn resolution(bounds: &[(f64, f64); 3], n: u32) -> (u32, u32, u32) {
+    let longest = bounds.iter().map(|b| b.1 - b.0).fold(0.0, f64::max);
+    let axis = |b: (f64, f64)| {
+        if longest > 0.0 && n > 1 {
+            ((b.1 - b.0) / longest * f64::from(n - 1)).round() as u32 + 1
+        } else {
+            1
+        }
+    };
+    (axis(bounds[0]), axis(bounds[1]), axis(bounds[2]))
+}
+
+pub(crate) struct Resampler<'a> {
+    dims: [usize; 3],
+    origin: [f64; 3],
+    spacing: [f64; 3],
+    attributes: &'a Attributes,
+    /// Resampled point data followed by resampled cell data
+    values: Vec<Vec<f32>>,
+}
+
+impl<'a> Resampler<'a> {
+    /// Prepares resampling of all point and cell data onto a grid covering `bounds`
+    pub fn new(
+        bounds: &[(f64, f64); 3],
+        dims: (u32, u32, u32),
+        attributes: &'a Attributes,
+    ) -> Self {
+        let dims = [dims.0 as usize, dims.1 as usize, dims.2 as usize];
+        let mut origin = [0.0; 3];
+        let mut spacing = [1.0; 3];
+        for axis in 0..3 {
+            origin[axis] = bounds[axis].0;
+            if dims[axis] > 1 && bounds[axis].1 > bounds[axis].0 {
+                spacing[axis] = (bounds[axis].1 - bounds[axis].0) / (dims[axis] - 1) as f64;
+            }
+        }
+
+        let len = dims[0] * dims[1] * dims[2];
+        let values = attributes
+            .point
+            .iter()
+            .chain(&attributes.cell)
+            .map(|a| vec![0.0; len * a.numcomp])
+            .collect();
+
+        Self {
+            dims,
+            origin,
+            spacing,
+            attributes,
+            values,
+        }
+    }
+
+    /// Fills the grid points inside the tetrahedron with corners `p`, which
+    /// are the points with index `ids`, and which is part of cell `cell`
+    pub fn tetra(&mut self, p: [[f64; 3]; 4], ids: [usize; 4], cell: usize) {
+        let e = |i: usize| [p[i][0] - p[0][0], p[i][1] - p[0][1], p[i][2] - p[0][2]];
+        let (a, b, c) = (e(1), e(2), e(3));
+
+        // Rows of the inverse of the matrix with columns a, b, c
+        let det = a[0] * (b[1] * c[2] - b[2] * c[1]) - b[0] * (a[1] * c[2] - a[2] * c[1])
+            + c[0] * (a[1] * b[2] - a[2] * b[1]);
+        if det.abs() < 1e-300 {
+            return;
+        }
+        let inv = [
+            cross(b, c).map(|x| x / det),
+            cross(c, a).map(|x| x / det),
+            cross(a, b).map(|x| x / det),
+        ];
+
+        let mut range = [(0, 0); 3];
+        for axis in 0..3 {
+            let lo = p.iter().map(|x| x[axis]).fold(f64::INFINITY, f64::min);
+            let hi = p.iter().map(|x| x[axis]).fold(f64::NEG_INFINITY, f64::max);
+            let lo = ((lo - self.origin[axis]) / self.spacing[axis])
+                .ceil()
+                .max(0.0) as usize;
+            let hi = ((hi - self.origin[axis]) / self.spacing[axis]).floor();
+            if hi < 0.0 || lo >= self.dims[axis] {
+                return;
+            }
+            range[axis] = (lo, (hi as usize).min(self.dims[axis] - 1));
+        }
+
+        const EPS: f64 = -1e-9;
+        for k in range[2].0..=range[2].1 {
+            for j in range[1].0..=range[1].1 {
+                for i in range[0].0..=range[0].1 {
+                    let x = [
+                        self.origin[0] + i as f64 * self.spacing[0] - p[0][0],
+                        self.origin[1] + j as f64 * self.spacing[1] - p[0][1],
+                        self.origin[2] + k as f64 * self.spacing[2] - p[0][2],
+                    ];
+                    let l1 = dot(inv[0], x);
+                    let l2 = dot(inv[1], x);
+                    let l3 = dot(inv[2], x);
+                    let l0 = 1.0 - l1 - l2 - l3;
+                    if l0 < EPS || l1 < EPS || l2 < EPS || l3 < EPS {
+                        continue;
+                    }
+
+                    let index = i + self.dims[0] * (j + self.dims[1] * k);
+                    let weights = [l0, l1, l2, l3];
+                    let (point_values, cell_values) =
+                        self.values.split_at_mut(self.attributes.point.len());
+
+                    for (array, values) in self.attributes.point.iter().zip(point_values) {
+                        let numcomp = array.numcomp;
+                        for comp in 0..numcomp {
+                            let v: f64 = ids
+                                .iter()
+                                .zip(&weights)
+                                .map(|(&id, w)| {
+                                    w * array.data.get(id * numcomp + comp).unwrap_or(0.0)
+                                })
+                                .sum();
+                            values[index * numcomp + comp] = v as f32;
+                        }
+                    }
+                    for (array, values) in self.attributes.cell.iter().zip(cell_values) {
+                        let numcomp = array.numcomp;
+                        for comp in 0..numcomp {
+                            let v = array.data.get(cell * numcomp + comp).unwrap_or(0.0);
+                            values[index * numcomp + comp] = v as f32;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Fills the grid points inside the hexahedron with corners in VTK order
+    pub fn hexahedron(&mut self, p: [[f64; 3]; 8], ids: [usize; 8], cell: usize) {
+        // Six tetrahedra around the diagonal from corner 0 to 6, which gives
+        // matching faces between neighbouring cells
+        const TETRAS: [[usize; 4]; 6] = [
+            [0, 1, 2, 6],
+            [0, 2, 3, 6],
+            [0, 3, 7, 6],
+            [0, 7, 4, 6],
+            [0, 4, 5, 6],
+            [0, 5, 1, 6],
+        ];
+        for t in &TETRAS {
+            self.tetra(
+                [p[t[0]], p[t[1]], p[t[2]], p[t[3]]],
+                [ids[t[0]], ids[t[1]], ids[t[2]], ids[t[3]]],
+                cell,
+            );
+        }
+    }
+
+    /// Fills the grid points inside the wedge with corners in VTK order
+    pub fn wedge(&mut self, p: [[f64; 3]; 6], ids: [usize; 6], cell: usize) {
+        const TETRAS: [[usize; 4]; 3] = [[0, 1, 2, 3], [1, 2, 3, 4], [2, 3, 4, 5]];
+        for t in &TETRAS {
+            self.tetra(
+                [p[t[0]], p[t[1]], p[t[2]], p[t[3]]],
+                [ids[t[0]], ids[t[1]], ids[t[2]], ids[t[3]]],
+                cell,
+            );
+        }
+    }
+
+    /// Fills the grid points inside the pyramid with corners in VTK order
+    pub fn pyramid(&mut self, p: [[f64; 3]; 5], ids: [usize; 5], cell: usize) {
+        const TETRAS: [[usize; 4]; 2] = [[0, 1, 2, 4], [0, 2, 3, 4]];
+        for t in &TETRAS {
+            self.tetra(
+                [p[t[0]], p[t[1]], p[t[2]], p[t[3]]],
+                [ids[t[0]], ids[t[1]], ids[t[2]], ids[t[3]]],
+                cell,
+            );
+        }
+    }
+
+    /// The resampled data, cell data is stored as point data of the grid
+    pub fn finish(self) -> StructuredPoints {
+        let point = self
+            .attributes
+            .point
+            .iter()
+            .chain(&self.attributes.cell)
+            .zip(self.values)
+            .map(|(array, values)| DataArray {
+                name: array.name.clone(),
+                kind: array.kind,
+                numcomp: array.numcomp,
+                data: Buffer::F32(values),
+                lookup_table: array.lookup_table.clone(),
+            })
+            .collect();
+
+        StructuredPoints {
+            dims: (
+                self.dims[0] as u32,
+                self.dims[1] as u32,
+                self.dims[2] as u32,
+            ),
+            origin: (
+                self.origin[0] as f32,
+                self.origin[1] as f32,
+                self.origin[2] as f32,
+            ),
+            spacing: (
+                self.spacing[0] as f32,
+                self.spacing[1] as f32,
+                self.spacing[2] as f32,
+            ),
+            attributes: Attributes {
+                point,
+                cell: Vec::new(),
+                lookup_tables: self.attributes.lookup_tables.clone(),
+            },
+        }
+    }
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DatasetAttributes;
+
+    #[test]
+    fn linear_field_is_reproduced() {
+        // The value at every corner of the unit cube is x + 2y + 3z
+        let corners = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [1.0, 0.0, 1.0],
+            [1.0, 1.0, 1.0],
+            [0.0, 1.0, 1.0],
+        ];
+        let attributes = Attributes {
+            point: vec![DataArray {
+                name: "f".to_string(),
+                kind: DatasetAttributes::Scalars,
+                numcomp: 1,
+                data: Buffer::F64(
+                    corners
+                        .iter()
+                        .map(|p| p[0] + 2.0 * p[1] + 3.0 * p[2])
+                        .collect(),
+                ),
+                lookup_table: None,
+            }],
+            cell: vec![DataArray {
+                name: "material".to_string(),
+                kind: DatasetAttributes::Scalars,
+                numcomp: 1,
+                data: Buffer::U8(vec![7]),
+                lookup_table: None,
+            }],
+            lookup_tables: Vec::new(),
+        };
+
+        let bounds = [(0.0, 1.0); 3];
+        let mut resampler = Resampler::new(&bounds, (3, 3, 3), &attributes);
+        resampler.hexahedron(corners, [0, 1, 2, 3, 4, 5, 6, 7], 0);
+        let points = resampler.finish();
+
+        let material = points.attributes.point_array("material").unwrap();
+        assert_eq!(material.data, Buffer::F32(vec![7.0; 27]));
+
+        let data = points.scalars().unwrap().data.as_f32().unwrap();
+        for k in 0..3 {
+            for j in 0..3 {
+                for i in 0..3 {
+                    let expected = (i + 2 * j + 3 * k) as f32 / 2.0;
+                    assert!((data[i + 3 * (j + 3 * k)] - expected).abs() < 1e-5);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn resolution_follows_extent() {
+        let bounds = [(0.0, 2.0), (0.0, 1.0), (5.0, 5.0)];
+        assert_eq!(resolution(&bounds, 5), (5, 3, 1));
+    }
+}
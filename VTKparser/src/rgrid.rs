@@ -0,0 +1,277 @@
+use std::io::prelude::*;
+
+use crate::attributes::{self, Attributes, DataArray};
+use crate::buffer::{self, Buffer, ScalarType};
+use crate::spoints::{cell_count, get_dimensions, point_count};
+use crate::{keyword, next_line, StructuredPoints, VTKparseError};
+
+/// A grid which is regular in topology, with an independent set of
+/// coordinates along each axis
+pub struct RectilinearGrid {
+    pub dims: (u32, u32, u32),
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+    pub z: Vec<f64>,
+    pub attributes: Attributes,
+}
+
+pub fn parse<R: BufRead>(reader: &mut R, binary: bool) -> Result<RectilinearGrid, VTKparseError> {
+    let dims = next_line(reader)?.unwrap_or_default();
+    if keyword(&dims) != "DIMENSIONS" {
+        return Err(VTKparseError::Expected("DIMENSIONS".to_string()));
+    }
+    let dims = get_dimensions(&dims)?;
+    // Every axis needs a coordinate to span the bounds of the grid
+    if dims.0 == 0 || dims.1 == 0 || dims.2 == 0 {
+        return Err(VTKparseError::FileFormat(format!(
+            "Dimensions {:?} have an empty axis",
+            dims
+        )));
+    }
+
+    let x = read_coordinates(reader, binary, "X_COORDINATES", dims.0)?;
+    let y = read_coordinates(reader, binary, "Y_COORDINATES", dims.1)?;
+    let z = read_coordinates(reader, binary, "Z_COORDINATES", dims.2)?;
+
+    let attributes = attributes::parse(reader, binary)?;
+
+    let points = point_count(dims)?;
+    if let Some(array) = attributes.point.iter().find(|x| x.len() != points) {
+        return Err(VTKparseError::FileFormat(format!(
+            "Array {} has {} points, but the dimensions give {}",
+            array.name,
+            array.len(),
+            points
+        )));
+    }
+    let cells = cell_count(dims)?;
+    if let Some(array) = attributes.cell.iter().find(|x| x.len() != cells) {
+        return Err(VTKparseError::FileFormat(format!(
+            "Array {} has {} cells, but the dimensions give {}",
+            array.name,
+            array.len(),
+            cells
+        )));
+    }
+
+    Ok(RectilinearGrid {
+        dims,
+        x,
+        y,
+        z,
+        attributes,
+    })
+}
+
+fn read_coordinates<R: BufRead>(
+    reader: &mut R,
+    binary: bool,
+    keyword: &str,
+    len: u32,
+) -> Result<Vec<f64>, VTKparseError> {
+    let line = next_line(reader)?.unwrap_or_default();
+    if crate::keyword(&line) != keyword {
+        return Err(VTKparseError::Expected(keyword.to_string()));
+    }
+    let mut words = line.split_whitespace().skip(1);
+
+    let n: u32 = words
+        .next()
+        .ok_or_else(|| VTKparseError::FileFormat(format!("{} has no length", keyword)))?
+        .parse()?;
+    if n != len {
+        return Err(VTKparseError::FileFormat(format!(
+            "{} has {} values, but the dimensions give {}",
+            keyword, n, len
+        )));
+    }
+
+    let datatype = words.next().unwrap_or("float");
+    let datatype = ScalarType::from_name(datatype)
+        .ok_or_else(|| VTKparseError::NotImplemented(format!("Datatype {}", datatype)))?;
+
+    Ok(buffer::read(reader, binary, datatype, n as usize)?.to_f64())
+}
+
+impl RectilinearGrid {
+    /// Smallest and largest coordinate along each axis
+    pub fn bounds(&self) -> [(f64, f64); 3] {
+        let bound = |c: &[f64]| (c[0], c[c.len() - 1]);
+        [bound(&self.x), bound(&self.y), bound(&self.z)]
+    }
+
+    /// Dimensions of a uniform grid fine enough to resolve the smallest
+    /// spacing along each axis, with at most `max` points per axis
+    pub fn default_resolution(&self, max: u32) -> (u32, u32, u32) {
+        let resolution = |c: &[f64]| {
+            let smallest = c
+                .windows(2)
+                .map(|w| w[1] - w[0])
+                .filter(|&d| d > 0.0)
+                .fold(f64::INFINITY, f64::min);
+            if !smallest.is_finite() {
+                return 1;
+            }
+            let n = ((c[c.len() - 1] - c[0]) / smallest).round() as u32 + 1;
+            n.clamp(c.len() as u32, max.max(c.len() as u32))
+        };
+        (
+            resolution(&self.x),
+            resolution(&self.y),
+            resolution(&self.z),
+        )
+    }
+
+    /// Trilinear interpolation of the point data onto a uniform grid
+    /// covering the same bounds
+    ///
+    /// The resampled arrays are stored as floats, cell data is dropped
+    pub fn resample(&self, dims: (u32, u32, u32)) -> StructuredPoints {
+        let (wx, ox, sx) = axis_weights(&self.x, dims.0);
+        let (wy, oy, sy) = axis_weights(&self.y, dims.1);
+        let (wz, oz, sz) = axis_weights(&self.z, dims.2);
+
+        let (nx, ny) = (self.dims.0 as usize, self.dims.1 as usize);
+
+        let point = self
+            .attributes
+            .point
+            .iter()
+            .map(|array| {
+                let numcomp = array.numcomp;
+                let value = |i: usize, j: usize, k: usize, c: usize| {
+                    array
+                        .data
+                        .get((i + nx * (j + ny * k)) * numcomp + c)
+                        .unwrap_or(0.0)
+                };
+
+                let mut data = Vec::with_capacity(wx.len() * wy.len() * wz.len() * numcomp);
+                for &(k, tz) in &wz {
+                    let k1 = (k + 1).min(self.z.len() - 1);
+                    for &(j, ty) in &wy {
+                        let j1 = (j + 1).min(self.y.len() - 1);
+                        for &(i, tx) in &wx {
+                            let i1 = (i + 1).min(self.x.len() - 1);
+                            for c in 0..numcomp {
+                                let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+                                let y0 = lerp(
+                                    lerp(value(i, j, k, c), value(i1, j, k, c), tx),
+                                    lerp(value(i, j1, k, c), value(i1, j1, k, c), tx),
+                                    ty,
+                                );
+                                let y1 = lerp(
+                                    lerp(value(i, j, k1, c), value(i1, j, k1, c), tx),
+                                    lerp(value(i, j1, k1, c), value(i1, j1, k1, c), tx),
+                                    ty,
+                                );
+                                data.push(lerp(y0, y1, tz) as f32);
+                            }
+                        }
+                    }
+                }
+
+                DataArray {
+                    name: array.name.clone(),
+                    kind: array.kind,
+                    numcomp,
+                    data: Buffer::F32(data),
+                    lookup_table: array.lookup_table.clone(),
+                }
+            })
+            .collect();
+
+        StructuredPoints {
+            dims,
+            origin: (ox, oy, oz),
+            spacing: (sx, sy, sz),
+            attributes: Attributes {
+                point,
+                cell: Vec::new(),
+                lookup_tables: self.attributes.lookup_tables.clone(),
+            },
+        }
+    }
+}
+
+/// For `n` uniformly placed samples along an axis, finds the index of the
+/// coordinate to the left and the weight of the coordinate to the right.
+/// Also returns the origin and spacing of the samples
+fn axis_weights(coords: &[f64], n: u32) -> (Vec<(usize, f64)>, f32, f32) {
+    let lo = coords[0];
+    let hi = coords[coords.len() - 1];
+    let spacing = if n > 1 {
+        (hi - lo) / f64::from(n - 1)
+    } else {
+        1.0
+    };
+
+    let weights = (0..n)
+        .map(|s| {
+            let t = lo + f64::from(s) * spacing;
+            let i = coords
+                .partition_point(|&c| c <= t)
+                .saturating_sub(1)
+                .min(coords.len().saturating_sub(2));
+            let width = coords.get(i + 1).map_or(0.0, |&c| c - coords[i]);
+            let w = if width > 0.0 {
+                ((t - coords[i]) / width).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            (i, w)
+        })
+        .collect();
+
+    (weights, lo as f32, spacing as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_resample() {
+        let text = "DIMENSIONS 3 2 1
+X_COORDINATES 3 float
+0 1 3
+Y_COORDINATES 2 double
+0 2
+Z_COORDINATES 1 float
+0
+POINT_DATA 6
+SCALARS temperature float
+LOOKUP_TABLE default
+0 1 3
+0 1 3
+";
+        let grid = parse(&mut text.as_bytes(), false).unwrap();
+        assert_eq!(grid.x, vec![0.0, 1.0, 3.0]);
+        assert_eq!(grid.bounds()[1], (0.0, 2.0));
+        assert_eq!(grid.default_resolution(100), (4, 2, 1));
+
+        let points = grid.resample((4, 2, 1));
+        assert_eq!(points.spacing, (1.0, 2.0, 1.0));
+        // The temperature equals the x coordinate
+        assert_eq!(
+            points.scalars().unwrap().data,
+            Buffer::F32(vec![0.0, 1.0, 2.0, 3.0, 0.0, 1.0, 2.0, 3.0])
+        );
+
+        // The two cells need a value each
+        let cells = "CELL_DATA 1\nSCALARS id int\nLOOKUP_TABLE default\n";
+        assert!(parse(&mut format!("{}{}0\n", text, cells).as_bytes(), false).is_err());
+        let cells = cells.replace("CELL_DATA 1", "CELL_DATA 2");
+        assert!(parse(&mut format!("{}{}0 1\n", text, cells).as_bytes(), false).is_ok());
+    }
+
+    #[test]
+    fn wrong_number_of_coordinates() {
+        let text = "DIMENSIONS 2 1 1\nX_COORDINATES 3 float\n0 1 2\n";
+        assert!(parse(&mut text.as_bytes(), false).is_err());
+
+        let text = "DIMENSIONS 0 1 1\nX_COORDINATES 0 float\nY_COORDINATES 1 float\n0\n\
+                    Z_COORDINATES 1 float\n0\n";
+        assert!(parse(&mut text.as_bytes(), false).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+use std::io::prelude::*;
+
+use crate::attributes::{self, Attributes};
+use crate::geometry::{self, read_points};
+use crate::resample::{self, Resampler};
+use crate::spoints::{cell_count, get_dimensions, point_count};
+use crate::{keyword, next_line, StructuredPoints, VTKparseError};
+
+/// A grid which is regular in topology, where every point has an
+/// explicit position
+pub struct StructuredGrid {
+    pub dims: (u32, u32, u32),
+    pub points: Vec<[f64; 3]>,
+    pub attributes: Attributes,
+}
+
+pub fn parse<R: BufRead>(reader: &mut R, binary: bool) -> Result<StructuredGrid, VTKparseError> {
+    let dims = next_line(reader)?.unwrap_or_default();
+    if keyword(&dims) != "DIMENSIONS" {
+        return Err(VTKparseError::Expected("DIMENSIONS".to_string()));
+    }
+    let dims = get_dimensions(&dims)?;
+    let len = point_count(dims)?;
+
+    let points = read_points(reader, binary, Some(len))?;
+
+    let attributes = attributes::parse(reader, binary)?;
+
+    if let Some(array) = attributes.point.iter().find(|x| x.len() != len) {
+        return Err(VTKparseError::FileFormat(format!(
+            "Array {} has {} points, but the dimensions give {}",
+            array.name,
+            array.len(),
+            len
+        )));
+    }
+    let cells = cell_count(dims)?;
+    if let Some(array) = attributes.cell.iter().find(|x| x.len() != cells) {
+        return Err(VTKparseError::FileFormat(format!(
+            "Array {} has {} cells, but the dimensions give {}",
+            array.name,
+            array.len(),
+            cells
+        )));
+    }
+
+    Ok(StructuredGrid {
+        dims,
+        points,
+        attributes,
+    })
+}
+
+impl StructuredGrid {
+    /// Smallest and largest coordinate along each axis
+    pub fn bounds(&self) -> [(f64, f64); 3] {
+        geometry::bounds(&self.points)
+    }
+
+    /// Dimensions of a uniform grid with cubic cells, with twice as many
+    /// points as this grid along the longest axis, but no more than `max`
+    pub fn default_resolution(&self, max: u32) -> (u32, u32, u32) {
+        let n = self.dims.0.max(self.dims.1).max(self.dims.2);
+        resample::resolution(&self.bounds(), (2 * n).min(max))
+    }
+
+    /// Interpolates the point data onto a uniform grid covering the bounds
+    ///
+    /// Every hexahedral cell is split into tetrahedra and the point data is
+    /// interpolated linearly inside them. The resampled arrays are stored
+    /// as floats, and points outside the grid are zero. Grids with a single
+    /// point along an axis have no hexahedra, and are not resampled
+    pub fn resample(&self, dims: (u32, u32, u32)) -> Result<StructuredPoints, VTKparseError> {
+        let (x, y, z) = self.dims;
+        if x < 2 || y < 2 || z < 2 {
+            return Err(VTKparseError::NotImplemented(format!(
+                "Resampling a structured grid of dimensions {} {} {}",
+                x, y, z
+            )));
+        }
+        let mut resampler = Resampler::new(&self.bounds(), dims, &self.attributes);
+
+        let (nx, ny, nz) = (
+            self.dims.0 as usize,
+            self.dims.1 as usize,
+            self.dims.2 as usize,
+        );
+        let index = |i: usize, j: usize, k: usize| i + nx * (j + ny * k);
+
+        for k in 0..nz.saturating_sub(1) {
+            for j in 0..ny.saturating_sub(1) {
+                for i in 0..nx.saturating_sub(1) {
+                    let ids = [
+                        index(i, j, k),
+                        index(i + 1, j, k),
+                        index(i + 1, j + 1, k),
+                        index(i, j + 1, k),
+                        index(i, j, k + 1),
+                        index(i + 1, j, k + 1),
+                        index(i + 1, j + 1, k + 1),
+                        index(i, j + 1, k + 1),
+                    ];
+                    let cell = i + (nx - 1) * (j + (ny - 1) * k);
+                    resampler.hexahedron(ids.map(|id| self.points[id]), ids, cell);
+                }
+            }
+        }
+
+        Ok(resampler.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+
+    #[test]
+    fn parse_and_resample() {
+        // A unit cube, with the top face sheared in x
+        let text = "DIMENSIONS 2 2 2
+POINTS 8 float
+0 0 0  1 0 0  0 1 0  1 1 0
+1 0 1  2 0 1  1 1 1  2 1 1
+POINT_DATA 8
+SCALARS height double
+LOOKUP_TABLE default
+0 0 0 0 1 1 1 1
+";
+        let grid = parse(&mut text.as_bytes(), false).unwrap();
+        assert_eq!(grid.points[5], [2.0, 0.0, 1.0]);
+        assert_eq!(grid.bounds(), [(0.0, 2.0), (0.0, 1.0), (0.0, 1.0)]);
+        assert_eq!(grid.default_resolution(100), (4, 3, 3));
+
+        let points = grid.resample((3, 2, 3)).unwrap();
+        let data = points.scalars().unwrap().data.as_f32().unwrap();
+        // Inside the sheared cell the value is the height
+        assert_eq!(data[0], 0.0);
+        assert!((data[1 + 3 * 2] - 0.5).abs() < 1e-6);
+        assert!((data[2 + 3 * 2 * 2] - 1.0).abs() < 1e-6);
+        // Outside of the cell
+        assert_eq!(data[2], 0.0);
+        assert_eq!(points.attributes.point[0].data.len(), 18);
+        assert!(matches!(points.attributes.point[0].data, Buffer::F32(_)));
+    }
+
+    #[test]
+    fn wrong_number_of_points() {
+        let text = "DIMENSIONS 2 1 1\nPOINTS 1 float\n0 0 0\n";
+        assert!(parse(&mut text.as_bytes(), false).is_err());
+    }
+
+    #[test]
+    fn wrong_number_of_cells() {
+        let points: String = (0..12)
+            .map(|i| format!("{} {} {}\n", i % 2, i / 2 % 2, i / 4))
+            .collect();
+        let text = format!(
+            "DIMENSIONS 2 2 3\nPOINTS 12 float\n{}CELL_DATA 1\nSCALARS id int\nLOOKUP_TABLE default\n0\n",
+            points
+        );
+        assert!(parse(&mut text.as_bytes(), false).is_err());
+
+        let text = text.replace("CELL_DATA 1", "CELL_DATA 2") + "1\n";
+        assert!(parse(&mut text.as_bytes(), false).is_ok());
+    }
+
+    #[test]
+    fn flat_grid_is_not_resampled() {
+        let text = "DIMENSIONS 2 2 1\nPOINTS 4 float\n0 0 0  1 0 0  0 1 0  1 1 0\n";
+        let grid = parse(&mut text.as_bytes(), false).unwrap();
+        assert!(grid.resample(grid.default_resolution(10)).is_err());
+    }
+}
@@ -1,126 +1,178 @@
-use std::io;
 use std::io::prelude::*;
 
-use crate::VTKparseError;
+use crate::attributes::{self, Attributes, DataArray};
+use crate::buffer;
+use crate::{keyword, next_line, VTKparseError};
+
+/// Dimensions, origin and spacing
+pub(crate) type Geometry = ((u32, u32, u32), (f32, f32, f32), (f32, f32, f32));
 
 pub struct StructuredPoints {
     pub dims: (u32, u32, u32),
     pub origin: (f32, f32, f32),
     pub spacing: (f32, f32, f32),
-    pub data: Vec<u8>,
-    pub datatype: String,
-    pub dataname: String,
-    pub numcomp: u8,
-    pub tablename: String,
+    pub attributes: Attributes,
 }
 
-pub fn parse<R: io::Read>(
-    reader: &mut io::BufReader<R>,
-    binary: bool,
-) -> Result<StructuredPoints, VTKparseError> {
-    let mut dims = String::new();
-    reader.read_line(&mut dims)?;
-
-    if !dims.starts_with("DIMENSIONS ") {
-        return Err(VTKparseError::FileFormat(
-            "Does not contain dimensions".to_string(),
-        ));
-    }
-    let dims = get_dimensions(&dims)?;
-
-    let mut origin = String::new();
-    reader.read_line(&mut origin)?;
-    if !origin.starts_with("ORIGIN ") {
-        return Err(VTKparseError::FileFormat(
-            "Does not contain origin".to_string(),
-        ));
+impl StructuredPoints {
+    /// Structured points with a single point array
+    pub fn from_array(
+        dims: (u32, u32, u32),
+        origin: (f32, f32, f32),
+        spacing: (f32, f32, f32),
+        array: DataArray,
+    ) -> Self {
+        StructuredPoints {
+            dims,
+            origin,
+            spacing,
+            attributes: Attributes {
+                point: vec![array],
+                ..Attributes::default()
+            },
+        }
     }
-    let origo = get_origo(&origin)?;
-
-    let mut spacing = String::new();
-    reader.read_line(&mut spacing)?;
 
-    if !spacing.starts_with("SPACING ") && !spacing.starts_with("ASPECT_RATIO ") {
-        return Err(VTKparseError::FileFormat(
-            "Does not contain spacing".to_string(),
-        ));
+    /// The first scalar array attached to the points
+    pub fn scalars(&self) -> Option<&DataArray> {
+        self.attributes.point_scalars().next()
     }
-    let spacing = get_spacing(&spacing)?;
-
-    let mut line = String::new();
-    reader.read_line(&mut line)?;
-    if !line.starts_with("POINT_DATA ") {
-        return Err(VTKparseError::FileFormat(
-            "Does not contain length".to_string(),
-        ));
-    }
-
-    let len: usize = line.split_whitespace().last().unwrap().parse()?;
 
-    let mut line = String::new();
-    reader.read_line(&mut line)?;
-    if !line.starts_with("SCALARS ") {
-        return Err(VTKparseError::FileFormat(
-            "Does not contain scalar type".to_string(),
-        ));
+    /// The array written by formats which hold a single array, the first
+    /// point array
+    pub(crate) fn single_array(&self) -> Result<&DataArray, VTKparseError> {
+        self.attributes
+            .point
+            .first()
+            .ok_or_else(|| VTKparseError::FileFormat("Volume has no point array".to_string()))
     }
 
-    let mut words = line.split_whitespace().skip(1);
+    /// Reverses the order of the points along the axes given, keeping the
+    /// space they cover
+    pub(crate) fn flip(&mut self, axes: [bool; 3]) {
+        if !axes.contains(&true) {
+            return;
+        }
+        let (nx, ny, nz) = self.dims;
+        let (nx, ny, nz) = (nx as usize, ny as usize, nz as usize);
+        let index = |i: usize, n: usize, axis: usize| if axes[axis] { n - 1 - i } else { i };
+        let tuples: Vec<usize> = (0..nz)
+            .flat_map(|z| {
+                (0..ny).flat_map(move |y| {
+                    (0..nx).map(move |x| {
+                        index(x, nx, 0) + nx * (index(y, ny, 1) + ny * index(z, nz, 2))
+                    })
+                })
+            })
+            .collect();
+        for array in &mut self.attributes.point {
+            array.data = array.data.select(&tuples, array.numcomp);
+        }
 
-    let dataname = words
-        .next()
-        .ok_or_else(|| VTKparseError::FileFormat("Could not find dataname".to_string()))?;
-    let datatype = words
-        .next()
-        .ok_or_else(|| VTKparseError::FileFormat("Could not find datatype".to_string()))?;
-    let numcomp: u8 = words.next().unwrap_or("1").parse()?;
-
-    let mut line = String::new();
-    reader.read_line(&mut line)?;
-
-    if !line.starts_with("LOOKUP_TABLE") {
-        return Err(VTKparseError::FileFormat("No lookup table".to_string()));
+        let (x, y, z) = self.dims;
+        let (sx, sy, sz) = self.spacing;
+        let extent = |flip: bool, n: u32, spacing: f32| {
+            if flip {
+                n.saturating_sub(1) as f32 * spacing
+            } else {
+                0.0
+            }
+        };
+        self.origin.0 -= extent(axes[0], x, sx);
+        self.origin.1 -= extent(axes[1], y, sy);
+        self.origin.2 -= extent(axes[2], z, sz);
     }
+}
 
-    let mut table = line.split_whitespace().skip(1);
-    let tablename = table.next().unwrap_or("default");
-
-    if datatype != "unsigned_char" {
-        return Err(VTKparseError::NotImplemented(
-            format!("Datatype {}", datatype).to_string(),
-        ));
+/// Which axes run against the world axes, for the directions of the three
+/// axes of an oriented volume
+///
+/// Readers flip these axes and leave out other rotations, so the volume is
+/// shown aligned with the axes. Axes are only flipped when each is closest
+/// to its own world axis, as for slightly tilted scans. Volumes with swapped
+/// axes are left as they are
+pub(crate) fn axis_flips(directions: [[f32; 3]; 3]) -> [bool; 3] {
+    let dominant = |d: [f32; 3]| {
+        (0..3)
+            .max_by(|&a, &b| d[a].abs().total_cmp(&d[b].abs()))
+            .unwrap()
+    };
+    if (0..3).any(|i| dominant(directions[i]) != i) {
+        return [false; 3];
     }
+    [
+        directions[0][0] < 0.0,
+        directions[1][1] < 0.0,
+        directions[2][2] < 0.0,
+    ]
+}
 
-    if !binary {
-        return Err(VTKparseError::NotImplemented(
-            "ASCII extraction".to_string(),
-        ));
-    }
+pub fn parse<R: BufRead>(reader: &mut R, binary: bool) -> Result<StructuredPoints, VTKparseError> {
+    let (dims, origo, spacing) = read_geometry(reader)?;
 
-    let mut colours = Vec::with_capacity(len);
-    reader.read_to_end(&mut colours)?;
+    let attributes = attributes::parse(reader, binary)?;
 
-    if colours.len() != len {
-        return Err(VTKparseError::UnknownFormat(format!(
-            "Number of elements in binary is not correct. Expected {} but got {}",
-            len,
-            colours.len()
+    let points = point_count(dims)?;
+    if let Some(array) = attributes.point.iter().find(|x| x.len() != points) {
+        return Err(VTKparseError::FileFormat(format!(
+            "Array {} has {} points, but the dimensions give {}",
+            array.name,
+            array.len(),
+            points
         )));
     }
 
     Ok(StructuredPoints {
-        dims: dims,
+        dims,
         origin: origo,
-        spacing: spacing,
-        data: colours,
-        datatype: datatype.to_string(),
-        dataname: dataname.to_string(),
-        numcomp: numcomp,
-        tablename: tablename.to_string(),
+        spacing,
+        attributes,
     })
 }
 
-fn get_dimensions(dims: &str) -> Result<(u32, u32, u32), VTKparseError> {
+/// Reads the `DIMENSIONS`, `ORIGIN` and `SPACING` lines, in any order
+pub(crate) fn read_geometry<R: BufRead>(reader: &mut R) -> Result<Geometry, VTKparseError> {
+    let mut dims = None;
+    let mut origo = None;
+    let mut spacing = None;
+
+    while dims.is_none() || origo.is_none() || spacing.is_none() {
+        let line = next_line(reader)?.unwrap_or_default();
+        match keyword(&line).as_str() {
+            "DIMENSIONS" if dims.is_none() => dims = Some(get_dimensions(&line)?),
+            "ORIGIN" if origo.is_none() => origo = Some(get_origo(&line)?),
+            "SPACING" | "ASPECT_RATIO" if spacing.is_none() => spacing = Some(get_spacing(&line)?),
+            _ => {
+                let missing = if dims.is_none() {
+                    "DIMENSIONS"
+                } else if origo.is_none() {
+                    "ORIGIN"
+                } else {
+                    "SPACING"
+                };
+                return Err(VTKparseError::Expected(missing.to_string()));
+            }
+        }
+    }
+
+    Ok((dims.unwrap(), origo.unwrap(), spacing.unwrap()))
+}
+
+/// Number of points of a grid with dimensions `dims`
+pub(crate) fn point_count(dims: (u32, u32, u32)) -> Result<usize, VTKparseError> {
+    buffer::count(&[dims.0 as usize, dims.1 as usize, dims.2 as usize])
+}
+
+/// Number of cells of a grid with dimensions `dims`, where axes of a single
+/// point do not divide the cells, as in VTK
+pub(crate) fn cell_count(dims: (u32, u32, u32)) -> Result<usize, VTKparseError> {
+    if point_count(dims)? == 0 {
+        return Ok(0);
+    }
+    buffer::count(&[dims.0, dims.1, dims.2].map(|n| (n as usize - 1).max(1)))
+}
+
+pub(crate) fn get_dimensions(dims: &str) -> Result<(u32, u32, u32), VTKparseError> {
     let mut dims = dims.split_whitespace().skip(1);
 
     let d0 = dims.next();
@@ -160,16 +212,81 @@ fn get_spacing(spacing: &str) -> Result<(f32, f32, f32), VTKparseError> {
     match (d0, d1, d2) {
         (Some(x), Some(y), Some(z)) => Ok((x.parse()?, y.parse()?, z.parse()?)),
         _ => Err(VTKparseError::FileFormat(
-            "Can not extract origin".to_string(),
+            "Can not extract spacing".to_string(),
         )),
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::parse;
+    use crate::Buffer;
+    use std::io::BufReader;
+
+    const HEADER: &str = "DIMENSIONS 3 2 2
+ORIGIN 0 0 0
+SPACING 1 1 1
+POINT_DATA 12
+SCALARS volume unsigned_char
+LOOKUP_TABLE default
+";
+
     #[test]
     fn read_something() {
-        use read_file;
+        use crate::read_file;
         read_file("../data/tooth.vtk").unwrap();
     }
+
+    #[test]
+    fn ascii_and_binary_are_equal() {
+        let values: Vec<u8> = (0..12).map(|x| x * 20).collect();
+
+        let mut binary = HEADER.as_bytes().to_vec();
+        binary.extend_from_slice(&values);
+
+        let ascii = format!(
+            "{}0 20 40\n60 80\n100 120 140 160 180\n\n 200  220\n",
+            HEADER
+        );
+
+        let binary = parse(&mut BufReader::new(&binary[..]), true).unwrap();
+        let ascii = parse(&mut BufReader::new(ascii.as_bytes()), false).unwrap();
+
+        assert_eq!(binary.scalars().unwrap().data, Buffer::U8(values));
+        assert_eq!(
+            ascii.scalars().unwrap().data,
+            binary.scalars().unwrap().data
+        );
+        assert_eq!(ascii.dims, binary.dims);
+    }
+
+    #[test]
+    fn short_volume() {
+        let header = HEADER.replace("unsigned_char", "short");
+        let values: Vec<i16> = (0..12).map(|x| x * 1000 - 6000).collect();
+
+        let mut binary = header.as_bytes().to_vec();
+        for v in &values {
+            binary.extend_from_slice(&v.to_be_bytes());
+        }
+        let mut ascii = header;
+        for v in &values {
+            ascii += &format!("{}\n", v);
+        }
+
+        let binary = parse(&mut BufReader::new(&binary[..]), true).unwrap();
+        let ascii = parse(&mut BufReader::new(ascii.as_bytes()), false).unwrap();
+
+        assert_eq!(binary.scalars().unwrap().data, Buffer::I16(values));
+        assert_eq!(
+            ascii.scalars().unwrap().data,
+            binary.scalars().unwrap().data
+        );
+    }
+
+    #[test]
+    fn ascii_wrong_length() {
+        let ascii = format!("{}0 20 40 60\n", HEADER);
+        assert!(parse(&mut BufReader::new(ascii.as_bytes()), false).is_err());
+    }
 }
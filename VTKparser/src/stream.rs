@@ -0,0 +1,136 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{BufReader, SeekFrom};
+use std::path::Path;
+
+use crate::buffer::{self, Buffer, ScalarType};
+use crate::{ArrayInfo, Probe, VTKparseError};
+
+/// Reads a point array of structured points one z slice at a time, so that
+/// a volume never has to be held in memory as a whole
+///
+/// The array is located with [`probe_file`](crate::probe_file). Each slice is
+/// returned with the type of the file, holding `dims.0 * dims.1` tuples.
+/// Bit arrays of binary files are not supported
+pub struct SliceReader<R> {
+    reader: R,
+    binary: bool,
+    scalar_type: ScalarType,
+    /// Number of values in a slice
+    slice_len: usize,
+    remaining: u32,
+}
+
+impl SliceReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(
+        file: P,
+        probe: &Probe,
+        array: &ArrayInfo,
+    ) -> Result<Self, VTKparseError> {
+        Self::new(BufReader::new(File::open(file)?), probe, array)
+    }
+}
+
+impl<R: BufRead + Seek> SliceReader<R> {
+    pub fn new(mut reader: R, probe: &Probe, array: &ArrayInfo) -> Result<Self, VTKparseError> {
+        if !array.point {
+            return Err(VTKparseError::NotImplemented(format!(
+                "Reading cell array {} by slices",
+                array.name
+            )));
+        }
+        // Slices of bits need not start at a byte
+        if probe.header.binary && array.scalar_type == ScalarType::Bit {
+            return Err(VTKparseError::NotImplemented(format!(
+                "Reading bit array {} by slices",
+                array.name
+            )));
+        }
+        reader.seek(SeekFrom::Start(array.offset))?;
+
+        let (width, height, depth) = probe.dims;
+        Ok(Self {
+            reader,
+            binary: probe.header.binary,
+            scalar_type: array.scalar_type,
+            slice_len: width as usize * height as usize * array.numcomp,
+            remaining: depth,
+        })
+    }
+
+    /// Number of slices which have not been read
+    pub fn remaining(&self) -> u32 {
+        self.remaining
+    }
+}
+
+impl<R: BufRead + Seek> Iterator for SliceReader<R> {
+    type Item = Result<Buffer, VTKparseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let slice = buffer::read(
+            &mut self.reader,
+            self.binary,
+            self.scalar_type,
+            self.slice_len,
+        );
+        if slice.is_err() {
+            // The position in the file is unknown after an error
+            self.remaining = 0;
+        }
+        Some(slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::probe_from;
+    use std::io::Cursor;
+
+    #[test]
+    fn slices() {
+        let header = "# vtk DataFile Version 3.0
+slices
+FORMAT
+DATASET STRUCTURED_POINTS
+DIMENSIONS 2 2 3
+ORIGIN 0 0 0
+SPACING 1 1 1
+POINT_DATA 12
+SCALARS a unsigned_short
+LOOKUP_TABLE default
+";
+        let values: Vec<u16> = (0..12).map(|x| x * 100).collect();
+
+        let mut binary = header.replace("FORMAT", "BINARY").into_bytes();
+        for v in &values {
+            binary.extend_from_slice(&v.to_be_bytes());
+        }
+        let mut ascii = header.replace("FORMAT", "ASCII");
+        for v in &values {
+            ascii += &format!("{}\n", v);
+        }
+
+        for bytes in &[binary, ascii.into_bytes()] {
+            let probe = probe_from(Cursor::new(bytes)).unwrap();
+            let reader = SliceReader::new(Cursor::new(bytes), &probe, &probe.arrays[0]).unwrap();
+            let slices: Vec<Buffer> = reader.map(Result::unwrap).collect();
+            assert_eq!(slices.len(), 3);
+            assert_eq!(slices[1], Buffer::U16(values[4..8].to_vec()));
+        }
+
+        let mut bits = header
+            .replace("FORMAT", "BINARY")
+            .replace("unsigned_short", "bit")
+            .into_bytes();
+        bits.extend_from_slice(&[0xff, 0]);
+        let probe = probe_from(Cursor::new(&bits)).unwrap();
+        assert!(SliceReader::new(Cursor::new(&bits), &probe, &probe.arrays[0]).is_err());
+    }
+}
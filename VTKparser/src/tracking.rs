@@ -0,0 +1,168 @@
+use std::io::prelude::*;
+use std::io::{self, SeekFrom};
+
+use crate::error::Location;
+
+/// Longest part of a line kept for error messages
+const MAX_CONTENT: usize = 256;
+
+/// Wraps a reader to keep track of the position, so that errors can tell
+/// where in the file they happened
+pub(crate) struct Tracking<R> {
+    inner: R,
+    lines: Lines,
+}
+
+/// The position and lines of the bytes read so far, kept apart from the
+/// reader so bytes borrowed from its buffer can be tracked
+struct Lines {
+    position: u64,
+    /// Number of line breaks read
+    line: usize,
+    line_start: u64,
+    /// Start of the line being read
+    current: Vec<u8>,
+    /// The last complete line, with its number and offset
+    previous: Option<(usize, u64, Vec<u8>)>,
+}
+
+impl<R> Tracking<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self::at(inner, 0)
+    }
+
+    /// Starts tracking a reader which is at byte `position`
+    pub(crate) fn at(inner: R, position: u64) -> Self {
+        Self {
+            inner,
+            lines: Lines {
+                position,
+                line: 0,
+                line_start: position,
+                current: Vec::new(),
+                previous: None,
+            },
+        }
+    }
+
+    /// Number of bytes read
+    pub(crate) fn position(&self) -> u64 {
+        self.lines.position
+    }
+
+    /// The line being read, or the last one if a line was just finished
+    pub(crate) fn location(&self) -> Location {
+        let lines = &self.lines;
+        let (line, offset, content) = match lines.previous {
+            Some((line, offset, ref content)) if lines.current.is_empty() => {
+                (line, offset, content)
+            }
+            _ => (lines.line + 1, lines.line_start, &lines.current),
+        };
+        Location {
+            line,
+            offset,
+            content: String::from_utf8_lossy(content).trim_end().to_string(),
+        }
+    }
+}
+
+impl Lines {
+    fn track(&mut self, bytes: &[u8]) {
+        let mut rest = bytes;
+        while let Some(i) = rest.iter().position(|&b| b == b'\n') {
+            self.keep(&rest[..i]);
+            let line = std::mem::take(&mut self.current);
+            let (previous_line, previous_start) = (self.line + 1, self.line_start);
+            // Reuse the allocation of the line before
+            if let Some((_, _, mut old)) = self.previous.take() {
+                old.clear();
+                self.current = old;
+            }
+            self.previous = Some((previous_line, previous_start, line));
+
+            self.line += 1;
+            self.line_start = self.position + (bytes.len() - rest.len() + i + 1) as u64;
+            rest = &rest[i + 1..];
+        }
+        self.keep(rest);
+        self.position += bytes.len() as u64;
+    }
+
+    fn keep(&mut self, bytes: &[u8]) {
+        let n = MAX_CONTENT
+            .saturating_sub(self.current.len())
+            .min(bytes.len());
+        self.current.extend_from_slice(&bytes[..n]);
+    }
+}
+
+impl<R: Seek> Tracking<R> {
+    /// Skips `bytes` bytes ahead, line breaks which are skipped are not
+    /// counted
+    pub(crate) fn skip(&mut self, bytes: u64) -> io::Result<()> {
+        let lines = &mut self.lines;
+        lines.position = self.inner.seek(SeekFrom::Start(lines.position + bytes))?;
+        lines.line_start = lines.position;
+        lines.current.clear();
+        lines.previous = None;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Tracking<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.lines.track(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Tracking<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if amt == 0 {
+            return;
+        }
+        // The buffer is already filled, so this does not read anything
+        let seen = match self.inner.fill_buf() {
+            Ok(buf) => {
+                let n = amt.min(buf.len());
+                self.lines.track(&buf[..n]);
+                n
+            }
+            Err(_) => 0,
+        };
+        // Bytes which could not be seen are still counted
+        self.lines.position += (amt - seen) as u64;
+        self.inner.consume(amt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines() {
+        let mut reader = Tracking::new(&b"first\nsecond line\nthird"[..]);
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(reader.location().content, "first");
+
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        let location = reader.location();
+        assert_eq!(location.line, 2);
+        assert_eq!(location.offset, 6);
+        assert_eq!(location.content, "second line");
+
+        reader.consume(2);
+        assert_eq!(reader.location().line, 3);
+        assert_eq!(reader.location().content, "th");
+        assert_eq!(reader.position(), 20);
+    }
+}
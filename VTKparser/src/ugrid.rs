@@ -0,0 +1,322 @@
+use std::convert::TryFrom;
+use std::io::prelude::*;
+
+use crate::attributes::{self, Attributes};
+use crate::buffer::{self, ScalarType};
+use crate::geometry::{self, read_cells, read_points, Cells};
+use crate::resample::{self, Resampler};
+use crate::{keyword, next_line, StructuredPoints, VTKparseError};
+
+/// The linear cell types of VTK, with their numeric identifiers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CellType {
+    Vertex,
+    PolyVertex,
+    Line,
+    PolyLine,
+    Triangle,
+    TriangleStrip,
+    Polygon,
+    Pixel,
+    Quad,
+    Tetra,
+    Voxel,
+    Hexahedron,
+    Wedge,
+    Pyramid,
+    /// Nonlinear and other cell types, which are kept but not interpreted
+    Other(u8),
+}
+
+impl CellType {
+    pub fn from_id(id: u8) -> Self {
+        match id {
+            1 => CellType::Vertex,
+            2 => CellType::PolyVertex,
+            3 => CellType::Line,
+            4 => CellType::PolyLine,
+            5 => CellType::Triangle,
+            6 => CellType::TriangleStrip,
+            7 => CellType::Polygon,
+            8 => CellType::Pixel,
+            9 => CellType::Quad,
+            10 => CellType::Tetra,
+            11 => CellType::Voxel,
+            12 => CellType::Hexahedron,
+            13 => CellType::Wedge,
+            14 => CellType::Pyramid,
+            x => CellType::Other(x),
+        }
+    }
+
+    pub fn id(self) -> u8 {
+        match self {
+            CellType::Vertex => 1,
+            CellType::PolyVertex => 2,
+            CellType::Line => 3,
+            CellType::PolyLine => 4,
+            CellType::Triangle => 5,
+            CellType::TriangleStrip => 6,
+            CellType::Polygon => 7,
+            CellType::Pixel => 8,
+            CellType::Quad => 9,
+            CellType::Tetra => 10,
+            CellType::Voxel => 11,
+            CellType::Hexahedron => 12,
+            CellType::Wedge => 13,
+            CellType::Pyramid => 14,
+            CellType::Other(x) => x,
+        }
+    }
+
+    /// Number of points of the cell, `None` if it is variable or unknown
+    pub fn num_points(self) -> Option<usize> {
+        match self {
+            CellType::Vertex => Some(1),
+            CellType::Line => Some(2),
+            CellType::Triangle => Some(3),
+            CellType::Pixel | CellType::Quad | CellType::Tetra => Some(4),
+            CellType::Pyramid => Some(5),
+            CellType::Wedge => Some(6),
+            CellType::Voxel | CellType::Hexahedron => Some(8),
+            _ => None,
+        }
+    }
+}
+
+/// Cells of arbitrary type
+pub struct UnstructuredGrid {
+    pub points: Vec<[f64; 3]>,
+    pub cells: Cells,
+    pub cell_types: Vec<CellType>,
+    pub attributes: Attributes,
+}
+
+pub fn parse<R: BufRead>(reader: &mut R, binary: bool) -> Result<UnstructuredGrid, VTKparseError> {
+    let points = read_points(reader, binary, None)?;
+
+    let line = next_line(reader)?.unwrap_or_default();
+    if keyword(&line) != "CELLS" {
+        return Err(VTKparseError::Expected("CELLS".to_string()));
+    }
+    let cells = read_cells(reader, binary, &line)?;
+
+    let line = next_line(reader)?.unwrap_or_default();
+    if keyword(&line) != "CELL_TYPES" {
+        return Err(VTKparseError::Expected("CELL_TYPES".to_string()));
+    }
+    let n: usize = line.split_whitespace().nth(1).unwrap_or("").parse()?;
+    if n != cells.len() {
+        return Err(VTKparseError::FileFormat(format!(
+            "Number of cell types is {}, but there are {} cells",
+            n,
+            cells.len()
+        )));
+    }
+    let cell_types = buffer::read(reader, binary, ScalarType::Int, n)?
+        .to_f64()
+        .into_iter()
+        .map(|x| match u8::try_from(x as i64) {
+            Ok(id) if x.fract() == 0.0 => Ok(CellType::from_id(id)),
+            _ => Err(VTKparseError::FileFormat(format!(
+                "Invalid cell type {}",
+                x
+            ))),
+        })
+        .collect::<Result<_, _>>()?;
+
+    let attributes = attributes::parse(reader, binary)?;
+
+    let grid = UnstructuredGrid {
+        points,
+        cells,
+        cell_types,
+        attributes,
+    };
+
+    for (i, (cell, kind)) in grid.cells.iter().zip(&grid.cell_types).enumerate() {
+        if let Some(&id) = cell.iter().find(|&&id| id >= grid.points.len()) {
+            return Err(VTKparseError::FileFormat(format!(
+                "Point index {} of cell {} is out of range, there are {} points",
+                id,
+                i,
+                grid.points.len()
+            )));
+        }
+        if let Some(n) = kind.num_points() {
+            if n != cell.len() {
+                return Err(VTKparseError::FileFormat(format!(
+                    "Cell {} of type {:?} has {} points, expected {}",
+                    i,
+                    kind,
+                    cell.len(),
+                    n
+                )));
+            }
+        }
+    }
+    if let Some(array) = grid
+        .attributes
+        .point
+        .iter()
+        .find(|x| x.len() != grid.points.len())
+    {
+        return Err(VTKparseError::FileFormat(format!(
+            "Array {} has {} points, but there are {}",
+            array.name,
+            array.len(),
+            grid.points.len()
+        )));
+    }
+    if let Some(array) = grid
+        .attributes
+        .cell
+        .iter()
+        .find(|x| x.len() != grid.cells.len())
+    {
+        return Err(VTKparseError::FileFormat(format!(
+            "Array {} has {} cells, but there are {}",
+            array.name,
+            array.len(),
+            grid.cells.len()
+        )));
+    }
+
+    Ok(grid)
+}
+
+impl UnstructuredGrid {
+    /// Smallest and largest coordinate along each axis
+    pub fn bounds(&self) -> [(f64, f64); 3] {
+        geometry::bounds(&self.points)
+    }
+
+    /// Dimensions of a uniform grid with cubic cells and about twice as
+    /// many points along each axis as the mesh, but no more than `max`
+    pub fn default_resolution(&self, max: u32) -> (u32, u32, u32) {
+        let n = 2.0 * (self.points.len() as f64).cbrt();
+        resample::resolution(&self.bounds(), (n.ceil() as u32).min(max))
+    }
+
+    /// Rasterises the point and cell data onto a uniform grid covering the
+    /// bounds
+    ///
+    /// Tetrahedra, voxels, hexahedra, wedges and pyramids are split into
+    /// tetrahedra, in which the point data is interpolated linearly and the
+    /// cell data is constant. Two dimensional and nonlinear cells do not
+    /// cover any volume and are skipped. The resampled arrays are stored as
+    /// floats, and points outside the mesh are zero
+    pub fn resample(&self, dims: (u32, u32, u32)) -> StructuredPoints {
+        let mut resampler = Resampler::new(&self.bounds(), dims, &self.attributes);
+        let p = |id: usize| self.points[id];
+
+        for (i, (cell, kind)) in self.cells.iter().zip(&self.cell_types).enumerate() {
+            match kind {
+                CellType::Tetra => {
+                    let ids = [cell[0], cell[1], cell[2], cell[3]];
+                    resampler.tetra(ids.map(p), ids, i);
+                }
+                CellType::Voxel => {
+                    // Voxels number their corners in x, y, z order
+                    let ids = [
+                        cell[0], cell[1], cell[3], cell[2], cell[4], cell[5], cell[7], cell[6],
+                    ];
+                    resampler.hexahedron(ids.map(p), ids, i);
+                }
+                CellType::Hexahedron => {
+                    let mut ids = [0; 8];
+                    ids.copy_from_slice(cell);
+                    resampler.hexahedron(ids.map(p), ids, i);
+                }
+                CellType::Wedge => {
+                    let mut ids = [0; 6];
+                    ids.copy_from_slice(cell);
+                    resampler.wedge(ids.map(p), ids, i);
+                }
+                CellType::Pyramid => {
+                    let mut ids = [0; 5];
+                    ids.copy_from_slice(cell);
+                    resampler.pyramid(ids.map(p), ids, i);
+                }
+                _ => {}
+            }
+        }
+
+        resampler.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Buffer;
+
+    // A unit cube split into a tetrahedron and a pyramid, plus a triangle
+    const TEXT: &str = "POINTS 6 float
+0 0 0  1 0 0  1 1 0  0 1 0
+0 0 1  1 1 1
+CELLS 3 15
+5 0 1 2 3 5
+4 0 3 5 4
+3 0 1 5
+CELL_TYPES 3
+14
+10
+5
+POINT_DATA 6
+SCALARS z float
+LOOKUP_TABLE default
+0 0 0 0 1 1
+CELL_DATA 3
+SCALARS material int
+LOOKUP_TABLE default
+1 2 3
+";
+
+    #[test]
+    fn parse_grid() {
+        let grid = parse(&mut TEXT.as_bytes(), false).unwrap();
+        assert_eq!(
+            grid.cell_types,
+            vec![CellType::Pyramid, CellType::Tetra, CellType::Triangle]
+        );
+        assert_eq!(grid.cells.get(1), Some(&[0, 3, 5, 4][..]));
+        assert_eq!(grid.attributes.cell[0].data, Buffer::I32(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn resample_grid() {
+        let grid = parse(&mut TEXT.as_bytes(), false).unwrap();
+        let points = grid.resample((2, 2, 3));
+
+        let z = points.attributes.point_array("z").unwrap();
+        let z = z.data.as_f32().unwrap();
+        let material = points.attributes.point_array("material").unwrap();
+        let material = material.data.as_f32().unwrap();
+
+        // (0, 0, 0.5) is on an edge of the tetrahedron
+        assert!((z[4] - 0.5).abs() < 1e-6);
+        // (1, 1, 0.5) is inside the pyramid
+        assert!((z[7] - 0.5).abs() < 1e-6);
+        assert_eq!(material[7], 1.0);
+        // (1, 0, 1) is outside of both
+        assert_eq!(z[9], 0.0);
+        assert_eq!(material[9], 0.0);
+    }
+
+    #[test]
+    fn wrong_number_of_points_in_cell() {
+        let text = "POINTS 3 float\n0 0 0 1 0 0 0 1 0\nCELLS 1 3\n2 0 1\nCELL_TYPES 1\n5\n";
+        assert!(parse(&mut text.as_bytes(), false).is_err());
+    }
+
+    #[test]
+    fn invalid_cell_type() {
+        let text = "POINTS 3 float\n0 0 0 1 0 0 0 1 0\nCELLS 1 4\n3 0 1 2\nCELL_TYPES 1\n5\n";
+        assert!(parse(&mut text.as_bytes(), false).is_ok());
+        for id in &["261", "-251"] {
+            let text = text.replace("TYPES 1\n5", &format!("TYPES 1\n{}", id));
+            assert!(parse(&mut text.as_bytes(), false).is_err());
+        }
+    }
+}
@@ -0,0 +1,636 @@
+use std::convert::TryInto;
+use std::io::prelude::*;
+
+use crate::attributes::{Attributes, DataArray};
+use crate::buffer::{self, Buffer, ScalarType};
+use crate::spoints::{cell_count, point_count};
+use crate::{Data, DatasetAttributes, Datatype, Header, StructuredPoints, VTKparseError};
+
+/// Checks whether the start of a file looks like a VTK XML file
+pub(crate) fn is_xml(start: &[u8]) -> bool {
+    let start = start.strip_prefix(b"\xef\xbb\xbf").unwrap_or(start);
+    let skip = start.iter().take_while(|b| b.is_ascii_whitespace()).count();
+    let start = &start[skip..];
+    start.starts_with(b"<?xml") || start.starts_with(b"<VTKFile")
+}
+
+/// Parses a VTK XML `ImageData` file into structured points
+///
+/// Arrays may be given as ascii, inline base64 or appended raw or base64
+/// data, with either byte order and header type. Compressed files and files
+/// with more than one piece are not supported, and the `Direction` of the
+/// image is ignored
+pub(crate) fn parse<R: Read>(mut reader: R) -> Result<Data, VTKparseError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    // Raw appended data is not valid XML, so it is split off first
+    let (markup, appended) = split_appended(&bytes)?;
+    let markup = std::str::from_utf8(markup)
+        .map_err(|_| VTKparseError::FileFormat("XML is not valid UTF-8".to_string()))?;
+    let tags = tags(markup);
+
+    let file = tags
+        .iter()
+        .find(|t| t.name == "VTKFile" && !t.closing)
+        .ok_or_else(|| VTKparseError::UnknownFormat("No VTKFile element".to_string()))?;
+    let kind = file.attribute("type").unwrap_or("");
+    if kind != "ImageData" {
+        return Err(VTKparseError::NotImplemented(format!(
+            "VTK XML {} files",
+            kind
+        )));
+    }
+    if file.attribute("compressor").is_some() {
+        return Err(VTKparseError::NotImplemented(
+            "Compressed VTK XML files".to_string(),
+        ));
+    }
+    let little_endian = match file.attribute("byte_order").unwrap_or("LittleEndian") {
+        "LittleEndian" => true,
+        "BigEndian" => false,
+        x => {
+            return Err(VTKparseError::FileFormat(format!(
+                "Unknown byte order {}",
+                x
+            )))
+        }
+    };
+    let header_size = match file.attribute("header_type").unwrap_or("UInt32") {
+        "UInt32" => 4,
+        "UInt64" => 8,
+        x => {
+            return Err(VTKparseError::FileFormat(format!(
+                "Unknown header type {}",
+                x
+            )))
+        }
+    };
+    let encoding = Encoding {
+        little_endian,
+        header_size,
+        appended,
+    };
+
+    let image = tags
+        .iter()
+        .find(|t| t.name == "ImageData" && !t.closing)
+        .ok_or_else(|| VTKparseError::FileFormat("No ImageData element".to_string()))?;
+    let pieces: Vec<&Tag> = tags
+        .iter()
+        .filter(|t| t.name == "Piece" && !t.closing)
+        .collect();
+    if pieces.len() > 1 {
+        return Err(VTKparseError::NotImplemented(
+            "VTK XML files with several pieces".to_string(),
+        ));
+    }
+
+    // The data covers the extent of the piece, which may start anywhere
+    let extent = pieces
+        .first()
+        .and_then(|p| p.attribute("Extent"))
+        .or_else(|| image.attribute("WholeExtent"))
+        .ok_or_else(|| VTKparseError::FileFormat("ImageData has no extent".to_string()))?;
+    let extent: Vec<i64> = numbers(extent, 6, "Extent")?;
+    let origin: Vec<f32> = numbers(image.attribute("Origin").unwrap_or("0 0 0"), 3, "Origin")?;
+    let spacing: Vec<f32> = numbers(image.attribute("Spacing").unwrap_or("1 1 1"), 3, "Spacing")?;
+
+    let mut dims = [0u32; 3];
+    for (axis, d) in dims.iter_mut().enumerate() {
+        let n = extent[2 * axis + 1] - extent[2 * axis] + 1;
+        if n < 1 {
+            return Err(VTKparseError::FileFormat(format!(
+                "Invalid extent {:?}",
+                extent
+            )));
+        }
+        *d = n as u32;
+    }
+    let dims = (dims[0], dims[1], dims[2]);
+    let points = point_count(dims)?;
+    let cells = cell_count(dims)?;
+
+    let mut attributes = Attributes::default();
+    let mut binary = false;
+    // Point or cell data, with the names of the active attributes
+    let mut section: Option<(bool, &Tag)> = None;
+    for tag in &tags {
+        match tag.name {
+            "PointData" | "CellData" if tag.closing => section = None,
+            "PointData" | "CellData" if !tag.self_closing => {
+                section = Some((tag.name == "PointData", tag));
+            }
+            "DataArray" if !tag.closing => {
+                let (is_point, active) = match section {
+                    Some(x) => x,
+                    // Coordinates and field data of other elements
+                    None => continue,
+                };
+                let len = if is_point { points } else { cells };
+                let (array, is_binary) = read_array(tag, len, &encoding)?;
+                binary |= is_binary;
+                let array = with_kind(array, active);
+
+                let arrays = if is_point {
+                    &mut attributes.point
+                } else {
+                    &mut attributes.cell
+                };
+                // The active scalars come first, as they are shown by default
+                if active.attribute("Scalars") == Some(array.name.as_str()) {
+                    arrays.insert(0, array);
+                } else {
+                    arrays.push(array);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let version = file.attribute("version").unwrap_or("0.1");
+    let mut version = version.split('.');
+    let major = version.next().unwrap_or("0").parse()?;
+    let minor = version.next().unwrap_or("0").parse()?;
+
+    Ok(Data {
+        header: Header {
+            file_version: (major, minor),
+            header: kind.to_string(),
+            binary,
+        },
+        dataset: Datatype::StructuredPoints(StructuredPoints {
+            dims,
+            origin: (
+                origin[0] + extent[0] as f32 * spacing[0],
+                origin[1] + extent[2] as f32 * spacing[1],
+                origin[2] + extent[4] as f32 * spacing[2],
+            ),
+            spacing: (spacing[0], spacing[1], spacing[2]),
+            attributes,
+        }),
+        field_data: None,
+    })
+}
+
+/// How binary data is laid out in the file
+struct Encoding<'a> {
+    little_endian: bool,
+    /// Size in bytes of the length preceding each block of binary data
+    header_size: usize,
+    appended: Option<Appended<'a>>,
+}
+
+struct Appended<'a> {
+    raw: bool,
+    /// Data following the `_` marker, which offsets are relative to
+    data: &'a [u8],
+}
+
+/// Splits the file into the markup and the contents of `AppendedData`
+fn split_appended(bytes: &[u8]) -> Result<(&[u8], Option<Appended<'_>>), VTKparseError> {
+    let tag = b"<AppendedData";
+    let start = match bytes.windows(tag.len()).position(|w| w == tag) {
+        Some(x) => x,
+        None => return Ok((bytes, None)),
+    };
+    let end = start
+        + bytes[start..]
+            .iter()
+            .position(|&b| b == b'>')
+            .ok_or_else(|| VTKparseError::FileFormat("Unterminated AppendedData".to_string()))?;
+
+    let appended = std::str::from_utf8(&bytes[start..=end])
+        .map_err(|_| VTKparseError::FileFormat("XML is not valid UTF-8".to_string()))?;
+    let raw = match tags(appended)[0].attribute("encoding").unwrap_or("raw") {
+        "raw" => true,
+        "base64" => false,
+        x => return Err(VTKparseError::NotImplemented(format!("Encoding {}", x))),
+    };
+
+    let underscore = bytes[end + 1..]
+        .iter()
+        .position(|&b| b == b'_')
+        .ok_or_else(|| VTKparseError::FileFormat("AppendedData has no _ marker".to_string()))?;
+    let data = &bytes[end + 2 + underscore..];
+
+    Ok((&bytes[..start], Some(Appended { raw, data })))
+}
+
+/// Reads a `DataArray` with `len` tuples, and whether it was stored as binary
+fn read_array(
+    tag: &Tag,
+    len: usize,
+    encoding: &Encoding,
+) -> Result<(DataArray, bool), VTKparseError> {
+    let name = tag.attribute("Name").unwrap_or("").to_string();
+    let numcomp: usize = tag.attribute("NumberOfComponents").unwrap_or("1").parse()?;
+    let datatype = tag.attribute("type").unwrap_or("");
+    let datatype = scalar_type(datatype)
+        .ok_or_else(|| VTKparseError::NotImplemented(format!("Datatype {}", datatype)))?;
+    let n = buffer::count(&[len, numcomp])?;
+
+    let format = tag.attribute("format").unwrap_or("ascii");
+    let bytes = match format {
+        "ascii" => {
+            let data = buffer::read_ascii(&mut tag.text.as_bytes(), datatype, n)?;
+            return Ok((array(name, numcomp, data), false));
+        }
+        "binary" => {
+            let text: Vec<u8> = tag
+                .text
+                .bytes()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect();
+            decode_block(&text, encoding)?
+        }
+        "appended" => {
+            let appended = encoding.appended.as_ref().ok_or_else(|| {
+                VTKparseError::FileFormat(format!("Array {} has no appended data", name))
+            })?;
+            let offset: usize = tag.attribute("offset").unwrap_or("0").parse()?;
+            let data = appended.data.get(offset..).ok_or_else(|| {
+                VTKparseError::FileFormat(format!("Offset of array {} is out of range", name))
+            })?;
+            if appended.raw {
+                let header = data.get(..encoding.header_size).unwrap_or_default();
+                let size = block_size(header, encoding)?;
+                let end = encoding.header_size.checked_add(size);
+                end.and_then(|end| data.get(encoding.header_size..end))
+                    .ok_or_else(|| {
+                        VTKparseError::FileFormat(format!("Array {} is truncated", name))
+                    })?
+                    .to_vec()
+            } else {
+                decode_block(data, encoding)?
+            }
+        }
+        x => return Err(VTKparseError::FileFormat(format!("Unknown format {}", x))),
+    };
+
+    let len = datatype.bytes(n)?;
+    if bytes.len() != len {
+        return Err(VTKparseError::FileFormat(format!(
+            "Array {} has {} bytes, but {} were expected",
+            name,
+            bytes.len(),
+            len
+        )));
+    }
+    let data = buffer::from_bytes(bytes, datatype, encoding.little_endian);
+    Ok((array(name, numcomp, data), true))
+}
+
+fn array(name: String, numcomp: usize, data: Buffer) -> DataArray {
+    DataArray {
+        name,
+        kind: DatasetAttributes::Scalars,
+        numcomp,
+        data,
+        lookup_table: None,
+    }
+}
+
+/// Sets the kind of the array from the active attributes of its section
+///
+/// Other arrays with at most four components are taken to be scalars, like
+/// in legacy files, and larger ones to be field data
+fn with_kind(mut array: DataArray, section: &Tag) -> DataArray {
+    let active = [
+        ("Scalars", DatasetAttributes::Scalars),
+        ("Vectors", DatasetAttributes::Vectors),
+        ("Normals", DatasetAttributes::Normals),
+        ("Tensors", DatasetAttributes::Tensors),
+        ("TCoords", DatasetAttributes::TextureCoordinates),
+    ];
+    array.kind = active
+        .iter()
+        .find(|(attribute, _)| section.attribute(attribute) == Some(array.name.as_str()))
+        .map(|&(_, kind)| kind)
+        .unwrap_or(if array.numcomp <= 4 {
+            DatasetAttributes::Scalars
+        } else {
+            DatasetAttributes::FieldData
+        });
+    array
+}
+
+fn scalar_type(name: &str) -> Option<ScalarType> {
+    match name {
+        "Int8" => Some(ScalarType::Char),
+        "UInt8" => Some(ScalarType::UnsignedChar),
+        "Int16" => Some(ScalarType::Short),
+        "UInt16" => Some(ScalarType::UnsignedShort),
+        "Int32" => Some(ScalarType::Int),
+        "UInt32" => Some(ScalarType::UnsignedInt),
+        "Int64" => Some(ScalarType::Long),
+        "UInt64" => Some(ScalarType::UnsignedLong),
+        "Float32" => Some(ScalarType::Float),
+        "Float64" => Some(ScalarType::Double),
+        _ => None,
+    }
+}
+
+/// The number of bytes of a block, given by its header
+fn block_size(header: &[u8], encoding: &Encoding) -> Result<usize, VTKparseError> {
+    let size = match (header.len(), encoding.little_endian) {
+        (4, true) => u32::from_le_bytes(header.try_into().unwrap()) as u64,
+        (4, false) => u32::from_be_bytes(header.try_into().unwrap()) as u64,
+        (8, true) => u64::from_le_bytes(header.try_into().unwrap()),
+        (8, false) => u64::from_be_bytes(header.try_into().unwrap()),
+        _ => {
+            return Err(VTKparseError::FileFormat(
+                "Binary data has no header".to_string(),
+            ))
+        }
+    };
+    Ok(size as usize)
+}
+
+/// Decodes a base64 block of a header followed by the data
+///
+/// VTK encodes the header and the data separately, giving padding after the
+/// header, but other writers encode them together
+fn decode_block(text: &[u8], encoding: &Encoding) -> Result<Vec<u8>, VTKparseError> {
+    let header_chars = encoding.header_size.div_ceil(3) * 4;
+    let head = text.get(..header_chars).ok_or_else(|| {
+        VTKparseError::FileFormat("Binary data is shorter than its header".to_string())
+    })?;
+    let header = base64(head)?;
+    let header = header.get(..encoding.header_size).ok_or_else(|| {
+        VTKparseError::FileFormat("Binary data is shorter than its header".to_string())
+    })?;
+    let size = block_size(header, encoding)?;
+
+    // Every four characters hold three bytes, which also bounds the sizes
+    // computed below
+    let truncated = || VTKparseError::FileFormat("Binary data is truncated".to_string());
+    if size > text.len() / 4 * 3 {
+        return Err(truncated());
+    }
+    if head.contains(&b'=') {
+        let data = text
+            .get(header_chars..header_chars + size.div_ceil(3) * 4)
+            .ok_or_else(truncated)?;
+        Ok(base64(data)?.into_iter().take(size).collect())
+    } else {
+        let total = encoding.header_size + size;
+        let data = text.get(..total.div_ceil(3) * 4).ok_or_else(truncated)?;
+        Ok(base64(data)?
+            .into_iter()
+            .skip(encoding.header_size)
+            .take(size)
+            .collect())
+    }
+}
+
+/// Decodes base64 up to the first padding character
+fn base64(text: &[u8]) -> Result<Vec<u8>, VTKparseError> {
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for &c in text {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            _ => {
+                return Err(VTKparseError::FileFormat(format!(
+                    "Invalid base64 character {:?}",
+                    c as char
+                )))
+            }
+        };
+        acc = (acc << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Ok(bytes)
+}
+
+fn numbers<T: std::str::FromStr>(
+    text: &str,
+    n: usize,
+    what: &str,
+) -> Result<Vec<T>, VTKparseError> {
+    let values: Vec<T> = text
+        .split_whitespace()
+        .map(|x| x.parse().ok())
+        .collect::<Option<_>>()
+        .ok_or_else(|| VTKparseError::FileFormat(format!("Invalid {} {}", what, text)))?;
+    if values.len() != n {
+        return Err(VTKparseError::FileFormat(format!(
+            "{} has {} values, expected {}",
+            what,
+            values.len(),
+            n
+        )));
+    }
+    Ok(values)
+}
+
+/// An XML tag with its attributes, and the text up to the next tag
+pub(crate) struct Tag<'a> {
+    pub(crate) name: &'a str,
+    attributes: Vec<(&'a str, String)>,
+    closing: bool,
+    self_closing: bool,
+    text: &'a str,
+}
+
+impl Tag<'_> {
+    pub(crate) fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(x, _)| *x == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Splits XML into its tags, skipping declarations and comments
+///
+/// This is only as much of XML as VTK writes, there is no support for
+/// CDATA or for `>` inside attribute values
+pub(crate) fn tags(xml: &str) -> Vec<Tag<'_>> {
+    let mut tags = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        if let Some(comment) = rest.strip_prefix("!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let end = rest.find('>').unwrap_or(rest.len());
+        let body = &rest[..end];
+        rest = rest.get(end + 1..).unwrap_or("");
+        if body.starts_with('?') || body.starts_with('!') {
+            continue;
+        }
+
+        let closing = body.starts_with('/');
+        let self_closing = body.ends_with('/');
+        let body = body.trim_start_matches('/').trim_end_matches('/');
+        let name_end = body
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(body.len());
+
+        let mut attributes = Vec::new();
+        let mut attrs = &body[name_end..];
+        while let Some(eq) = attrs.find('=') {
+            let key = attrs[..eq].trim();
+            let value = attrs[eq + 1..].trim_start();
+            let quote = match value.chars().next() {
+                Some(q @ '"') | Some(q @ '\'') => q,
+                _ => break,
+            };
+            let value = &value[1..];
+            let value_end = value.find(quote).unwrap_or(value.len());
+            attributes.push((key, unescape(&value[..value_end])));
+            attrs = value.get(value_end + 1..).unwrap_or("");
+        }
+
+        tags.push(Tag {
+            name: &body[..name_end],
+            attributes,
+            closing,
+            self_closing,
+            text: &rest[..rest.find('<').unwrap_or(rest.len())],
+        });
+    }
+    tags
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii() {
+        let text = r#"<?xml version="1.0"?>
+<!-- written by hand -->
+<VTKFile type="ImageData" version="1.0" byte_order="LittleEndian" header_type="UInt64">
+  <ImageData WholeExtent="1 2 0 1 0 0" Origin="0 0 0" Spacing="0.5 1 1">
+    <Piece Extent="1 2 0 1 0 0">
+      <PointData Scalars="density">
+        <DataArray type="Float32" Name="velocity" NumberOfComponents="3" format="ascii">
+          0 0 0 1 1 1 2 2 2 3 3 3
+        </DataArray>
+        <DataArray type="Float32" Name="density" format="ascii">
+          0.5 1.5
+          2.5 3.5
+        </DataArray>
+      </PointData>
+      <CellData>
+        <DataArray type="Int32" Name="id" format="ascii">7</DataArray>
+      </CellData>
+    </Piece>
+  </ImageData>
+</VTKFile>
+"#;
+        assert!(is_xml(text.as_bytes()));
+        let data = parse(text.as_bytes()).unwrap();
+        assert_eq!(data.header.file_version, (1, 0));
+        assert!(!data.header.binary);
+
+        let points = data.structured_points().unwrap();
+        assert_eq!(points.dims, (2, 2, 1));
+        assert_eq!(points.origin, (0.5, 0.0, 0.0));
+        assert_eq!(
+            points.scalars().unwrap().data,
+            Buffer::F32(vec![0.5, 1.5, 2.5, 3.5])
+        );
+        assert_eq!(points.attributes.point_array("velocity").unwrap().len(), 4);
+        assert_eq!(points.attributes.cell[0].data, Buffer::I32(vec![7]));
+    }
+
+    #[test]
+    fn inline_base64() {
+        let text = r#"<VTKFile type="ImageData" version="0.1" byte_order="LittleEndian">
+  <ImageData WholeExtent="0 1 0 1 0 0" Origin="0 0 0" Spacing="1 1 1">
+    <Piece Extent="0 1 0 1 0 0">
+      <PointData>
+        <DataArray type="UInt16" Name="separate" format="binary">
+          CAAAAA==AQACAAMABAA=
+        </DataArray>
+        <DataArray type="Int8" Name="together" format="binary">BAAAAP8AAQI=</DataArray>
+      </PointData>
+    </Piece>
+  </ImageData>
+</VTKFile>
+"#;
+        let data = parse(text.as_bytes()).unwrap();
+        let attributes = &data.structured_points().unwrap().attributes;
+        assert_eq!(attributes.point[0].data, Buffer::U16(vec![1, 2, 3, 4]));
+        assert_eq!(attributes.point[1].data, Buffer::I8(vec![-1, 0, 1, 2]));
+
+        // A block header claiming more data than there is
+        let text = text
+            .replace("CAAAAA==AQACAAMABAA=", "//////////8=")
+            .replace("byte_order", "header_type=\"UInt64\" byte_order");
+        assert!(parse(text.as_bytes()).is_err());
+
+        // Padding within the header
+        let text = text.replace("//////////8=", "AA==========");
+        assert!(parse(text.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn raw_appended() {
+        let mut bytes = br#"<?xml version="1.0"?>
+<VTKFile type="ImageData" version="1.0" byte_order="BigEndian" header_type="UInt64">
+  <ImageData WholeExtent="0 2 0 0 0 0" Origin="1 2 3" Spacing="1 1 1">
+    <Piece Extent="0 2 0 0 0 0">
+      <PointData Scalars="b">
+        <DataArray type="Float64" Name="a" format="appended" offset="0"/>
+        <DataArray type="Int16" Name="b" format="appended" offset="32"/>
+      </PointData>
+    </Piece>
+  </ImageData>
+  <AppendedData encoding="raw">
+   _"#
+        .to_vec();
+        bytes.extend_from_slice(&24u64.to_be_bytes());
+        for x in &[0.5f64, -1.0, 2.0] {
+            bytes.extend_from_slice(&x.to_be_bytes());
+        }
+        bytes.extend_from_slice(&6u64.to_be_bytes());
+        for x in &[-3i16, 0, 3] {
+            bytes.extend_from_slice(&x.to_be_bytes());
+        }
+        bytes.extend_from_slice(b"\n  </AppendedData>\n</VTKFile>\n");
+
+        let data = parse(&bytes[..]).unwrap();
+        assert!(data.header.binary);
+        let points = data.structured_points().unwrap();
+        assert_eq!(points.dims, (3, 1, 1));
+        assert_eq!(points.origin, (1.0, 2.0, 3.0));
+        assert_eq!(points.scalars().unwrap().data, Buffer::I16(vec![-3, 0, 3]));
+        assert_eq!(
+            points.attributes.point_array("a").unwrap().data,
+            Buffer::F64(vec![0.5, -1.0, 2.0])
+        );
+    }
+
+    #[test]
+    fn other_types_are_rejected() {
+        let text = r#"<VTKFile type="PolyData"></VTKFile>"#;
+        assert!(parse(text.as_bytes()).is_err());
+        let text = r#"<VTKFile type="ImageData" compressor="vtkZLibDataCompressor"></VTKFile>"#;
+        assert!(parse(text.as_bytes()).is_err());
+    }
+}
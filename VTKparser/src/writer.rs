@@ -0,0 +1,455 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::attributes::{Attributes, DataArray};
+use crate::buffer::{self, Buffer};
+use crate::geometry::Cells;
+use crate::spoints::{cell_count, point_count};
+use crate::{
+    Data, DatasetAttributes, Datatype, FieldData, PolyData, RectilinearGrid, StructuredGrid,
+    StructuredPoints, UnstructuredGrid, VTKparseError,
+};
+
+/// Writes `data` to `file`, in the binary or ASCII format given by its header
+pub fn write_file<P: AsRef<Path>>(file: P, data: &Data) -> Result<(), VTKparseError> {
+    let file = File::create(file)?;
+    let mut writer = Writer::new(BufWriter::new(file), data.header.binary);
+    writer.write(data)?;
+    writer.into_inner().flush()?;
+    Ok(())
+}
+
+/// Serialises datasets as legacy VTK files
+///
+/// Files are written as version 3.0, with cells in the legacy layout. Points
+/// and coordinates are written as doubles, and attribute arrays keep their
+/// type. Writes are small, so `inner` should be buffered
+pub struct Writer<W: Write> {
+    inner: W,
+    binary: bool,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(inner: W, binary: bool) -> Self {
+        Self { inner, binary }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    pub fn write(&mut self, data: &Data) -> Result<(), VTKparseError> {
+        self.write_dataset(&data.header.header, &data.dataset, data.field_data.as_ref())
+    }
+
+    /// Writes a complete file with the title `title`, which is cut to one
+    /// line of at most 255 characters
+    pub fn write_dataset(
+        &mut self,
+        title: &str,
+        dataset: &Datatype,
+        field_data: Option<&FieldData>,
+    ) -> Result<(), VTKparseError> {
+        let title: String = title
+            .lines()
+            .next()
+            .unwrap_or("")
+            .chars()
+            .take(255)
+            .collect();
+        writeln!(self.inner, "# vtk DataFile Version 3.0")?;
+        writeln!(self.inner, "{}", title)?;
+        writeln!(
+            self.inner,
+            "{}",
+            if self.binary { "BINARY" } else { "ASCII" }
+        )?;
+
+        let name = match dataset {
+            Datatype::StructuredPoints(_) => "STRUCTURED_POINTS",
+            Datatype::StructuredGrid(_) => "STRUCTURED_GRID",
+            Datatype::RectilinearGrid(_) => "RECTILINEAR_GRID",
+            Datatype::PolyData(_) => "POLYDATA",
+            Datatype::UnstructuredGrid(_) => "UNSTRUCTURED_GRID",
+            Datatype::Field(ref field) => return self.write_field(field),
+            Datatype::Empty => return Ok(()),
+        };
+        writeln!(self.inner, "DATASET {}", name)?;
+        if let Some(field) = field_data {
+            self.write_field(field)?;
+        }
+
+        match dataset {
+            Datatype::StructuredPoints(ref x) => self.write_structured_points(x),
+            Datatype::StructuredGrid(ref x) => self.write_structured_grid(x),
+            Datatype::RectilinearGrid(ref x) => self.write_rectilinear_grid(x),
+            Datatype::PolyData(ref x) => self.write_poly_data(x),
+            Datatype::UnstructuredGrid(ref x) => self.write_unstructured_grid(x),
+            Datatype::Field(_) | Datatype::Empty => unreachable!(),
+        }
+    }
+
+    fn write_structured_points(&mut self, data: &StructuredPoints) -> Result<(), VTKparseError> {
+        let (d, o, s) = (data.dims, data.origin, data.spacing);
+        writeln!(self.inner, "DIMENSIONS {} {} {}", d.0, d.1, d.2)?;
+        writeln!(self.inner, "ORIGIN {} {} {}", o.0, o.1, o.2)?;
+        writeln!(self.inner, "SPACING {} {} {}", s.0, s.1, s.2)?;
+        self.write_attributes(&data.attributes, point_count(d)?, cell_count(d)?)
+    }
+
+    fn write_structured_grid(&mut self, data: &StructuredGrid) -> Result<(), VTKparseError> {
+        let d = data.dims;
+        writeln!(self.inner, "DIMENSIONS {} {} {}", d.0, d.1, d.2)?;
+        self.write_points(&data.points)?;
+        self.write_attributes(&data.attributes, point_count(d)?, cell_count(d)?)
+    }
+
+    fn write_rectilinear_grid(&mut self, data: &RectilinearGrid) -> Result<(), VTKparseError> {
+        let d = data.dims;
+        writeln!(self.inner, "DIMENSIONS {} {} {}", d.0, d.1, d.2)?;
+        for (keyword, values) in &[
+            ("X_COORDINATES", &data.x),
+            ("Y_COORDINATES", &data.y),
+            ("Z_COORDINATES", &data.z),
+        ] {
+            writeln!(self.inner, "{} {} double", keyword, values.len())?;
+            self.write_buffer(&Buffer::F64(values.to_vec()))?;
+        }
+        self.write_attributes(&data.attributes, point_count(d)?, cell_count(d)?)
+    }
+
+    fn write_poly_data(&mut self, data: &PolyData) -> Result<(), VTKparseError> {
+        self.write_points(&data.points)?;
+        for (keyword, cells) in &[
+            ("VERTICES", &data.vertices),
+            ("LINES", &data.lines),
+            ("POLYGONS", &data.polygons),
+            ("TRIANGLE_STRIPS", &data.triangle_strips),
+        ] {
+            if !cells.is_empty() {
+                self.write_cells(keyword, cells)?;
+            }
+        }
+        let cells = data.cells().map(Cells::len).sum();
+        self.write_attributes(&data.attributes, data.points.len(), cells)
+    }
+
+    fn write_unstructured_grid(&mut self, data: &UnstructuredGrid) -> Result<(), VTKparseError> {
+        self.write_points(&data.points)?;
+        self.write_cells("CELLS", &data.cells)?;
+        writeln!(self.inner, "CELL_TYPES {}", data.cell_types.len())?;
+        let ids = data.cell_types.iter().map(|x| x.id().into()).collect();
+        self.write_buffer(&Buffer::I32(ids))?;
+        self.write_attributes(&data.attributes, data.points.len(), data.cells.len())
+    }
+
+    fn write_points(&mut self, points: &[[f64; 3]]) -> Result<(), VTKparseError> {
+        writeln!(self.inner, "POINTS {} double", points.len())?;
+        self.write_buffer(&Buffer::F64(points.iter().flatten().copied().collect()))
+    }
+
+    /// Writes every cell as its number of points followed by the indices
+    fn write_cells(&mut self, keyword: &str, cells: &Cells) -> Result<(), VTKparseError> {
+        let mut values = Vec::with_capacity(cells.len() + cells.connectivity.len());
+        for cell in cells.iter() {
+            values.push(cell.len() as i32);
+            values.extend(cell.iter().map(|&i| i as i32));
+        }
+        writeln!(self.inner, "{} {} {}", keyword, cells.len(), values.len())?;
+        self.write_buffer(&Buffer::I32(values))
+    }
+
+    fn write_attributes(
+        &mut self,
+        attributes: &Attributes,
+        points: usize,
+        cells: usize,
+    ) -> Result<(), VTKparseError> {
+        // Lookup tables go in the first section, as they must follow
+        // POINT_DATA or CELL_DATA, which is written for them if need be
+        let mut lookup_tables = !attributes.lookup_tables.is_empty();
+        for (keyword, arrays, len) in &[
+            ("POINT_DATA", &attributes.point, points),
+            ("CELL_DATA", &attributes.cell, cells),
+        ] {
+            if arrays.is_empty() && !lookup_tables {
+                continue;
+            }
+            writeln!(self.inner, "{} {}", keyword, len)?;
+
+            let (fields, arrays): (Vec<&DataArray>, Vec<&DataArray>) = arrays
+                .iter()
+                .partition(|x| x.kind == DatasetAttributes::FieldData);
+            for array in arrays {
+                self.write_array(array)?;
+            }
+            if !fields.is_empty() {
+                writeln!(self.inner, "FIELD FieldData {}", fields.len())?;
+                for array in fields {
+                    self.write_field_array(array)?;
+                }
+            }
+
+            if lookup_tables {
+                for table in &attributes.lookup_tables {
+                    writeln!(
+                        self.inner,
+                        "LOOKUP_TABLE {} {}",
+                        table.name,
+                        table.colours.len()
+                    )?;
+                    let colours = table.colours.iter().flatten().copied().collect();
+                    self.write_colours(&Buffer::F32(colours))?;
+                }
+                lookup_tables = false;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_array(&mut self, array: &DataArray) -> Result<(), VTKparseError> {
+        let name = &array.name;
+        let datatype = array.data.scalar_type().name();
+        match array.kind {
+            DatasetAttributes::Scalars => {
+                writeln!(
+                    self.inner,
+                    "SCALARS {} {} {}",
+                    name, datatype, array.numcomp
+                )?;
+                writeln!(
+                    self.inner,
+                    "LOOKUP_TABLE {}",
+                    array.lookup_table.as_deref().unwrap_or("default")
+                )?;
+            }
+            DatasetAttributes::ColorScalars => {
+                writeln!(self.inner, "COLOR_SCALARS {} {}", name, array.numcomp)?;
+                return self.write_colours(&array.data);
+            }
+            DatasetAttributes::Vectors => writeln!(self.inner, "VECTORS {} {}", name, datatype)?,
+            DatasetAttributes::Normals => writeln!(self.inner, "NORMALS {} {}", name, datatype)?,
+            DatasetAttributes::Tensors => writeln!(self.inner, "TENSORS {} {}", name, datatype)?,
+            DatasetAttributes::TextureCoordinates => writeln!(
+                self.inner,
+                "TEXTURE_COORDINATES {} {} {}",
+                name, array.numcomp, datatype
+            )?,
+            DatasetAttributes::LookupTable | DatasetAttributes::FieldData => {
+                return Err(VTKparseError::NotImplemented(format!(
+                    "Writing {:?} array {}",
+                    array.kind, name
+                )))
+            }
+        }
+        self.write_buffer(&array.data)
+    }
+
+    fn write_field(&mut self, field: &FieldData) -> Result<(), VTKparseError> {
+        writeln!(self.inner, "FIELD {} {}", field.name, field.arrays.len())?;
+        for array in &field.arrays {
+            self.write_field_array(array)?;
+        }
+        Ok(())
+    }
+
+    fn write_field_array(&mut self, array: &DataArray) -> Result<(), VTKparseError> {
+        writeln!(
+            self.inner,
+            "{} {} {} {}",
+            array.name,
+            array.numcomp,
+            array.len(),
+            array.data.scalar_type().name()
+        )?;
+        self.write_buffer(&array.data)
+    }
+
+    /// Colours in [0, 1], stored as unsigned chars in binary files
+    fn write_colours(&mut self, colours: &Buffer) -> Result<(), VTKparseError> {
+        if self.binary {
+            let bytes = colours
+                .to_f64()
+                .into_iter()
+                .map(|x| (x.clamp(0.0, 1.0) * 255.0).round() as u8)
+                .collect();
+            self.write_buffer(&Buffer::U8(bytes))
+        } else {
+            let colours = colours.to_f64().into_iter().map(|x| x as f32).collect();
+            self.write_buffer(&Buffer::F32(colours))
+        }
+    }
+
+    fn write_buffer(&mut self, buffer: &Buffer) -> Result<(), VTKparseError> {
+        buffer::write(&mut self.inner, self.binary, buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{read_file, read_from, CellType, LookupTable};
+
+    /// Writes `data` in both formats and reads it back
+    fn round_trip(data: &Data) -> Vec<Data> {
+        [false, true]
+            .iter()
+            .map(|&binary| {
+                let mut writer = Writer::new(Vec::new(), binary);
+                writer.write(data).unwrap();
+
+                let read = read_from(&writer.into_inner()[..]).unwrap();
+                assert_eq!(read.header.binary, binary);
+                assert_eq!(read.header.header.trim(), data.header.header.trim());
+                read
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tooth() {
+        let data = read_file("../data/tooth.vtk").unwrap();
+        let points = data.structured_points().unwrap();
+
+        for read in round_trip(&data) {
+            let read = read.structured_points().unwrap();
+            assert_eq!(read.dims, points.dims);
+            assert_eq!(read.origin, points.origin);
+            assert_eq!(read.spacing, points.spacing);
+            assert_eq!(read.scalars().unwrap().data, points.scalars().unwrap().data);
+        }
+    }
+
+    #[test]
+    fn attributes() {
+        let data = read_from(
+            "# vtk DataFile Version 3.0
+attributes
+ASCII
+DATASET STRUCTURED_POINTS
+FIELD FieldData 1
+TIME 1 1 double
+2.5
+DIMENSIONS 2 1 1
+ORIGIN 0 0 0
+SPACING 1 1 1
+POINT_DATA 2
+SCALARS a short 1
+LOOKUP_TABLE palette
+-1 1
+COLOR_SCALARS c 3
+1 0 0 0 1 0
+VECTORS v double
+1 2 3 4 5 6
+FIELD FieldData 1
+flags 1 2 bit
+1 0
+LOOKUP_TABLE palette 2
+0 0 0 1
+1 1 1 1
+CELL_DATA 1
+TEXTURE_COORDINATES uv 2 float
+0.25 0.5
+"
+            .as_bytes(),
+        )
+        .unwrap();
+
+        for read in round_trip(&data) {
+            let time = read.field_data.as_ref().unwrap().array("TIME").unwrap();
+            assert_eq!(time.data, Buffer::F64(vec![2.5]));
+
+            let attributes = &read.structured_points().unwrap().attributes;
+            for array in &data.structured_points().unwrap().attributes.point {
+                let read = attributes.point_array(&array.name).unwrap();
+                assert_eq!(read.kind, array.kind);
+                assert_eq!(read.data, array.data);
+                assert_eq!(read.lookup_table, array.lookup_table);
+            }
+            assert_eq!(
+                attributes.cell_array("uv").unwrap().data,
+                Buffer::F32(vec![0.25, 0.5])
+            );
+            assert_eq!(
+                attributes.lookup_tables[0].colours,
+                vec![[0.0, 0.0, 0.0, 1.0], [1.0; 4]]
+            );
+        }
+    }
+
+    #[test]
+    fn cells() {
+        let data = read_from(
+            "# vtk DataFile Version 3.0
+cells
+ASCII
+DATASET UNSTRUCTURED_GRID
+POINTS 5 float
+0 0 0  1 0 0  0 1 0  0 0 1  0.5 0.5 2
+CELLS 2 9
+4 0 1 2 3
+3 1 2 4
+CELL_TYPES 2
+10
+5
+CELL_DATA 2
+SCALARS id int
+LOOKUP_TABLE default
+7 8
+"
+            .as_bytes(),
+        )
+        .unwrap();
+
+        for read in round_trip(&data) {
+            let read = read.unstructured_grid().unwrap();
+            let grid = data.unstructured_grid().unwrap();
+            assert_eq!(read.points, grid.points);
+            assert_eq!(read.cells, grid.cells);
+            assert_eq!(read.cell_types, vec![CellType::Tetra, CellType::Triangle]);
+            assert_eq!(read.attributes.cell[0].data, Buffer::I32(vec![7, 8]));
+        }
+
+        let polydata = Data {
+            dataset: Datatype::PolyData(PolyData {
+                points: grid_points(),
+                vertices: Cells::default(),
+                lines: Cells {
+                    offsets: vec![0, 2],
+                    connectivity: vec![0, 1],
+                },
+                polygons: Cells {
+                    offsets: vec![0, 3],
+                    connectivity: vec![0, 1, 2],
+                },
+                triangle_strips: Cells::default(),
+                attributes: Attributes {
+                    lookup_tables: vec![LookupTable {
+                        name: "red".to_string(),
+                        colours: vec![[1.0, 0.0, 0.0, 1.0]],
+                    }],
+                    ..Attributes::default()
+                },
+            }),
+            ..data
+        };
+        for read in round_trip(&polydata) {
+            let read = read.poly_data().unwrap();
+            assert_eq!(read.points, grid_points());
+            assert!(read.vertices.is_empty());
+            assert_eq!(read.lines.get(0), Some(&[0, 1][..]));
+            assert_eq!(read.triangles(), vec![[0, 1, 2]]);
+            // Written after the counts of the dataset, without any arrays
+            assert_eq!(
+                read.attributes.lookup_tables[0].colours,
+                vec![[1.0, 0.0, 0.0, 1.0]]
+            );
+        }
+    }
+
+    fn grid_points() -> Vec<[f64; 3]> {
+        vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+    }
+}
@@ -44,7 +44,7 @@ pub const INDICES: [u8; 3 * 12] = [
     6, 4, 6, 5,
 ];
 
-pub const VERT_SHADER: &'static str = r#"
+pub const VERT_SHADER: &str = r#"
 #version 140
 
 in vec3 pos;
@@ -62,7 +62,7 @@ void main() {
 }
 "#;
 
-pub const FRAG_SHADER: &'static str = r#"
+pub const FRAG_SHADER: &str = r#"
 #version 140
 
 in vec3 v_pos;
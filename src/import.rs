@@ -0,0 +1,210 @@
+use std::path::{Path, PathBuf};
+use vtk_parser::{RawLayout, ScalarType, StructuredPoints, VTKparseError};
+
+pub const USAGE: &str = "Usage: project [FILE [OPTIONS]]...
+
+Shows the files of the data folder, and the files given. The options after
+a file give the layout of a file without a header, and take precedence over
+its sidecar FILE.layout:
+    --dims X Y Z        Number of points along each axis
+    --type TYPE         Type of the values, e.g. unsigned_short or float
+    --components N      Number of values of every point, 1 by default
+    --endian ENDIAN     little (default) or big
+    --skip BYTES        Size of a header to skip at the start of the file
+    --spacing X Y Z     Distance between points, 1 1 1 by default
+    --origin X Y Z      Position of the first point, 0 0 0 by default
+    -h, --help          Print this message";
+
+/// A file given on the command line, with the layout options which followed
+/// it as lines of a sidecar file
+pub struct Argument {
+    pub path: PathBuf,
+    pub layout: String,
+}
+
+pub fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Vec<Argument>, String> {
+    let mut files: Vec<Argument> = Vec::new();
+    while let Some(arg) = args.next() {
+        let (key, count) = match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            "--dims" => ("dims", 3),
+            "--type" => ("type", 1),
+            "--components" => ("components", 1),
+            "--endian" => ("endian", 1),
+            "--skip" => ("skip", 1),
+            "--spacing" => ("spacing", 3),
+            "--origin" => ("origin", 3),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ => {
+                files.push(Argument {
+                    path: PathBuf::from(arg),
+                    layout: String::new(),
+                });
+                continue;
+            }
+        };
+        let file = files
+            .last_mut()
+            .ok_or_else(|| format!("Option {} has to follow a file", arg))?;
+        let values: Vec<String> = (&mut args).take(count).collect();
+        if values.len() != count {
+            return Err(format!("Option {} needs {} values", arg, count));
+        }
+        file.layout += &format!("{} = {}\n", key, values.join(" "));
+    }
+    Ok(files)
+}
+
+/// Layout of a raw file from its sidecar, if there is one, and the options
+/// given on the command line
+pub fn layout(path: &Path, options: &str) -> Result<RawLayout, VTKparseError> {
+    let sidecar = RawLayout::sidecar(path);
+    let mut text = if sidecar.exists() {
+        std::fs::read_to_string(sidecar)?
+    } else {
+        String::new()
+    };
+    // Later keys replace earlier ones
+    text += options;
+    RawLayout::parse(&text)
+}
+
+/// Types which can be chosen in the dialog
+const TYPES: [ScalarType; 10] = [
+    ScalarType::UnsignedChar,
+    ScalarType::Char,
+    ScalarType::UnsignedShort,
+    ScalarType::Short,
+    ScalarType::UnsignedInt,
+    ScalarType::Int,
+    ScalarType::UnsignedLong,
+    ScalarType::Long,
+    ScalarType::Float,
+    ScalarType::Double,
+];
+
+pub enum Action {
+    Load,
+    Skip,
+}
+
+/// Asks for the layout of a file which could not be read
+pub struct RawDialog {
+    pub path: PathBuf,
+    file_len: u64,
+    dims: [i32; 3],
+    scalar_type: usize,
+    components: i32,
+    big_endian: bool,
+    skip: i32,
+    spacing: [f32; 3],
+    origin: [f32; 3],
+    /// Whether to write the layout to the sidecar of the file once loaded
+    pub save: bool,
+    /// Why the file could not be read, or the last layout failed
+    pub error: String,
+}
+
+impl RawDialog {
+    /// Starts from the sidecar of the file if it has one, or else from a
+    /// cube of bytes as large as the file
+    pub fn new(path: PathBuf, error: &VTKparseError) -> Self {
+        let file_len = std::fs::metadata(&path).map_or(0, |x| x.len());
+        let layout = RawLayout::read_sidecar(RawLayout::sidecar(&path)).unwrap_or_else(|_| {
+            let side = (file_len as f64).cbrt().floor().max(1.0) as u32;
+            RawLayout::new((side, side, side), ScalarType::UnsignedChar)
+        });
+        let (x, y, z) = layout.dims;
+        let (sx, sy, sz) = layout.spacing;
+        let (ox, oy, oz) = layout.origin;
+        Self {
+            path,
+            file_len,
+            dims: [x as i32, y as i32, z as i32],
+            scalar_type: TYPES
+                .iter()
+                .position(|&x| x == layout.scalar_type)
+                .unwrap_or(0),
+            components: layout.numcomp as i32,
+            big_endian: !layout.little_endian,
+            skip: layout.skip as i32,
+            spacing: [sx, sy, sz],
+            origin: [ox, oy, oz],
+            save: false,
+            error: error.to_string(),
+        }
+    }
+
+    pub fn layout(&self) -> RawLayout {
+        let [x, y, z] = self.dims.map(|n| n.max(1) as u32);
+        let mut layout = RawLayout::new((x, y, z), TYPES[self.scalar_type]);
+        layout.numcomp = self.components.max(1) as usize;
+        layout.little_endian = !self.big_endian;
+        layout.skip = self.skip.max(0) as u64;
+        layout.spacing = (self.spacing[0], self.spacing[1], self.spacing[2]);
+        layout.origin = (self.origin[0], self.origin[1], self.origin[2]);
+        layout
+    }
+
+    pub fn read(&self) -> Result<StructuredPoints, VTKparseError> {
+        vtk_parser::read_raw(&self.path, &self.layout())
+    }
+
+    pub fn show(&mut self, ui: &imgui::Ui) -> Option<Action> {
+        use imgui::im_str;
+        let mut action = None;
+        imgui::Window::new(im_str!("Import raw volume"))
+            .always_auto_resize(true)
+            .collapsible(false)
+            .build(ui, || {
+                ui.text(im_str!("{}", self.path.display()));
+                ui.text_colored([1.0, 0.4, 0.4, 1.0], &self.error);
+                ui.separator();
+
+                ui.input_int3(im_str!("Dimensions"), &mut self.dims).build();
+                let names: Vec<_> = TYPES.iter().map(|x| im_str!("{}", x.name())).collect();
+                let names: Vec<&imgui::ImStr> = names.iter().map(|x| x.as_ref()).collect();
+                imgui::ComboBox::new(im_str!("Type")).build_simple_string(
+                    ui,
+                    &mut self.scalar_type,
+                    &names,
+                );
+                ui.input_int(im_str!("Components"), &mut self.components)
+                    .build();
+                ui.checkbox(im_str!("Big endian"), &mut self.big_endian);
+                ui.input_int(im_str!("Header bytes to skip"), &mut self.skip)
+                    .build();
+                ui.input_float3(im_str!("Spacing"), &mut self.spacing)
+                    .build();
+                ui.input_float3(im_str!("Origin"), &mut self.origin).build();
+
+                let layout = self.layout();
+                match layout.data_len() {
+                    Ok(len) => ui.text(im_str!(
+                        "File size: {} bytes, layout needs {} bytes",
+                        self.file_len,
+                        layout.skip.saturating_add(len as u64)
+                    )),
+                    Err(_) => ui.text(im_str!(
+                        "File size: {} bytes, layout is too large",
+                        self.file_len
+                    )),
+                }
+                ui.checkbox(im_str!("Save layout next to the file"), &mut self.save);
+                if ui.button(im_str!("Load"), [80.0, 0.0]) {
+                    match layout.check_len(self.file_len) {
+                        Ok(()) => action = Some(Action::Load),
+                        Err(err) => self.error = err.to_string(),
+                    }
+                }
+                ui.same_line(0.0);
+                if ui.button(im_str!("Skip"), [80.0, 0.0]) {
+                    action = Some(Action::Skip);
+                }
+            });
+        action
+    }
+}
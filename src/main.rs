@@ -1,15 +1,28 @@
+// glium's implement_vertex! expands to a transmute clippy does not like
+#![allow(clippy::useless_transmute)]
+
 use cgmath::Matrix4;
-use glium::{
-    texture::{Texture2d, Texture3d},
-    uniform, IndexBuffer, Program, Surface, VertexBuffer,
-};
+use glium::{texture::Texture2d, uniform, IndexBuffer, Program, Surface, VertexBuffer};
 use imgui_glium_renderer::Renderer;
+use std::collections::VecDeque;
 
 mod cube;
+mod import;
 mod raycast;
+mod series;
 mod support;
+mod surface;
+mod volume;
 
 fn main() {
+    let arguments = match import::parse_args(std::env::args().skip(1)) {
+        Ok(arguments) => arguments,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, import::USAGE);
+            std::process::exit(2);
+        }
+    };
+
     let events_loop = glium::glutin::event_loop::EventLoop::new();
     let builder = glium::glutin::window::WindowBuilder::new();
     let context = glium::glutin::ContextBuilder::new()
@@ -47,6 +60,8 @@ fn main() {
     struct Textures {
         backface: Texture2d,
         frontface: Texture2d,
+        surface: Texture2d,
+        surface_position: Texture2d,
         noise: Texture2d,
     }
 
@@ -67,6 +82,22 @@ fn main() {
             1024,
         )
         .unwrap(),
+        surface: Texture2d::empty_with_format(
+            &display,
+            glium::texture::UncompressedFloatFormat::F32F32F32F32,
+            glium::texture::MipmapsOption::NoMipmap,
+            1024,
+            1024,
+        )
+        .unwrap(),
+        surface_position: Texture2d::empty_with_format(
+            &display,
+            glium::texture::UncompressedFloatFormat::F32F32F32F32,
+            glium::texture::MipmapsOption::NoMipmap,
+            1024,
+            1024,
+        )
+        .unwrap(),
         noise: {
             let random_bytes = include_bytes!("random.bin").to_vec();
 
@@ -87,6 +118,7 @@ fn main() {
     struct DepthBuffers {
         frontface: glium::framebuffer::DepthRenderBuffer,
         backface: glium::framebuffer::DepthRenderBuffer,
+        surface: glium::framebuffer::DepthRenderBuffer,
     }
 
     let depth_buffers = DepthBuffers {
@@ -104,42 +136,120 @@ fn main() {
             1024,
         )
         .unwrap(),
+        surface: glium::framebuffer::DepthRenderBuffer::new(
+            &display,
+            glium::texture::DepthFormat::F32,
+            1024,
+            1024,
+        )
+        .unwrap(),
     };
 
-    let (volume_tex, names) = {
-        let files =
-            std::fs::read_dir("data").expect("Folder named data not found in this directory");
-
-        let mut volume_tex = Vec::new();
+    // Files which could not be read, to ask for their layout as raw files
+    let mut unrecognised = VecDeque::new();
+
+    let (mut volumes, mut names, surfaces) = {
+        let paths = match std::fs::read_dir("data") {
+            Ok(files) => files.map(|file| file.unwrap().path()).collect(),
+            // Files given on the command line are shown without a data folder
+            Err(_) if !arguments.is_empty() => Vec::new(),
+            Err(err) => panic!("Folder named data not found in this directory: {}", err),
+        };
+        let mut all = series::group(paths);
+        all.extend(
+            arguments
+                .iter()
+                .map(|x| series::Series::single(x.path.clone())),
+        );
+
+        let mut volumes = Vec::new();
         let mut names = Vec::new();
+        let mut surfaces = Vec::new();
+
+        for series in all {
+            let first = volumes.len();
+
+            for (frame, (time, path)) in series.frames.iter().enumerate() {
+                let layout = arguments
+                    .iter()
+                    .find(|x| &x.path == path && !x.layout.is_empty());
+                let dataset = match layout {
+                    Some(argument) => import::layout(path, &argument.layout)
+                        .and_then(|layout| vtk_parser::read_raw(path, &layout))
+                        .map(volume::Dataset::Volume),
+                    None => volume::load(path),
+                };
+                let timesteps = match dataset {
+                    Ok(volume::Dataset::Volume(x)) => vec![(*time, x)],
+                    // The timesteps of a file replace the time of the frame
+                    Ok(volume::Dataset::Series(x)) => x,
+                    Ok(volume::Dataset::Surface(x)) => {
+                        // Surfaces are not animated, only the first is shown
+                        if frame == 0 {
+                            surfaces.push(surface::Surface::new(&display, &x).unwrap());
+                        }
+                        continue;
+                    }
+                    Ok(volume::Dataset::Streamed(probe)) => {
+                        for array in probe.point_scalars() {
+                            // Only the metadata was probed, so the data may
+                            // still turn out to be truncated
+                            let texture =
+                                match volume::upload_streamed(&display, path, &probe, array) {
+                                    Ok(texture) => texture,
+                                    Err(err) => {
+                                        eprintln!("{}: {}", path.display(), err);
+                                        break;
+                                    }
+                                };
+                            // Lookup tables are not read from streamed files
+                            let model =
+                                volume::model_matrix(probe.dims, probe.origin, probe.spacing);
+                            volume::add(
+                                &mut volumes,
+                                first,
+                                &array.name,
+                                *time,
+                                texture,
+                                None,
+                                model,
+                            );
+                        }
+                        continue;
+                    }
+                    // Files with a layout which does not fit may be fixed
+                    Err(err) if layout.is_some() || volume::unrecognised(path, &err) => {
+                        unrecognised.push_back(import::RawDialog::new(path.clone(), &err));
+                        continue;
+                    }
+                    Err(err) => {
+                        eprintln!("{}: {}", path.display(), err);
+                        continue;
+                    }
+                };
 
-        for file in files {
-            let file = file.unwrap();
-
-            names.push(imgui::ImString::new(
-                file.file_name().into_string().unwrap(),
-            ));
-
-            let path = file.path();
+                for (time, data) in timesteps {
+                    if let Err(err) = volume::add_points(&display, &mut volumes, first, time, &data)
+                    {
+                        eprintln!("{}: {}", path.display(), err);
+                        break;
+                    }
+                }
+            }
+            name_volumes(&mut names, &series.name, &volumes[first..]);
+        }
+        (volumes, names, surfaces)
+    };
+    let mut dialog = unrecognised.pop_front();
 
-            let data = vtk_parser::read_file(path).unwrap();
-            let data = data.structured_points().unwrap();
+    let surface_prog =
+        Program::from_source(&display, surface::VERT_SHADER, surface::FRAG_SHADER, None).unwrap();
 
-            let image = glium::texture::RawImage3d {
-                data: std::borrow::Cow::Owned(data.data.clone()),
-                width: data.dims.0,
-                height: data.dims.1,
-                depth: data.dims.2,
-                format: glium::texture::ClientFormat::U8,
-            };
+    let default_lookup_tex =
+        glium::texture::Texture1d::new(&display, vec![(1.0f32, 1.0f32, 1.0f32, 1.0f32)]).unwrap();
 
-            volume_tex.push(
-                Texture3d::with_mipmaps(&display, image, glium::texture::MipmapsOption::NoMipmap)
-                    .unwrap(),
-            );
-        }
-        (volume_tex, names)
-    };
+    // Raycast when no volume is selected, which leaves only the surfaces
+    let empty_volume = glium::texture::Texture3d::new(&display, vec![vec![vec![0u8]]]).unwrap();
 
     let mut state = support::State::default();
 
@@ -156,7 +266,7 @@ fn main() {
         use imgui_winit_support::HiDpiMode;
         let gl_window = display.gl_window();
         let window = gl_window.window();
-        platform.attach_window(imgui.io_mut(), &window, HiDpiMode::Rounded);
+        platform.attach_window(imgui.io_mut(), window, HiDpiMode::Rounded);
     }
     let mut renderer = Renderer::init(&mut imgui, &display).unwrap();
 
@@ -173,7 +283,7 @@ fn main() {
             Event::MainEventsCleared => {
                 let gl_window = display.gl_window();
                 platform
-                    .prepare_frame(imgui.io_mut(), &gl_window.window())
+                    .prepare_frame(imgui.io_mut(), gl_window.window())
                     .unwrap();
                 gl_window.window().request_redraw();
             }
@@ -244,6 +354,66 @@ fn main() {
                     )
                     .unwrap();
 
+                let light = [
+                    state.light[0].sin() * state.light[1].cos(),
+                    state.light[0].sin() * state.light[0].sin(),
+                    state.light[0].cos(),
+                ];
+
+                // Surfaces are drawn to a texture, which the raycaster uses to
+                // stop rays hitting the surface and for the colour behind the volume
+                let mut surface_buffer = glium::framebuffer::MultiOutputFrameBuffer::with_depth_buffer(
+                    &display,
+                    vec![
+                        ("colour", &textures.surface),
+                        ("position", &textures.surface_position),
+                    ],
+                    &depth_buffers.surface,
+                )
+                .unwrap();
+                surface_buffer.clear_color_and_depth((0.0, 0.0, 0.0, 0.0), 1.0);
+
+                let selected = volumes.get(state.selection);
+                // Without a volume, surfaces are drawn in their own coordinates
+                let model = selected.map_or(IDENTITY, |x| x.model);
+
+                if state.show_surfaces {
+                    let params = glium::DrawParameters {
+                        depth: glium::draw_parameters::Depth {
+                            test: glium::draw_parameters::DepthTest::IfLess,
+                            write: true,
+                            ..Default::default()
+                        },
+                        point_size: Some(4.0),
+                        ..Default::default()
+                    };
+                    for surface in &surfaces {
+                        for (indices, lit) in [
+                            (&surface.triangles, true),
+                            (&surface.lines, false),
+                            (&surface.points, false),
+                        ]
+                        .iter()
+                        {
+                            surface_buffer
+                                .draw(
+                                    &surface.vertices,
+                                    *indices,
+                                    &surface_prog,
+                                    &uniform! {
+                                        u_mvp: vp,
+                                        u_model: model,
+                                        u_colour: state.surface_colour,
+                                        u_L: light,
+                                        u_lit: *lit,
+                                    },
+                                    &params,
+                                )
+                                .unwrap();
+                        }
+                    }
+                }
+
                 let mut target = display.draw();
                 target.clear_color_and_depth(
                     (
@@ -263,12 +433,23 @@ fn main() {
                     ..Default::default()
                 };
 
+                let (frame, lookup) = match selected {
+                    Some(volume) => (
+                        &volume.frames[state.frame.min(volume.frames.len() - 1)],
+                        volume.lookup.as_ref(),
+                    ),
+                    None => (&empty_volume, None),
+                };
                 let uniforms = uniform! {
                     u_back : &textures.backface,
                     u_front: &textures.frontface,
-                    u_volume: volume_tex[state.selection].sampled().wrap_function(glium::uniforms::SamplerWrapFunction::Clamp).magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear),
+                    u_volume: frame.sampled().wrap_function(glium::uniforms::SamplerWrapFunction::Clamp).magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear),
                     u_noise: &textures.noise,
                     u_use_noise: state.noise,
+                    u_surface: &textures.surface,
+                    u_surface_position: &textures.surface_position,
+                    u_lut: lookup.unwrap_or(&default_lookup_tex).sampled().wrap_function(glium::uniforms::SamplerWrapFunction::Clamp).magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear),
+                    u_use_lut: state.lookup_table && lookup.is_some(),
                     u_gamma: state.gamma,
 
                     u_steps: state.steps,
@@ -286,7 +467,7 @@ fn main() {
                     u_specular: state.spe_colour,
                     u_spe_str: state.spe_str,
                     u_alpha: state.alpha,
-                    u_L: [state.light[0].sin()*state.light[1].cos(), state.light[0].sin()*state.light[0].sin(), state.light[0].cos()]
+                    u_L: light
                 };
 
                 target
@@ -296,16 +477,55 @@ fn main() {
                 // Dear ImGui related
                 let frame_rate = imgui.io().framerate;
                 state.frame_rate = frame_rate;
+                state.advance(
+                    imgui.io().delta_time,
+                    selected.map_or(0, |x| x.frames.len()),
+                );
                 let ui = imgui.frame();
                 let gl_window = display.gl_window();
 
-                support::gui(&ui, &mut state, &mut camera, &names);
+                let times = selected.map_or(&[][..], |x| &x.times);
+                support::gui(&ui, &mut state, &mut camera, &names, times);
+                let action = dialog.as_mut().and_then(|x| x.show(&ui));
 
                 platform.prepare_render(&ui, gl_window.window());
                 let draw_data = ui.render();
                 renderer.render(&mut target, draw_data).unwrap();
 
                 target.finish().unwrap();
+
+                match action {
+                    Some(import::Action::Load) => {
+                        let raw = dialog.as_mut().unwrap();
+                        let first = volumes.len();
+                        let added = raw.read().map(|data| {
+                            volume::add_points(&display, &mut volumes, first, 0.0, &data)
+                        });
+                        match added {
+                            Ok(Err(err)) => {
+                                volumes.truncate(first);
+                                raw.error = err.to_string();
+                            }
+                            Ok(Ok(())) => {
+                                let name = series::file_name(&raw.path);
+                                name_volumes(&mut names, &name, &volumes[first..]);
+                                state.selection = first;
+                                if raw.save {
+                                    let sidecar = vtk_parser::RawLayout::sidecar(&raw.path);
+                                    if let Err(err) =
+                                        std::fs::write(sidecar, raw.layout().to_string())
+                                    {
+                                        eprintln!("Could not save the layout: {}", err);
+                                    }
+                                }
+                                dialog = unrecognised.pop_front();
+                            }
+                            Err(err) => raw.error = err.to_string(),
+                        }
+                    }
+                    Some(import::Action::Skip) => dialog = unrecognised.pop_front(),
+                    None => {}
+                }
             }
             Event::WindowEvent {
                 event: glium::glutin::event::WindowEvent::CloseRequested,
@@ -321,3 +541,22 @@ fn main() {
         }
     })
 }
+
+const IDENTITY: [[f32; 4]; 4] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+/// Names the volumes of a series in the list of datasets, after the array
+/// if the series has more than one
+fn name_volumes(names: &mut Vec<imgui::ImString>, series: &str, volumes: &[volume::Volume]) {
+    for volume in volumes {
+        names.push(imgui::ImString::new(if volumes.len() == 1 {
+            series.to_string()
+        } else {
+            format!("{}: {}", series, volume.array)
+        }));
+    }
+}
@@ -16,7 +16,7 @@ pub const VERTICES: [QuadVertex; 4] = [
 
 pub const INDICES: [u8; 6] = [0, 1, 2, 1, 3, 2];
 
-pub const VERT_SHADER: &'static str = r#"
+pub const VERT_SHADER: &str = r#"
     #version 140
 
     in vec2 pos;
@@ -29,7 +29,7 @@ pub const VERT_SHADER: &'static str = r#"
     }
     "#;
 
-pub const FRAG_SHADER: &'static str = r#"
+pub const FRAG_SHADER: &str = r#"
     #version 140
 
     in vec2 v_pos;
@@ -41,6 +41,12 @@ pub const FRAG_SHADER: &'static str = r#"
     uniform sampler2D u_noise;
     uniform bool u_use_noise;
 
+    uniform sampler1D u_lut;
+    uniform bool u_use_lut;
+
+    uniform sampler2D u_surface;
+    uniform sampler2D u_surface_position;
+
     uniform int u_steps;
     uniform float u_dx;
     uniform float u_gamma;
@@ -73,18 +79,27 @@ pub const FRAG_SHADER: &'static str = r#"
     }
 
     void main() {
+        vec4 surface = texture(u_surface, v_pos);
+
         if (texture(u_front, v_pos).a == 0) {
-            colour = vec4(0.0);
+            colour = surface;
             return;
         }
 
         vec3 start = texture(u_front, v_pos).xyz;
         vec3 end   = texture(u_back,  v_pos).xyz;
 
+        vec3 direction = normalize(end - start);
 
-        int n = int (floor(distance(end, start) / u_dx)) - 2;
+        // Opaque surfaces inside the volume stop the ray
+        if (surface.a > 0.0) {
+            float depth = dot(texture(u_surface_position, v_pos).xyz - start, direction);
+            if (depth < distance(end, start)) {
+                end = start + max(depth, 0.0)*direction;
+            }
+        }
 
-        vec3 direction = normalize(end - start);
+        int n = int (floor(distance(end, start) / u_dx)) - 2;
 
         vec3 ray = start;
         if (u_use_noise) {
@@ -103,9 +118,13 @@ pub const FRAG_SHADER: &'static str = r#"
             }
 
             if (max_found > 0.0) {
-                colour = gamma_correct(vec4(max_found*u_colour, 1.0), u_gamma);
+                vec3 mip_colour = u_use_lut ? texture(u_lut, max_found).rgb : max_found*u_colour;
+                vec4 mip = gamma_correct(vec4(mip_colour, 1.0), u_gamma);
+                // The volume in front of a surface is laid over it, with the
+                // intensity as opacity
+                colour = surface.a > 0.0 ? vec4(mix(surface.rgb, mip.rgb, max_found), 1.0) : mip;
             } else {
-                colour = vec4(0.0);
+                colour = surface;
             }
 
             return;
@@ -124,8 +143,11 @@ pub const FRAG_SHADER: &'static str = r#"
                     gradient = normalize(gradient);
 
 
-                    vec3 ambient = u_amb_str*u_ambient;
-                    vec3 diffuse = u_dif_str*u_diffuse*max(dot(gradient, u_L), 0.0);
+                    vec3 surface_ambient = u_use_lut ? texture(u_lut, u_iso).rgb : u_ambient;
+                    vec3 surface_diffuse = u_use_lut ? texture(u_lut, u_iso).rgb : u_diffuse;
+
+                    vec3 ambient = u_amb_str*surface_ambient;
+                    vec3 diffuse = u_dif_str*surface_diffuse*max(dot(gradient, u_L), 0.0);
 
                     vec3 H = normalize(u_L + direction);
                     vec3 specular = u_spe_str*u_specular*pow(dot(gradient, H), u_alpha) * (u_alpha + 8.0) / 8.0;
@@ -134,7 +156,7 @@ pub const FRAG_SHADER: &'static str = r#"
                     return;
                 }
             }
-        colour = vec4(0.0);
+        colour = surface;
         }
     }
 "#;
@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Files shown as one dataset, with the time of each frame
+pub struct Series {
+    pub name: String,
+    pub frames: Vec<(f64, PathBuf)>,
+}
+
+impl Series {
+    /// A series of one file, named after it
+    pub fn single(path: PathBuf) -> Self {
+        Self {
+            name: file_name(&path),
+            frames: vec![(0.0, path)],
+        }
+    }
+}
+
+/// Groups the files of the data folder into time series
+///
+/// Each `.pvd` collection becomes a series, using the first part of every
+/// timestep, and numbered files such as `name_0000.vtk, name_0001.vtk` are
+/// grouped with their number as the time. All other files are series of a
+/// single frame, except the data files of detached NRRD, MetaImage and
+/// NIfTI headers and the `.layout` sidecars of raw files. A four
+/// dimensional NIfTI file is a series by itself, and so are folders and the
+/// DICOM slices of the data folder, as volumes assembled from the slices
+///
+/// Collections and headers which cannot be read are left as series of a
+/// single frame, so that loading them reports the error
+pub fn group(paths: Vec<PathBuf>) -> Vec<Series> {
+    let key = |path: &Path| std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+    let mut series = Vec::new();
+    let mut collected = Vec::new();
+    for path in paths.iter().filter(|x| extension(x) == "pvd") {
+        let entries = match vtk_parser::read_collection(path) {
+            Ok(entries) => entries,
+            Err(_) => {
+                series.push(Series::single(path.clone()));
+                continue;
+            }
+        };
+        let mut frames: Vec<(f64, PathBuf)> = Vec::new();
+        for entry in entries {
+            collected.push(key(&entry.file));
+            if frames.last().map(|x| x.0) != Some(entry.timestep) {
+                frames.push((entry.timestep, entry.file));
+            }
+        }
+        series.push(Series {
+            name: file_name(path),
+            frames,
+        });
+    }
+    // The data of detached NRRD, MetaImage and NIfTI headers is read
+    // through the header
+    for path in &paths {
+        let files = match extension(path).as_str() {
+            "nhdr" => vtk_parser::nrrd_data_files(path),
+            "mhd" => vtk_parser::mhd_data_files(path),
+            "hdr" => Ok(vec![path.with_extension("img")]),
+            _ => continue,
+        };
+        let files = files.unwrap_or_default();
+        collected.extend(files.iter().map(|x| key(x)));
+    }
+
+    // Folders hold the slices of a DICOM series, and the slices next to the
+    // other files are read as one more series
+    let mut slices = None;
+    let mut numbered: BTreeMap<(String, String), Vec<(u64, PathBuf)>> = BTreeMap::new();
+    for path in paths {
+        // Sidecars are read together with their raw file
+        let read = matches!(extension(&path).as_str(), "pvd" | "layout");
+        if read || collected.contains(&key(&path)) {
+            continue;
+        }
+        if path.is_dir() {
+            series.push(Series::single(path));
+            continue;
+        }
+        if vtk_parser::is_dicom(&path) {
+            slices = path.parent().map(Path::to_path_buf);
+            continue;
+        }
+        let stem = path
+            .file_stem()
+            .map_or_else(String::new, |x| x.to_string_lossy().into_owned());
+        let prefix = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+        match stem[prefix.len()..].parse() {
+            Ok(number) => numbered
+                .entry((prefix.to_string(), extension(&path)))
+                .or_default()
+                .push((number, path)),
+            Err(_) => series.push(Series::single(path)),
+        }
+    }
+
+    for ((prefix, extension), mut files) in numbered {
+        if files.len() == 1 {
+            let (_, path) = files.pop().unwrap();
+            series.push(Series::single(path));
+            continue;
+        }
+        files.sort();
+        series.push(Series {
+            name: if extension.is_empty() {
+                format!("{}*", prefix)
+            } else {
+                format!("{}*.{}", prefix, extension)
+            },
+            frames: files.into_iter().map(|(n, x)| (n as f64, x)).collect(),
+        });
+    }
+
+    if let Some(folder) = slices {
+        series.push(Series {
+            name: "DICOM".to_string(),
+            frames: vec![(0.0, folder)],
+        });
+    }
+
+    series.sort_by(|a, b| a.name.cmp(&b.name));
+    series
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map_or_else(String::new, |x| x.to_string_lossy().to_lowercase())
+}
+
+pub fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map_or_else(String::new, |x| x.to_string_lossy().into_owned())
+}
@@ -137,6 +137,9 @@ pub struct State {
     pub background: [f32; 3],
     pub selection: usize,
     pub noise: bool,
+    pub lookup_table: bool,
+    pub show_surfaces: bool,
+    pub surface_colour: [f32; 3],
     pub gamma: f32,
     pub mip_or_iso: i32,
     pub mip_colour: [f32; 3],
@@ -152,6 +155,14 @@ pub struct State {
     pub grad_step: f32,
     pub perspective_selection: usize,
     pub frame_rate: f32,
+    /// Timestep of the selected dataset being shown
+    pub frame: usize,
+    pub playing: bool,
+    pub looping: bool,
+    /// Playback speed in timesteps per second
+    pub speed: f32,
+    /// Part of a timestep which has been played
+    pub frame_clock: f32,
 }
 
 impl Default for State {
@@ -162,6 +173,9 @@ impl Default for State {
             background: [0.0, 0.0, 0.0],
             selection: 0,
             noise: true,
+            lookup_table: true,
+            show_surfaces: true,
+            surface_colour: [0.8, 0.8, 0.2],
             gamma: 2.2,
             mip_or_iso: 0,
             mip_colour: [1.0, 1.0, 1.0],
@@ -177,28 +191,63 @@ impl Default for State {
             grad_step: 5.0 / 256.0,
             perspective_selection: 0,
             frame_rate: 0.0,
+            frame: 0,
+            playing: false,
+            looping: true,
+            speed: 5.0,
+            frame_clock: 0.0,
         }
     }
 }
 
-pub fn gui(ui: &imgui::Ui, state: &mut State, camera: &mut Camera, names: &[imgui::ImString]) {
+impl State {
+    /// Plays `dt` seconds of a dataset with `frames` timesteps
+    pub fn advance(&mut self, dt: f32, frames: usize) {
+        self.frame = self.frame.min(frames.saturating_sub(1));
+        if !self.playing || frames < 2 {
+            self.frame_clock = 0.0;
+            return;
+        }
+
+        self.frame_clock += dt * self.speed;
+        while self.frame_clock >= 1.0 {
+            self.frame_clock -= 1.0;
+            if self.frame + 1 < frames {
+                self.frame += 1;
+            } else if self.looping {
+                self.frame = 0;
+            } else {
+                self.playing = false;
+                self.frame_clock = 0.0;
+            }
+        }
+    }
+}
+
+pub fn gui(
+    ui: &imgui::Ui,
+    state: &mut State,
+    camera: &mut Camera,
+    names: &[imgui::ImString],
+    times: &[f64],
+) {
     use imgui::im_str;
     imgui::Window::new(im_str!("Graphics options"))
         .resizable(true)
         .collapsible(true)
         .movable(true)
         .size([300.0, 100.0], imgui::Condition::FirstUseEver)
-        .build(&ui, || {
+        .build(ui, || {
             imgui::Slider::new(im_str!("Maximum number of steps"))
                 .range(0..=400)
-                .build(&ui, &mut state.steps);
+                .build(ui, &mut state.steps);
             imgui::Slider::new(im_str!("Step size"))
                 .range(0.0..=0.05)
-                .build(&ui, &mut state.dx);
+                .build(ui, &mut state.dx);
             imgui::Slider::new(im_str!("Gamma factor"))
                 .range(0.4..=3.0)
-                .build(&ui, &mut state.gamma);
-            imgui::ColorEdit::new(im_str!("Background colour"), &mut state.background).build(&ui);
+                .build(ui, &mut state.gamma);
+            imgui::ColorEdit::new(im_str!("Background colour"), &mut state.background).build(ui);
             ui.text(im_str!("Projection:"));
             ui.same_line(0.0);
             ui.radio_button(im_str!("Perspective"), &mut state.perspective_selection, 0);
@@ -207,25 +256,62 @@ pub fn gui(ui: &imgui::Ui, state: &mut State, camera: &mut Camera, names: &[imgu
 
             ui.checkbox(im_str!("Lock camera"), &mut camera.camera_lock);
             ui.checkbox(im_str!("Use noise texture"), &mut state.noise);
+            ui.checkbox(
+                im_str!("Use lookup table from file"),
+                &mut state.lookup_table,
+            );
 
             if ui.small_button(im_str!("Volume dataset:")) {
                 ui.open_popup(im_str!("Select:"));
             }
             ui.same_line(0.0);
-            ui.text(&names[state.selection]);
+            match names.get(state.selection) {
+                Some(name) => ui.text(name),
+                None => ui.text(im_str!("None")),
+            }
             ui.popup(im_str!("Select:"), || {
                 for (index, name) in names.iter().enumerate() {
                     if imgui::Selectable::new(name)
                         .flags(imgui::SelectableFlags::empty())
                         .selected(false)
                         .size([0.0, 0.0])
-                        .build(&ui)
+                        .build(ui)
                     {
                         state.selection = index;
                     }
                 }
             });
 
+            if times.len() > 1 {
+                let label = if state.playing {
+                    im_str!("Pause")
+                } else {
+                    im_str!("Play")
+                };
+                if ui.small_button(label) {
+                    state.playing = !state.playing;
+                    // Playing from the last timestep starts over
+                    if state.playing && state.frame + 1 >= times.len() {
+                        state.frame = 0;
+                    }
+                }
+                ui.same_line(0.0);
+                ui.checkbox(im_str!("Loop"), &mut state.looping);
+                imgui::Slider::new(im_str!("Timesteps per second"))
+                    .range(0.5..=30.0)
+                    .build(ui, &mut state.speed);
+
+                let mut frame = state.frame.min(times.len() - 1) as i32;
+                imgui::Slider::new(im_str!("Timestep"))
+                    .range(0..=times.len() as i32 - 1)
+                    .build(ui, &mut frame);
+                state.frame = frame as usize;
+                ui.text(im_str!("Time: {}", times[state.frame]));
+            }
+
+            ui.checkbox(im_str!("Show surfaces"), &mut state.show_surfaces);
+            imgui::ColorEdit::new(im_str!("Surface colour"), &mut state.surface_colour).build(ui);
+
             ui.text(im_str!("Framerate: {:.2}", state.frame_rate));
 
             ui.text(im_str!("Select projection mode:"));
@@ -234,46 +320,46 @@ pub fn gui(ui: &imgui::Ui, state: &mut State, camera: &mut Camera, names: &[imgu
             ui.same_line(0.0);
             ui.radio_button(im_str!("ISO"), &mut state.mip_or_iso, 1);
 
-            if imgui::CollapsingHeader::new(im_str!("Maximum Intensity Projection")).build(&ui) {
-                imgui::ColorEdit::new(im_str!("MIP colour"), &mut state.mip_colour).build(&ui);
+            if imgui::CollapsingHeader::new(im_str!("Maximum Intensity Projection")).build(ui) {
+                imgui::ColorEdit::new(im_str!("MIP colour"), &mut state.mip_colour).build(ui);
             }
 
-            if imgui::CollapsingHeader::new(im_str!("Isosurface Extraction")).build(&ui) {
+            if imgui::CollapsingHeader::new(im_str!("Isosurface Extraction")).build(ui) {
                 imgui::Slider::new(im_str!("Isovalue"))
                     .range(0.0..=1.0)
-                    .build(&ui, &mut state.isovalue);
+                    .build(ui, &mut state.isovalue);
                 imgui::Slider::new(im_str!("Gradient step length"))
                     .range(0.0..=1.0 / 10.0)
-                    .build(&ui, &mut state.grad_step);
+                    .build(ui, &mut state.grad_step);
 
                 ui.separator();
 
-                imgui::ColorEdit::new(im_str!("Ambient colour"), &mut state.amb_colour).build(&ui);
+                imgui::ColorEdit::new(im_str!("Ambient colour"), &mut state.amb_colour).build(ui);
                 imgui::Slider::new(im_str!("Ambient strength"))
                     .range(0.0..=1.0)
-                    .build(&ui, &mut state.amb_str);
+                    .build(ui, &mut state.amb_str);
 
-                imgui::ColorEdit::new(im_str!("Diffuse colour"), &mut state.dif_colour).build(&ui);
+                imgui::ColorEdit::new(im_str!("Diffuse colour"), &mut state.dif_colour).build(ui);
                 imgui::Slider::new(im_str!("Diffuse strength"))
                     .range(0.0..=1.0)
-                    .build(&ui, &mut state.dif_str);
+                    .build(ui, &mut state.dif_str);
 
-                imgui::ColorEdit::new(im_str!("Specular colour"), &mut state.spe_colour).build(&ui);
+                imgui::ColorEdit::new(im_str!("Specular colour"), &mut state.spe_colour).build(ui);
                 imgui::Slider::new(im_str!("Specular strength"))
                     .range(0.0..=0.03)
-                    .build(&ui, &mut state.spe_str);
+                    .build(ui, &mut state.spe_str);
                 imgui::Slider::new(im_str!("Specular alpha"))
                     .range(10.0..=900.0)
-                    .build(&ui, &mut state.alpha);
+                    .build(ui, &mut state.alpha);
 
                 ui.separator();
 
                 imgui::Slider::new(im_str!("Light vector theta"))
                     .range(0.0..=std::f32::consts::PI)
-                    .build(&ui, &mut state.light[0]);
+                    .build(ui, &mut state.light[0]);
                 imgui::Slider::new(im_str!("Light vector phi"))
                     .range(0.0..=2.0 * std::f32::consts::PI)
-                    .build(&ui, &mut state.light[1]);
+                    .build(ui, &mut state.light[1]);
             }
         });
 }
@@ -0,0 +1,124 @@
+use glium::{implement_vertex, index::PrimitiveType, IndexBuffer, VertexBuffer};
+use vtk_parser::PolyData;
+
+#[derive(Copy, Clone)]
+pub struct SurfaceVertex {
+    position: (f32, f32, f32),
+    normal: (f32, f32, f32),
+}
+implement_vertex!(SurfaceVertex, position, normal);
+
+pub const VERT_SHADER: &str = r#"
+#version 140
+
+in vec3 position;
+in vec3 normal;
+
+uniform mat4 u_mvp;
+uniform mat4 u_model;
+
+out vec3 v_normal;
+out vec3 v_pos;
+
+void main() {
+    vec4 P = u_model*vec4(position, 1.0);
+    v_pos = P.xyz;
+    v_normal = transpose(inverse(mat3(u_model)))*normal;
+    gl_Position = u_mvp*P;
+}
+"#;
+
+pub const FRAG_SHADER: &str = r#"
+#version 140
+
+in vec3 v_normal;
+in vec3 v_pos;
+
+uniform vec3 u_colour;
+uniform vec3 u_L;
+uniform bool u_lit;
+
+out vec4 colour;
+out vec4 position;
+
+void main() {
+    float light = 1.0;
+    if (u_lit) {
+        // Both sides of the surface are lit
+        light = 0.2 + 0.8*abs(dot(normalize(v_normal), u_L));
+    }
+    colour = vec4(light*u_colour, 1.0);
+    // Position in the same coordinates as the front and back faces
+    position = vec4(0.5 + 0.5*v_pos, 1.0);
+}
+"#;
+
+/// Geometry of a polydata file, uploaded to the GPU
+pub struct Surface {
+    pub vertices: VertexBuffer<SurfaceVertex>,
+    pub triangles: IndexBuffer<u32>,
+    pub lines: IndexBuffer<u32>,
+    pub points: IndexBuffer<u32>,
+}
+
+impl Surface {
+    pub fn new<F: glium::backend::Facade>(
+        display: &F,
+        data: &PolyData,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let triangles = data.triangles();
+
+        // Area weighted vertex normals
+        let mut normals = vec![[0.0f64; 3]; data.points.len()];
+        for t in &triangles {
+            let p = |i: usize| data.points[t[i]];
+            let (a, b, c) = (p(0), p(1), p(2));
+            let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+            let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+            let n = [
+                u[1] * v[2] - u[2] * v[1],
+                u[2] * v[0] - u[0] * v[2],
+                u[0] * v[1] - u[1] * v[0],
+            ];
+            for &i in t {
+                for axis in 0..3 {
+                    normals[i][axis] += n[axis];
+                }
+            }
+        }
+
+        let vertices: Vec<SurfaceVertex> = data
+            .points
+            .iter()
+            .zip(&normals)
+            .map(|(p, n)| SurfaceVertex {
+                position: (p[0] as f32, p[1] as f32, p[2] as f32),
+                normal: (n[0] as f32, n[1] as f32, n[2] as f32),
+            })
+            .collect();
+
+        let triangles: Vec<u32> = triangles.iter().flatten().map(|&i| i as u32).collect();
+
+        let mut lines = Vec::new();
+        for line in data.lines.iter() {
+            for segment in line.windows(2) {
+                lines.push(segment[0] as u32);
+                lines.push(segment[1] as u32);
+            }
+        }
+
+        let points: Vec<u32> = data
+            .vertices
+            .connectivity
+            .iter()
+            .map(|&i| i as u32)
+            .collect();
+
+        Ok(Self {
+            vertices: VertexBuffer::new(display, &vertices)?,
+            triangles: IndexBuffer::new(display, PrimitiveType::TrianglesList, &triangles)?,
+            lines: IndexBuffer::new(display, PrimitiveType::LinesList, &lines)?,
+            points: IndexBuffer::new(display, PrimitiveType::Points, &points)?,
+        })
+    }
+}
@@ -0,0 +1,358 @@
+use glium::texture::pixel_buffer::PixelBuffer;
+use glium::texture::{
+    ClientFormat, MipmapsOption, PixelValue, RawImage3d, Texture1d, Texture3d,
+    UncompressedFloatFormat,
+};
+use std::borrow::Cow;
+use std::error::Error;
+use std::path::Path;
+use vtk_parser::{
+    ArrayInfo, Buffer, DataArray, Datatype, LookupTable, PolyData, Probe, RawLayout, ScalarType,
+    SliceReader, StructuredPoints, VTKparseError,
+};
+
+/// Largest number of points along an axis when resampling onto a uniform grid
+const MAX_RESOLUTION: u32 = 256;
+
+/// Volumes in files larger than this are streamed to the GPU one slice at a
+/// time, rather than read into memory
+const STREAM_SIZE: u64 = 256 << 20;
+
+/// A dataset which can be shown, either as a volume to raycast or as a
+/// surface drawn inside the volume
+pub enum Dataset {
+    Volume(StructuredPoints),
+    Surface(PolyData),
+    /// A large volume, of which only the metadata has been read
+    Streamed(Probe),
+    /// Volumes of the timesteps of a file, with their time
+    Series(Vec<(f64, StructuredPoints)>),
+}
+
+/// Reads a dataset and turns it into something which can be shown
+///
+/// Datasets which are not uniform are resampled onto a uniform grid. Large
+/// structured points files are only probed, to be uploaded with
+/// `upload_streamed`. NRRD (.nrrd, .nhdr) and MetaImage (.mha, .mhd) files
+/// are read as volumes, and NIfTI-1 files (.nii, .hdr) as a series of them.
+/// Folders are read as a series of DICOM slices, and files with a `.layout`
+/// sidecar as raw values
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Dataset, VTKparseError> {
+    let path = path.as_ref();
+    if path.is_dir() {
+        return Ok(Dataset::Volume(vtk_parser::read_dicom_dir(path)?));
+    }
+    let sidecar = RawLayout::sidecar(path);
+    if sidecar.exists() {
+        let layout = RawLayout::read_sidecar(sidecar)?;
+        return Ok(Dataset::Volume(vtk_parser::read_raw(path, &layout)?));
+    }
+    match extension(path).as_str() {
+        "nrrd" | "nhdr" => return Ok(Dataset::Volume(vtk_parser::read_nrrd(path)?)),
+        "mha" | "mhd" => return Ok(Dataset::Volume(vtk_parser::read_mhd(path)?)),
+        "nii" | "hdr" => {
+            let mut volumes = vtk_parser::read_nifti(path)?;
+            if volumes.len() == 1 {
+                return Ok(Dataset::Volume(volumes.pop().unwrap().1));
+            }
+            return Ok(Dataset::Series(volumes));
+        }
+        _ => {}
+    }
+    if std::fs::metadata(path)?.len() > STREAM_SIZE {
+        if let Ok(probe) = vtk_parser::probe_file(path) {
+            if probe.scalars().is_some() {
+                return Ok(Dataset::Streamed(probe));
+            }
+        }
+    }
+
+    let data = vtk_parser::read_file(path)?;
+
+    match data.dataset {
+        Datatype::StructuredPoints(x) => Ok(Dataset::Volume(x)),
+        Datatype::RectilinearGrid(x) => Ok(Dataset::Volume(
+            x.resample(x.default_resolution(MAX_RESOLUTION)),
+        )),
+        Datatype::StructuredGrid(x) => Ok(Dataset::Volume(
+            x.resample(x.default_resolution(MAX_RESOLUTION))?,
+        )),
+        Datatype::UnstructuredGrid(x) => Ok(Dataset::Volume(
+            x.resample(x.default_resolution(MAX_RESOLUTION)),
+        )),
+        Datatype::PolyData(x) => Ok(Dataset::Surface(x)),
+        _ => Err(VTKparseError::NotImplemented(
+            "Rendering of this dataset type".to_string(),
+        )),
+    }
+}
+
+/// A scalar array to raycast, with a texture for every timestep
+pub struct Volume {
+    pub array: String,
+    pub frames: Vec<Texture3d>,
+    pub times: Vec<f64>,
+    pub lookup: Option<Texture1d>,
+    pub model: [[f32; 4]; 4],
+}
+
+/// Adds a timestep of an array to the volumes of a series, which start at
+/// `first`
+///
+/// Arrays are matched by name between the frames of a series
+pub fn add(
+    volumes: &mut Vec<Volume>,
+    first: usize,
+    array: &str,
+    time: f64,
+    texture: Texture3d,
+    lookup: Option<Texture1d>,
+    model: [[f32; 4]; 4],
+) {
+    match volumes[first..].iter_mut().find(|x| x.array == array) {
+        Some(volume) => {
+            volume.frames.push(texture);
+            volume.times.push(time);
+        }
+        None => volumes.push(Volume {
+            array: array.to_string(),
+            frames: vec![texture],
+            times: vec![time],
+            lookup,
+            model,
+        }),
+    }
+}
+
+/// Uploads every point scalar array of a timestep, with its lookup table
+pub fn add_points<F: glium::backend::Facade>(
+    display: &F,
+    volumes: &mut Vec<Volume>,
+    first: usize,
+    time: f64,
+    data: &StructuredPoints,
+) -> Result<(), glium::texture::TextureCreationError> {
+    let model = model_matrix(data.dims, data.origin, data.spacing);
+    for array in data.attributes.point_scalars() {
+        let texture = upload(display, data.dims, array)?;
+        let lookup = match data.attributes.lookup_table(array) {
+            Some(table) => Some(upload_lookup_table(display, table)?),
+            None => None,
+        };
+        add(volumes, first, &array.name, time, texture, lookup, model);
+    }
+    Ok(())
+}
+
+/// Whether a file failed to load because its format is not known, so its
+/// layout can be asked for
+pub fn unrecognised(path: &Path, err: &VTKparseError) -> bool {
+    let err = match err {
+        VTKparseError::Located(_, err) => err,
+        err => err,
+    };
+    if RawLayout::sidecar(path).exists() {
+        return true;
+    }
+    let known = matches!(
+        extension(path).as_str(),
+        "vtk" | "vti" | "nrrd" | "nhdr" | "mha" | "mhd" | "nii" | "hdr"
+    );
+    !known && !path.is_dir() && matches!(err, VTKparseError::UnknownFormat(_))
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map_or_else(String::new, |x| x.to_string_lossy().to_lowercase())
+}
+
+/// Maps the coordinates of the volume onto the cube [-1, 1]^3 which is raycast
+pub fn model_matrix(
+    dims: (u32, u32, u32),
+    origin: (f32, f32, f32),
+    spacing: (f32, f32, f32),
+) -> [[f32; 4]; 4] {
+    let axis = |n: u32, origin: f32, spacing: f32| {
+        let extent = if n > 1 {
+            (n - 1) as f32 * spacing
+        } else {
+            spacing
+        };
+        let scale = 2.0 / extent;
+        (scale, -origin * scale - 1.0)
+    };
+    let (sx, tx) = axis(dims.0, origin.0, spacing.0);
+    let (sy, ty) = axis(dims.1, origin.1, spacing.1);
+    let (sz, tz) = axis(dims.2, origin.2, spacing.2);
+
+    [
+        [sx, 0.0, 0.0, 0.0],
+        [0.0, sy, 0.0, 0.0],
+        [0.0, 0.0, sz, 0.0],
+        [tx, ty, tz, 1.0],
+    ]
+}
+
+/// Uploads a scalar array of a volume to the GPU
+///
+/// Unsigned bytes and shorts are uploaded as normalised integers, all
+/// other types are rescaled to [0, 1] using the range of the data. Only
+/// the first component of multi-component scalars is used
+pub fn upload<F: glium::backend::Facade>(
+    display: &F,
+    dims: (u32, u32, u32),
+    data: &DataArray,
+) -> Result<Texture3d, glium::texture::TextureCreationError> {
+    let (width, height, depth) = dims;
+
+    if data.numcomp == 1 {
+        if let Buffer::U8(ref x) = data.data {
+            let image = RawImage3d {
+                data: Cow::Borrowed(&x[..]),
+                width,
+                height,
+                depth,
+                format: ClientFormat::U8,
+            };
+            return Texture3d::with_mipmaps(display, image, MipmapsOption::NoMipmap);
+        }
+        if let Buffer::U16(ref x) = data.data {
+            let image = RawImage3d {
+                data: Cow::Borrowed(&x[..]),
+                width,
+                height,
+                depth,
+                format: ClientFormat::U16,
+            };
+            return Texture3d::with_mipmaps(display, image, MipmapsOption::NoMipmap);
+        }
+    }
+
+    let image = RawImage3d {
+        data: Cow::Owned(normalise(&data.data, data.numcomp, data.data.range())),
+        width,
+        height,
+        depth,
+        format: ClientFormat::F32,
+    };
+    Texture3d::with_format(
+        display,
+        image,
+        glium::texture::UncompressedFloatFormat::F32,
+        MipmapsOption::NoMipmap,
+    )
+}
+
+/// Uploads a scalar array of a large volume, reading it from the file one
+/// slice at a time
+///
+/// Types are handled like in `upload`, but other types than unsigned bytes
+/// and shorts need an extra pass over the file to find the range
+pub fn upload_streamed<F: glium::backend::Facade>(
+    display: &F,
+    path: &Path,
+    probe: &Probe,
+    array: &ArrayInfo,
+) -> Result<Texture3d, Box<dyn Error>> {
+    let slices = || SliceReader::open(path, probe, array);
+
+    // Uploads from pixel buffers expect rows aligned to four bytes
+    let aligned = (probe.dims.0 as usize * array.scalar_type.size()).is_multiple_of(4);
+    if array.numcomp == 1 && aligned {
+        match array.scalar_type {
+            ScalarType::UnsignedChar => {
+                return upload_slices(
+                    display,
+                    probe.dims,
+                    UncompressedFloatFormat::U8,
+                    slices()?,
+                    |x| Cow::Borrowed(x.as_u8().unwrap()),
+                )
+            }
+            ScalarType::UnsignedShort => {
+                return upload_slices(
+                    display,
+                    probe.dims,
+                    UncompressedFloatFormat::U16,
+                    slices()?,
+                    |x| Cow::Borrowed(x.as_u16().unwrap()),
+                )
+            }
+            _ => {}
+        }
+    }
+
+    let mut range: Option<(f64, f64)> = None;
+    for slice in slices()? {
+        if let Some((lo, hi)) = slice?.range() {
+            range = Some(range.map_or((lo, hi), |(a, b)| (a.min(lo), b.max(hi))));
+        }
+    }
+    upload_slices(
+        display,
+        probe.dims,
+        UncompressedFloatFormat::F32,
+        slices()?,
+        |x| Cow::Owned(normalise(x, array.numcomp, range)),
+    )
+}
+
+/// Fills a texture from slices along z, converted to texels by `texels`
+fn upload_slices<F, T, I, C>(
+    display: &F,
+    dims: (u32, u32, u32),
+    format: UncompressedFloatFormat,
+    slices: I,
+    texels: C,
+) -> Result<Texture3d, Box<dyn Error>>
+where
+    F: glium::backend::Facade,
+    T: PixelValue,
+    I: Iterator<Item = Result<Buffer, VTKparseError>>,
+    C: Fn(&Buffer) -> Cow<[T]>,
+{
+    let (width, height, depth) = dims;
+    let texture = Texture3d::empty_with_format(
+        display,
+        format,
+        MipmapsOption::NoMipmap,
+        width,
+        height,
+        depth,
+    )?;
+    let pixels = PixelBuffer::new_empty(display, width as usize * height as usize);
+
+    for (z, slice) in (0..depth).zip(slices) {
+        pixels.write(&texels(&slice?));
+        texture.main_level().raw_upload_from_pixel_buffer(
+            pixels.as_slice(),
+            0..width,
+            0..height,
+            z..z + 1,
+        );
+    }
+    Ok(texture)
+}
+
+/// Uploads a colour table, indexed by the normalised scalar value
+pub fn upload_lookup_table<F: glium::backend::Facade>(
+    display: &F,
+    table: &LookupTable,
+) -> Result<Texture1d, glium::texture::TextureCreationError> {
+    let colours: Vec<(f32, f32, f32, f32)> = table
+        .colours
+        .iter()
+        .map(|c| (c[0], c[1], c[2], c[3]))
+        .collect();
+    Texture1d::new(display, colours)
+}
+
+/// First component of every tuple, rescaled from `range` to [0, 1]
+fn normalise(buffer: &Buffer, numcomp: usize, range: Option<(f64, f64)>) -> Vec<f32> {
+    let (lo, hi) = range.unwrap_or((0.0, 1.0));
+    let scale = if hi > lo { 1.0 / (hi - lo) } else { 1.0 };
+
+    (0..buffer.len() / numcomp)
+        .map(|i| ((buffer.get(i * numcomp).unwrap() - lo) * scale) as f32)
+        .collect()
+}